@@ -36,7 +36,7 @@ fn default_handler(
 enum ObjectType<'a> {
     Collection(&'a str),
     Item { col: &'a str, item: &'a str },
-    Session(&'a str),
+    Session(#[allow(dead_code)] &'a str),
 }
 
 fn get_object_type_and_id<'a>(path: &'a ObjectPath<&'a str>) -> Option<ObjectType<'a>> {
@@ -148,55 +148,24 @@ fn item_handler(
     }
 }
 
+/// Only gets calls to org.freedesktop.Secret.Session.Close, the dispatcher answers everything else with an error
 #[allow(clippy::unnecessary_wraps)]
-fn session_handler(
+fn close_session_handler(
     ctx: &mut &mut Context,
     matches: Matches,
     msg: &MarshalledMessage,
     _env: &mut MyHandleEnv,
 ) -> HandleResult<()> {
     println!(
-        "Woohoo the session handler got called for: {:?}",
+        "Woohoo the close session handler got called for: {:?}",
         msg.dynheader
     );
     let ses_id = matches
         .matches
-        .get(":collection_id")
+        .get(":session_id")
         .expect("Called session interface without a match on \":session_id\"");
-    match msg
-        .dynheader
-        .interface
-        .as_ref()
-        .expect("NO INTERFACE :(")
-        .as_str()
-    {
-        "org.freedesktop.Secret.Session" => {
-            match msg
-                .dynheader
-                .member
-                .as_ref()
-                .expect("NO MEMBER :(")
-                .as_str()
-            {
-                "Close" => {
-                    ctx.service.close_session(ses_id).unwrap();
-                    Ok(None)
-                }
-                other => {
-                    println!("Unknown method called: {}", other);
-                    Ok(Some(rustbus::standard_messages::unknown_method(
-                        &msg.dynheader,
-                    )))
-                }
-            }
-        }
-        other => {
-            println!("Unknown interface called: {}", other);
-            Ok(Some(rustbus::standard_messages::unknown_method(
-                &msg.dynheader,
-            )))
-        }
-    }
+    ctx.service.close_session(ses_id).unwrap();
+    Ok(None)
 }
 
 fn main() {
@@ -233,7 +202,6 @@ fn main() {
     let service_handler = Box::new(service_handler);
     let collection_handler = Box::new(collection_handler);
     let item_handler = Box::new(item_handler);
-    let session_handler = Box::new(session_handler);
     dp_con.add_handler("/org/freedesktop/secrets", service_handler);
     dp_con.add_handler(
         "/org/freedesktop/secrets/collection/:collection_id",
@@ -243,9 +211,12 @@ fn main() {
         "/org/freedesktop/secrets/collection/:collection_id/:item_id",
         item_handler,
     );
-    dp_con.add_handler(
+    dp_con.add_method_handler(
         "/org/freedesktop/secrets/session/:session_id",
-        session_handler,
+        "org.freedesktop.Secret.Session",
+        "Close",
+        Some(""),
+        Box::new(close_session_handler),
     );
 
     dp_con.run().unwrap();
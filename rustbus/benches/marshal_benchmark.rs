@@ -12,10 +12,10 @@ fn marsh(msg: &rustbus::message_builder::MarshalledMessage, buf: &mut Vec<u8>) {
 }
 
 fn unmarshal(buf: &[u8]) {
-    let (hdrbytes, header) = unmarshal_header(&buf, 0).unwrap();
-    let (dynhdrbytes, dynheader) = unmarshal_dynamic_header(&header, &buf, hdrbytes).unwrap();
+    let (hdrbytes, header) = unmarshal_header(buf, 0).unwrap();
+    let (dynhdrbytes, dynheader) = unmarshal_dynamic_header(&header, buf, hdrbytes).unwrap();
     let (_, _unmarshed_msg) =
-        unmarshal_next_message(&header, dynheader, &buf, hdrbytes + dynhdrbytes).unwrap();
+        unmarshal_next_message(&header, dynheader, buf, hdrbytes + dynhdrbytes).unwrap();
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
@@ -75,5 +75,46 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("unmarshal", |b| b.iter(|| unmarshal(black_box(&buf))));
 }
 
-criterion_group!(benches, criterion_benchmark);
+fn fixed_size_array_benchmark(c: &mut Criterion) {
+    let samples: Vec<f64> = (0..64 * 1024).map(|x| x as f64 / 3.0).collect();
+
+    let mut msg = rustbus::message_builder::MessageBuilder::new()
+        .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+        .build();
+    let mut old_style = rustbus::message_builder::MarshalledMessageBody::new();
+    let sample_params: Vec<Param> = samples
+        .iter()
+        .map(|x| rustbus::params::Base::Double(x.to_bits()).into())
+        .collect();
+    let sample_params: Param = Container::make_array("d", &mut sample_params.into_iter())
+        .unwrap()
+        .into();
+    c.bench_function("marshal_f64_array_params", |b| {
+        b.iter(|| {
+            old_style.reset();
+            old_style.push_old_param(black_box(&sample_params)).unwrap();
+        })
+    });
+    c.bench_function("marshal_f64_array_slice", |b| {
+        b.iter(|| {
+            msg.body.reset();
+            msg.body.push_param(black_box(samples.as_slice())).unwrap();
+        })
+    });
+
+    c.bench_function("unmarshal_f64_array_params", |b| {
+        b.iter(|| {
+            let mut parser = msg.body.parser();
+            black_box(parser.get_param().unwrap());
+        })
+    });
+    c.bench_function("unmarshal_f64_array_vec", |b| {
+        b.iter(|| black_box(msg.body.parser().get::<Vec<f64>>().unwrap()))
+    });
+    c.bench_function("unmarshal_f64_array_borrowed", |b| {
+        b.iter(|| black_box(msg.body.parser().get::<&[f64]>().unwrap()))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark, fixed_size_array_benchmark);
 criterion_main!(benches);
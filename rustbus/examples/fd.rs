@@ -4,7 +4,7 @@ use rustbus::{
 };
 
 use std::io::Write;
-use std::os::unix::io::FromRawFd;
+use std::os::unix::io::{AsFd, OwnedFd};
 
 fn main() -> Result<(), rustbus::connection::Error> {
     if std::env::args()
@@ -20,7 +20,7 @@ fn main() -> Result<(), rustbus::connection::Error> {
             .write_all()
             .unwrap();
 
-        con.send_message(&mut standard_messages::add_match("type='signal'".into()))?
+        con.send_message(&mut standard_messages::add_match("type='signal'"))?
             .write_all()
             .unwrap();
 
@@ -31,17 +31,16 @@ fn main() -> Result<(), rustbus::connection::Error> {
                 .dynheader
                 .interface
                 .eq(&Some("io.killing.spark".to_owned()))
+                && signal.dynheader.member.eq(&Some("TestSignal".to_owned()))
             {
-                if signal.dynheader.member.eq(&Some("TestSignal".to_owned())) {
-                    break signal;
-                }
+                break signal;
             }
         };
 
         println!("Got signal: {:?}", sig);
-        let fd: rustbus::wire::UnixFd = sig.body.parser().get().unwrap();
+        let fd: OwnedFd = sig.body.parser().get().unwrap();
 
-        let mut file = unsafe { std::fs::File::from_raw_fd(fd.take_raw_fd().unwrap()) };
+        let mut file = std::fs::File::from(fd);
         file.write_all(
             format!(
                 "This is a line from process with pid: {}\n",
@@ -62,16 +61,15 @@ fn send_fd() -> Result<(), rustbus::connection::Error> {
         .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
         .build();
 
-    use std::os::unix::io::AsRawFd;
-    let stdin_fd = std::io::stdin();
-    sig.body.push_param((&stdin_fd) as &dyn AsRawFd).unwrap();
+    let stdin = std::io::stdin();
+    sig.body.push_param(stdin.as_fd()).unwrap();
     sig.dynheader.num_fds = Some(1);
-    con.send.send_message(&mut sig)?.write_all().unwrap();
+    con.send.send_message(&sig)?.write_all().unwrap();
 
-    let mut sig = MessageBuilder::new()
+    let sig = MessageBuilder::new()
         .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
         .build();
-    con.send.send_message(&mut sig)?.write_all().unwrap();
+    con.send.send_message(&sig)?.write_all().unwrap();
 
     println!("Printing stuff from stdin. The following is input from the other process!");
     let mut line = String::new();
@@ -60,7 +60,7 @@ fn make_message() -> MarshalledMessage {
 
 fn dump_message(path: &str, msg: &MarshalledMessage) {
     let mut hdrbuf = vec![];
-    rustbus::wire::marshal::marshal(msg, 0, &mut hdrbuf).unwrap();
+    rustbus::wire::marshal::marshal(msg, 1, &mut hdrbuf).unwrap();
 
     let mut file = std::fs::File::create(path).unwrap();
     file.write_all(&hdrbuf).unwrap();
@@ -0,0 +1,321 @@
+//! A command line tool for talking to services on the bus, in the spirit of busctl and dbus-send.
+//!
+//! Arguments for calls are given as a signature followed by the values, like busctl expects them:
+//! `rustbus-ctl call org.freedesktop.DBus /org/freedesktop/DBus org.freedesktop.DBus.GetNameOwner s org.freedesktop.DBus`
+
+use rustbus::connection::monitor_conn::MonitorConn;
+use rustbus::connection::Timeout;
+use rustbus::message_builder::{MarshalledMessage, MessageType};
+use rustbus::params::{text, Container, Param};
+use rustbus::{format, MatchRule, MessageBuilder, RpcConn};
+
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+type CtlResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+const USAGE: &str = "Usage: rustbus-ctl [OPTIONS] COMMAND ...
+
+Options:
+  --session           Talk to the session bus (default)
+  --system            Talk to the system bus
+  --json              Print messages like busctl --json=pretty
+  --timeout=SECS      Timeout for calls (default 25)
+
+Commands:
+  call DEST PATH IFACE.MEMBER [SIG [ARGS...]]
+  emit PATH IFACE.MEMBER [SIG [ARGS...]]
+  get-property DEST PATH IFACE PROPERTY
+  set-property DEST PATH IFACE PROPERTY SIG ARGS...
+  introspect DEST PATH
+  tree DEST
+  monitor [MATCHRULE...]";
+
+struct Options {
+    system: bool,
+    json: bool,
+    timeout: Duration,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(err) = run(args) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Vec<String>) -> CtlResult<()> {
+    let mut options = Options {
+        system: false,
+        json: false,
+        timeout: Duration::from_secs(25),
+    };
+    let mut args: VecDeque<String> = args.into();
+    while let Some(arg) = args.front().filter(|arg| arg.starts_with("--")).cloned() {
+        args.pop_front();
+        match arg.as_str() {
+            "--session" => options.system = false,
+            "--system" => options.system = true,
+            "--json" => options.json = true,
+            "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => match arg.strip_prefix("--timeout=") {
+                Some(secs) => {
+                    options.timeout = Duration::try_from_secs_f64(secs.parse()?)
+                        .map_err(|_| format!("Invalid timeout {}", secs))?
+                }
+                None => return Err(format!("Unknown option {}\n\n{}", arg, USAGE).into()),
+            },
+        }
+    }
+
+    let command = args.pop_front().ok_or(USAGE)?;
+    let args: Vec<String> = args.into();
+    match command.as_str() {
+        "call" => call(&options, &args),
+        "emit" => emit(&options, &args),
+        "get-property" => get_property(&options, &args),
+        "set-property" => set_property(&options, &args),
+        "introspect" => introspect(&options, &args),
+        "tree" => tree(&options, &args),
+        "monitor" => monitor(&options, &args),
+        _ => Err(format!("Unknown command {}\n\n{}", command, USAGE).into()),
+    }
+}
+
+fn connect(options: &Options) -> CtlResult<RpcConn> {
+    let timeout = Timeout::Duration(options.timeout);
+    let conn = if options.system {
+        RpcConn::system_conn(timeout)?
+    } else {
+        RpcConn::session_conn(timeout)?
+    };
+    Ok(conn)
+}
+
+fn need_args<'a>(args: &'a [String], count: usize, usage: &str) -> CtlResult<&'a [String]> {
+    if args.len() < count {
+        return Err(format!("Usage: rustbus-ctl {}", usage).into());
+    }
+    Ok(&args[..count])
+}
+
+/// Split IFACE.MEMBER at the last dot
+fn split_member(name: &str) -> CtlResult<(&str, &str)> {
+    name.rsplit_once('.')
+        .ok_or_else(|| format!("Expected INTERFACE.MEMBER but got {}", name).into())
+}
+
+/// The arguments after the signature, parsed as busctl does. See `rustbus::params::text` for the syntax.
+fn parse_args(sig_and_args: &[String]) -> CtlResult<Vec<Param<'static, 'static>>> {
+    match sig_and_args.split_first() {
+        Some((sig, args)) => Ok(text::parse_args(sig, args)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Send the call and return the reply, or the error the callee answered with
+fn send_call(
+    conn: &mut RpcConn,
+    options: &Options,
+    mut msg: MarshalledMessage,
+) -> CtlResult<MarshalledMessage> {
+    let timeout = Timeout::Duration(options.timeout);
+    let serial = conn
+        .send_message(&mut msg)?
+        .write(timeout)
+        .map_err(|(_, err)| err)?;
+    let reply = conn.wait_response(serial, timeout)?;
+    if reply.typ == MessageType::Error {
+        let name = reply.dynheader.error_name.clone().unwrap_or_default();
+        return Err(match reply.body.parser().get::<String>() {
+            Ok(message) => format!("Call failed: {}: {}", name, message),
+            Err(_) => format!("Call failed: {}", name),
+        }
+        .into());
+    }
+    Ok(reply)
+}
+
+fn print_body(options: &Options, msg: &MarshalledMessage) -> CtlResult<()> {
+    if options.json {
+        print!("{}", format::busctl_json(msg, None)?);
+    } else {
+        print!("{}", format::dbus_monitor_body(msg)?);
+    }
+    Ok(())
+}
+
+fn call(options: &Options, args: &[String]) -> CtlResult<()> {
+    let usage = "call DEST PATH IFACE.MEMBER [SIG [ARGS...]]";
+    let fixed = need_args(args, 3, usage)?;
+    let (interface, member) = split_member(&fixed[2])?;
+    let mut msg = MessageBuilder::new()
+        .call(member)
+        .with_interface(interface)
+        .on(fixed[1].as_str())
+        .at(fixed[0].as_str())
+        .build();
+    msg.body.push_old_params(&parse_args(&args[3..])?)?;
+
+    let mut conn = connect(options)?;
+    let reply = send_call(&mut conn, options, msg)?;
+    print_body(options, &reply)
+}
+
+fn emit(options: &Options, args: &[String]) -> CtlResult<()> {
+    let usage = "emit PATH IFACE.MEMBER [SIG [ARGS...]]";
+    let fixed = need_args(args, 2, usage)?;
+    let (interface, member) = split_member(&fixed[1])?;
+    let mut msg = MessageBuilder::new()
+        .signal(interface, member, fixed[0].as_str())
+        .build();
+    msg.body.push_old_params(&parse_args(&args[2..])?)?;
+
+    let mut conn = connect(options)?;
+    conn.send_message(&mut msg)?
+        .write_all()
+        .map_err(|(_, err)| err)?;
+    Ok(())
+}
+
+fn properties_call(dest: &str, path: &str, member: &str) -> MarshalledMessage {
+    MessageBuilder::new()
+        .call(member)
+        .with_interface("org.freedesktop.DBus.Properties")
+        .on(path)
+        .at(dest)
+        .build()
+}
+
+fn get_property(options: &Options, args: &[String]) -> CtlResult<()> {
+    let usage = "get-property DEST PATH IFACE PROPERTY";
+    let fixed = need_args(args, 4, usage)?;
+    let mut msg = properties_call(&fixed[0], &fixed[1], "Get");
+    msg.body.push_param2(fixed[2].as_str(), fixed[3].as_str())?;
+
+    let mut conn = connect(options)?;
+    let reply = send_call(&mut conn, options, msg)?;
+    print_body(options, &reply)
+}
+
+fn set_property(options: &Options, args: &[String]) -> CtlResult<()> {
+    let usage = "set-property DEST PATH IFACE PROPERTY SIG ARGS...";
+    let fixed = need_args(args, 6, usage)?;
+    let mut values = parse_args(&args[4..])?;
+    if values.len() != 1 {
+        return Err("A property has exactly one value".into());
+    }
+    let mut msg = properties_call(&fixed[0], &fixed[1], "Set");
+    msg.body.push_param2(fixed[2].as_str(), fixed[3].as_str())?;
+    msg.body
+        .push_old_param(&Param::Container(Container::make_variant(values.remove(0))))?;
+
+    let mut conn = connect(options)?;
+    send_call(&mut conn, options, msg)?;
+    Ok(())
+}
+
+fn introspect_xml(
+    conn: &mut RpcConn,
+    options: &Options,
+    dest: &str,
+    path: &str,
+) -> CtlResult<String> {
+    let msg = MessageBuilder::new()
+        .call("Introspect")
+        .with_interface("org.freedesktop.DBus.Introspectable")
+        .on(path)
+        .at(dest)
+        .build();
+    let reply = send_call(conn, options, msg)?;
+    Ok(reply.body.parser().get::<String>()?)
+}
+
+fn introspect(options: &Options, args: &[String]) -> CtlResult<()> {
+    let fixed = need_args(args, 2, "introspect DEST PATH")?;
+    let mut conn = connect(options)?;
+    println!(
+        "{}",
+        introspect_xml(&mut conn, options, &fixed[0], &fixed[1])?
+    );
+    Ok(())
+}
+
+/// The names of the child nodes in introspection data. The first node element is the object itself.
+fn child_nodes(xml: &str) -> Vec<String> {
+    let mut children = Vec::new();
+    let mut rest = match xml.find("<node") {
+        Some(start) => &xml[start + "<node".len()..],
+        None => return children,
+    };
+    while let Some(start) = rest.find("<node") {
+        rest = &rest[start + "<node".len()..];
+        let tag_end = rest.find('>').unwrap_or(rest.len());
+        let tag = &rest[..tag_end];
+        if let Some(name_start) = tag.find("name=\"") {
+            let name = &tag[name_start + "name=\"".len()..];
+            if let Some(name_end) = name.find('"') {
+                children.push(name[..name_end].to_owned());
+            }
+        }
+    }
+    children
+}
+
+fn tree(options: &Options, args: &[String]) -> CtlResult<()> {
+    let fixed = need_args(args, 1, "tree DEST")?;
+    let mut conn = connect(options)?;
+    let mut paths = VecDeque::new();
+    paths.push_back("/".to_owned());
+    while let Some(path) = paths.pop_front() {
+        println!("{}", path);
+        let xml = match introspect_xml(&mut conn, options, &fixed[0], &path) {
+            Ok(xml) => xml,
+            // the other objects may still be fine
+            Err(err) if path != "/" => {
+                eprintln!("Failed to introspect {}: {}", path, err);
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        let mut children = child_nodes(&xml);
+        children.sort();
+        for child in children.into_iter().rev() {
+            let child = if path == "/" {
+                format!("/{}", child)
+            } else {
+                format!("{}/{}", path, child)
+            };
+            // depth first, so children are printed right below their parent
+            paths.push_front(child);
+        }
+    }
+    Ok(())
+}
+
+fn monitor(options: &Options, args: &[String]) -> CtlResult<()> {
+    let rules = args
+        .iter()
+        .map(|rule| rule.parse::<MatchRule>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("Invalid match rule: {:?}", err))?;
+    let timeout = Timeout::Duration(options.timeout);
+    let mut monitor = if options.system {
+        MonitorConn::system_conn(&rules, timeout)?
+    } else {
+        MonitorConn::session_conn(&rules, timeout)?
+    };
+    loop {
+        let (received, msg): (SystemTime, MarshalledMessage) =
+            monitor.next_marshalled(Timeout::Infinite)?;
+        if options.json {
+            print!("{}", format::busctl_json(&msg, Some(received))?);
+        } else {
+            println!("{}", format::dbus_monitor(&msg, Some(received))?);
+        }
+    }
+}
@@ -2,11 +2,21 @@
 //!
 //! * ll_conn is the basic send and recive primitives used to build the other connection types
 //! * dispatch_conn is meant for services that need to dispatch calls to different handlers
+//! * threaded_dispatch_conn does the same as dispatch_conn, but runs the handlers on a pool of threads
 //! * rpc_conn is meant for clients that make calls to services on the bus
+//! * name_owner helps with owning well known names on the bus
+//! * name_watcher keeps track of the owners of names on the bus
+//! * bus_proxy offers typed calls to the org.freedesktop.DBus interface of the bus
+//! * monitor_conn observes the messages on the bus like dbus-monitor
 
+pub mod bus_proxy;
 pub mod dispatch_conn;
 pub mod ll_conn;
+pub mod monitor_conn;
+pub mod name_owner;
+pub mod name_watcher;
 pub mod rpc_conn;
+pub mod threaded_dispatch_conn;
 
 use std::path::PathBuf;
 use std::time;
@@ -51,6 +61,18 @@ pub enum Error {
     TimedOut,
     #[error("Connection has been closed by the other side")]
     ConnectionClosed,
+    #[error("File descriptors sent with a message were discarded because there were more than the configured maximum")]
+    UnixFdsTruncated,
+    #[error("The message header announced {0} file descriptors but {1} were received")]
+    UnixFdCountMismatch(u32, usize),
+    #[error("The bus rejected the match rule: {0}")]
+    MatchRuleRejected(String),
+    #[error("The bus refused to make the connection a monitor: {0}")]
+    MonitorRejected(String),
+    #[error("The worker threads handling the messages stopped")]
+    WorkersStopped,
+    #[error("Another thread panicked while using the connection")]
+    Poisoned,
 }
 
 impl std::convert::From<std::io::Error> for Error {
@@ -172,7 +194,7 @@ mod tests {
                 // stripped from the session bus' determined path.
                 assert_eq!("/tmp/dbus-test-not-exist", path);
             }
-            _ => assert!(false, "expected Error::PathDoesNotExist"),
+            _ => panic!("expected Error::PathDoesNotExist"),
         }
 
         let addr = parse_dbus_addr_str(abstract_path).unwrap();
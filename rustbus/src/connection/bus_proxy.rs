@@ -0,0 +1,253 @@
+//! Typed calls to the org.freedesktop.DBus interface of the bus
+//!
+//! The `standard_messages` only build the messages for the most common calls, the `BusProxy` makes the calls and
+//! decodes the replies. Requesting and releasing names is done with the `NameOwner`, see `name_owner`.
+//!
+//! ```rust,no_run
+//! use rustbus::connection::bus_proxy::BusProxy;
+//! use rustbus::connection::Timeout;
+//! use rustbus::RpcConn;
+//!
+//! let mut con = RpcConn::session_conn(Timeout::Infinite).unwrap();
+//! let mut bus = BusProxy::new(&mut con, Timeout::Infinite);
+//! for name in bus.list_names().unwrap() {
+//!     if name.starts_with(':') {
+//!         continue;
+//!     }
+//!     let owner = bus.get_name_owner(&name).unwrap();
+//!     let creds = bus.get_connection_credentials(&owner).unwrap();
+//!     println!("{} is owned by {} (pid {:?})", name, owner, creds.process_id);
+//! }
+//! ```
+
+use super::rpc_conn::{CallError, RpcConn};
+use super::*;
+use crate::message_builder::{MarshalArgs, UnmarshalArgs};
+use crate::standard_messages;
+use crate::wire::errors::UnmarshalError;
+use crate::wire::UnixFd;
+
+use std::collections::HashMap;
+
+// The values the bus uses in the replies of GetConnectionCredentials and the properties
+mod values {
+    use crate::wire::UnixFd;
+
+    crate::dbus_variant_sig!(BusValue, U32 => u32; U32List => Vec<u32>; Bytes => Vec<u8>; Str => String; StrList => Vec<String>; Fd => UnixFd);
+}
+use values::BusValue;
+
+/// What the bus answered to StartServiceByName
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartServiceReply {
+    /// The service was started
+    Success,
+    /// The name already had an owner
+    AlreadyRunning,
+}
+
+impl StartServiceReply {
+    pub fn from_u32(reply: u32) -> Option<Self> {
+        match reply {
+            standard_messages::DBUS_START_REPLY_SUCCESS => Some(Self::Success),
+            standard_messages::DBUS_START_REPLY_ALREADY_RUNNING => Some(Self::AlreadyRunning),
+            _ => None,
+        }
+    }
+}
+
+/// The credentials of a connection as returned by GetConnectionCredentials. The bus only reports the credentials it
+/// knows, the others are None.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConnectionCredentials {
+    pub unix_user_id: Option<u32>,
+    pub unix_group_ids: Option<Vec<u32>>,
+    pub process_id: Option<u32>,
+    /// A pidfd for the process, only sent by some buses and only if the connection supports fd passing
+    pub process_fd: Option<UnixFd>,
+    pub windows_sid: Option<String>,
+    /// The label of the process as returned by the LSM, including the terminating nul byte
+    pub linux_security_label: Option<Vec<u8>>,
+}
+
+impl ConnectionCredentials {
+    fn from_map(map: HashMap<String, BusValue>) -> std::result::Result<Self, UnmarshalError> {
+        let mut creds = Self::default();
+        for (key, value) in map {
+            match (key.as_str(), value) {
+                ("UnixUserID", BusValue::U32(uid)) => creds.unix_user_id = Some(uid),
+                ("UnixGroupIDs", BusValue::U32List(gids)) => creds.unix_group_ids = Some(gids),
+                ("ProcessID", BusValue::U32(pid)) => creds.process_id = Some(pid),
+                ("ProcessFD", BusValue::Fd(fd)) => creds.process_fd = Some(fd),
+                ("WindowsSID", BusValue::Str(sid)) => creds.windows_sid = Some(sid),
+                ("LinuxSecurityLabel", BusValue::Bytes(label)) => {
+                    creds.linux_security_label = Some(label)
+                }
+                ("UnixUserID", _)
+                | ("UnixGroupIDs", _)
+                | ("ProcessID", _)
+                | ("ProcessFD", _)
+                | ("WindowsSID", _)
+                | ("LinuxSecurityLabel", _) => return Err(UnmarshalError::WrongSignature),
+                // the spec allows the bus to add more credentials later
+                _ => {}
+            }
+        }
+        Ok(creds)
+    }
+}
+
+/// Makes typed calls to the bus itself. See the module docs.
+pub struct BusProxy<'a> {
+    conn: &'a mut RpcConn,
+    timeout: Timeout,
+}
+
+impl<'a> BusProxy<'a> {
+    /// The timeout is used for each of the calls
+    pub fn new(conn: &'a mut RpcConn, timeout: Timeout) -> Self {
+        Self { conn, timeout }
+    }
+
+    pub fn timeout(&self) -> Timeout {
+        self.timeout
+    }
+
+    pub fn set_timeout(&mut self, timeout: Timeout) {
+        self.timeout = timeout;
+    }
+
+    /// The connection the calls are made on
+    pub fn conn(&mut self) -> &mut RpcConn {
+        self.conn
+    }
+
+    fn call<Args, Ret>(&mut self, member: &str, args: Args) -> std::result::Result<Ret, CallError>
+    where
+        Args: MarshalArgs,
+        Ret: for<'b> UnmarshalArgs<'b>,
+    {
+        self.conn.call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            member,
+            args,
+            self.timeout,
+        )
+    }
+
+    fn get_property(&mut self, name: &str) -> std::result::Result<BusValue, CallError> {
+        let (value,): (BusValue,) = self.conn.call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus.Properties",
+            "Get",
+            ("org.freedesktop.DBus", name),
+            self.timeout,
+        )?;
+        Ok(value)
+    }
+
+    /// The unique name of the owner of the name. Fails with `WellKnownError::NameHasNoOwner` if nobody owns it.
+    pub fn get_name_owner(&mut self, name: &str) -> std::result::Result<String, CallError> {
+        self.call("GetNameOwner", (name,)).map(|(owner,)| owner)
+    }
+
+    pub fn name_has_owner(&mut self, name: &str) -> std::result::Result<bool, CallError> {
+        self.call("NameHasOwner", (name,)).map(|(owned,)| owned)
+    }
+
+    /// All names that currently have an owner, including the unique names
+    pub fn list_names(&mut self) -> std::result::Result<Vec<String>, CallError> {
+        self.call("ListNames", ()).map(|(names,)| names)
+    }
+
+    /// All names that can be started by the bus
+    pub fn list_activatable_names(&mut self) -> std::result::Result<Vec<String>, CallError> {
+        self.call("ListActivatableNames", ()).map(|(names,)| names)
+    }
+
+    /// The unique names of the connections waiting for the name, starting with the current owner
+    pub fn list_queued_owners(
+        &mut self,
+        name: &str,
+    ) -> std::result::Result<Vec<String>, CallError> {
+        self.call("ListQueuedOwners", (name,))
+            .map(|(owners,)| owners)
+    }
+
+    pub fn start_service_by_name(
+        &mut self,
+        name: &str,
+    ) -> std::result::Result<StartServiceReply, CallError> {
+        // the flags are currently unused by the spec
+        let (reply,): (u32,) = self.call("StartServiceByName", (name, 0u32))?;
+        StartServiceReply::from_u32(reply)
+            .ok_or(CallError::Unmarshal(UnmarshalError::NoMatchingVariantFound))
+    }
+
+    /// Add the variables to the environment of services started by the bus
+    pub fn update_activation_environment(
+        &mut self,
+        environment: &HashMap<String, String>,
+    ) -> std::result::Result<(), CallError> {
+        self.call("UpdateActivationEnvironment", (environment,))
+    }
+
+    pub fn get_connection_unix_user(&mut self, name: &str) -> std::result::Result<u32, CallError> {
+        self.call("GetConnectionUnixUser", (name,))
+            .map(|(uid,)| uid)
+    }
+
+    pub fn get_connection_unix_process_id(
+        &mut self,
+        name: &str,
+    ) -> std::result::Result<u32, CallError> {
+        self.call("GetConnectionUnixProcessID", (name,))
+            .map(|(pid,)| pid)
+    }
+
+    pub fn get_connection_credentials(
+        &mut self,
+        name: &str,
+    ) -> std::result::Result<ConnectionCredentials, CallError> {
+        let (map,): (HashMap<String, BusValue>,) =
+            self.call("GetConnectionCredentials", (name,))?;
+        ConnectionCredentials::from_map(map).map_err(CallError::Unmarshal)
+    }
+
+    /// The Solaris audit session data of the connection
+    pub fn get_adt_audit_session_data(
+        &mut self,
+        name: &str,
+    ) -> std::result::Result<Vec<u8>, CallError> {
+        self.call("GetAdtAuditSessionData", (name,))
+            .map(|(data,)| data)
+    }
+
+    /// The unique id of the bus
+    pub fn get_id(&mut self) -> std::result::Result<String, CallError> {
+        self.call("GetId", ()).map(|(id,)| id)
+    }
+
+    pub fn reload_config(&mut self) -> std::result::Result<(), CallError> {
+        self.call("ReloadConfig", ())
+    }
+
+    /// The optional features the bus supports, e.g. "SystemdActivation"
+    pub fn features(&mut self) -> std::result::Result<Vec<String>, CallError> {
+        match self.get_property("Features")? {
+            BusValue::StrList(features) => Ok(features),
+            _ => Err(CallError::Unmarshal(UnmarshalError::WrongSignature)),
+        }
+    }
+
+    /// The optional interfaces the bus object implements in addition to org.freedesktop.DBus
+    pub fn interfaces(&mut self) -> std::result::Result<Vec<String>, CallError> {
+        match self.get_property("Interfaces")? {
+            BusValue::StrList(interfaces) => Ok(interfaces),
+            _ => Err(CallError::Unmarshal(UnmarshalError::WrongSignature)),
+        }
+    }
+}
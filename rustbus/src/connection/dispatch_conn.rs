@@ -8,13 +8,25 @@ use super::ll_conn::DuplexConn;
 use super::ll_conn::RecvConn;
 use super::ll_conn::SendConn;
 use super::*;
+use crate::match_rule::MatchRule;
+use crate::message_builder::DynamicHeader;
 use crate::message_builder::MarshalledMessage;
+use crate::message_builder::MessageType;
+use crate::standard_messages::DBusError;
+use crate::standard_messages::WellKnownError;
 use crate::wire::errors::MarshalError;
 use crate::wire::errors::UnmarshalError;
 
+use nix::poll::{poll, PollFd, PollFlags};
 use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 #[derive(Eq, PartialEq, Hash)]
 enum PathPart {
@@ -91,17 +103,54 @@ impl ObjectPathPattern {
     }
 }
 
-pub struct PathMatcher<UserData, UserError: std::fmt::Debug> {
-    pathes: HashMap<ObjectPathPattern, Box<HandleFn<UserData, UserError>>>,
+/// The handlers registered for one object path pattern
+struct ObjectHandlers<H> {
+    /// Gets all messages for the object that are not handled by one of the methods
+    fallback: Option<H>,
+    methods: Vec<MethodHandler<H>>,
 }
 
-impl<UserData, UserError: std::fmt::Debug> Default for PathMatcher<UserData, UserError> {
+struct MethodHandler<H> {
+    interface: String,
+    member: String,
+    signature: Option<String>,
+    handler: H,
+}
+
+impl<H> Default for ObjectHandlers<H> {
+    fn default() -> Self {
+        Self {
+            fallback: None,
+            methods: Vec::new(),
+        }
+    }
+}
+
+/// Where a message should go, as determined by `Router::route`
+pub enum Route<'a, H> {
+    /// Call this handler
+    Handler(Matches, &'a mut H),
+    /// The object exists but the call can not be handled. Send this error reply.
+    Error(Box<MarshalledMessage>),
+    /// No object matches the path of the message
+    NoObject,
+}
+
+/// Maps object paths (and interfaces and members) to handlers of type `H`
+pub struct Router<H> {
+    pathes: HashMap<ObjectPathPattern, ObjectHandlers<H>>,
+}
+
+/// The router used by the `DispatchConn`
+pub type PathMatcher<UserData, UserError> = Router<Box<HandleFn<UserData, UserError>>>;
+
+impl<H> Default for Router<H> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<UserData, UserError: std::fmt::Debug> PathMatcher<UserData, UserError> {
+impl<H> Router<H> {
     pub fn new() -> Self {
         Self {
             pathes: HashMap::new(),
@@ -117,22 +166,161 @@ impl<UserData, UserError: std::fmt::Debug> PathMatcher<UserData, UserError> {
     /// 1. /io.killingspark/API/v1/ManagedObjects/1234/SetName
     /// 1. /io.killingspark/API/v1/ManagedObjects/CoolID/SetName
     /// 1. /io.killingspark/API/v1/ManagedObjects/1D5_4R3_FUN/SetName
-    pub fn insert(&mut self, path_pattern: &str, handler: Box<HandleFn<UserData, UserError>>) {
+    ///
+    /// The handler gets all messages for matching objects that are not handled by a handler added with `insert_method`.
+    pub fn insert(&mut self, path_pattern: &str, handler: H) {
         self.pathes
-            .insert(ObjectPathPattern::new(path_pattern), handler);
+            .entry(ObjectPathPattern::new(path_pattern))
+            .or_default()
+            .fallback = Some(handler);
     }
 
-    pub fn get_match(
+    /// Add a handler for one method of the objects matching the pattern (see `insert` for the patterns).
+    /// If a signature is given, calls with other arguments are answered with an `InvalidArgs` error.
+    pub fn insert_method(
         &mut self,
-        query: &str,
-    ) -> Option<(Matches, &mut HandleFn<UserData, UserError>)> {
-        for (path, fun) in &mut self.pathes {
-            if let Some(matches) = path.matches(query) {
-                return Some((matches, fun.as_mut()));
+        path_pattern: &str,
+        interface: &str,
+        member: &str,
+        signature: Option<&str>,
+        handler: H,
+    ) {
+        let object = self
+            .pathes
+            .entry(ObjectPathPattern::new(path_pattern))
+            .or_default();
+        object.methods.retain(|method| {
+            !(method.interface == interface
+                && method.member == member
+                && method.signature.as_deref() == signature)
+        });
+        object.methods.push(MethodHandler {
+            interface: interface.to_owned(),
+            member: member.to_owned(),
+            signature: signature.map(str::to_owned),
+            handler,
+        });
+    }
+
+    /// Find the handler that was added with `insert` for an object path
+    pub fn get_match(&mut self, query: &str) -> Option<(Matches, &mut H)> {
+        for (path, object) in &mut self.pathes {
+            if let Some(fallback) = &mut object.fallback {
+                if let Some(matches) = path.matches(query) {
+                    return Some((matches, fallback));
+                }
             }
         }
         None
     }
+
+    /// Find the handler for a message.
+    ///
+    /// Calls are routed to the method handlers of the object by interface, member and signature. Calls without an
+    /// interface are routed to the method with that member, if exactly one interface of the object has one.
+    /// All other messages, and calls no method handler fits, go to the handler added with `insert`. If there is none
+    /// the appropriate error reply (`UnknownInterface`, `UnknownMethod` or `InvalidArgs`) is returned.
+    pub fn route(&mut self, msg: &MarshalledMessage) -> Route<'_, H> {
+        let query = match &msg.dynheader.object {
+            Some(obj) => obj,
+            None => return Route::NoObject,
+        };
+        let (matches, object) = match self
+            .pathes
+            .iter_mut()
+            .find_map(|(path, object)| path.matches(query).map(|matches| (matches, object)))
+        {
+            Some(found) => found,
+            None => return Route::NoObject,
+        };
+
+        if msg.typ != MessageType::Call || object.methods.is_empty() {
+            return match &mut object.fallback {
+                Some(fallback) => Route::Handler(matches, fallback),
+                None => Route::Error(Box::new(crate::standard_messages::unknown_method(
+                    &msg.dynheader,
+                ))),
+            };
+        }
+
+        let member = msg.dynheader.member.as_deref().unwrap_or("");
+        let interface = match msg.dynheader.interface.as_deref() {
+            Some(interface) => Some(interface),
+            None => {
+                let mut interfaces = object
+                    .methods
+                    .iter()
+                    .filter(|method| method.member == member)
+                    .map(|method| method.interface.as_str());
+                let first = interfaces.next();
+                if interfaces.any(|other| Some(other) != first) {
+                    // The spec leaves it open what to do here, so be explicit about it
+                    let text = format!(
+                        "Method {} exists on more than one interface of object {}, the call needs an interface",
+                        member, query
+                    );
+                    return Route::Error(Box::new(
+                        msg.dynheader
+                            .make_error_response(WellKnownError::UnknownMethod.name(), Some(text)),
+                    ));
+                }
+                first
+            }
+        };
+
+        let mut interface_known = false;
+        let mut member_known = None;
+        let mut found = None;
+        for (idx, method) in object.methods.iter().enumerate() {
+            if Some(method.interface.as_str()) != interface {
+                continue;
+            }
+            interface_known = true;
+            if method.member != member {
+                continue;
+            }
+            match &method.signature {
+                Some(sig) if sig != msg.get_sig() => member_known = Some(sig.as_str()),
+                _ => {
+                    found = Some(idx);
+                    break;
+                }
+            }
+        }
+
+        if let Some(idx) = found {
+            return Route::Handler(matches, &mut object.methods[idx].handler);
+        }
+        if let Some(fallback) = &mut object.fallback {
+            return Route::Handler(matches, fallback);
+        }
+        let error = if let Some(sig) = member_known {
+            crate::standard_messages::invalid_args(&msg.dynheader, Some(sig))
+        } else if interface_known || interface.is_none() {
+            crate::standard_messages::unknown_method(&msg.dynheader)
+        } else {
+            crate::standard_messages::unknown_interface(&msg.dynheader)
+        };
+        Route::Error(Box::new(error))
+    }
+
+    /// Add all handlers of the other router, replacing handlers that were registered for the same things
+    pub(crate) fn merge(&mut self, other: Self) {
+        for (path, handlers) in other.pathes {
+            let object = self.pathes.entry(path).or_default();
+            if handlers.fallback.is_some() {
+                object.fallback = handlers.fallback;
+            }
+            for method in handlers.methods {
+                object.methods.retain(|existing| {
+                    !(existing.interface == method.interface
+                        && existing.member == method.member
+                        && existing.signature == method.signature)
+                });
+                object.methods.push(method);
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -142,6 +330,24 @@ pub enum HandleError<UserError: std::fmt::Debug> {
     Connection(crate::connection::Error),
     User(UserError),
 }
+impl<UserError: std::fmt::Debug + DBusError> HandleError<UserError> {
+    /// The error reply the caller of a failed call gets. Errors in the connection itself can not be answered, for those
+    /// this returns None.
+    pub fn to_error_reply(&self, call: &DynamicHeader) -> Option<MarshalledMessage> {
+        match self {
+            HandleError::User(err) => Some(err.to_error_reply(call)),
+            // most likely the call did not have the arguments the handler expected
+            HandleError::Unmarshal(err) => Some(
+                call.make_error_response(WellKnownError::InvalidArgs.name(), Some(err.to_string())),
+            ),
+            HandleError::Marshal(err) => {
+                Some(call.make_error_response(WellKnownError::Failed.name(), Some(err.to_string())))
+            }
+            HandleError::Connection(_) => None,
+        }
+    }
+}
+
 impl<UserError: std::fmt::Debug> From<MarshalError> for HandleError<UserError> {
     fn from(err: MarshalError) -> Self {
         HandleError::Marshal(err)
@@ -162,6 +368,125 @@ impl<UserError: std::fmt::Debug> From<crate::connection::Error> for HandleError<
 pub struct HandleEnvironment<UserData, UserError: std::fmt::Debug> {
     pub conn: Arc<Mutex<SendConn>>,
     pub new_dispatches: PathMatcher<UserData, UserError>,
+    reply_deferred: bool,
+    new_reply_handlers: Vec<(u32, Box<HandleFn<UserData, UserError>>)>,
+}
+
+impl<UserData, UserError: std::fmt::Debug> HandleEnvironment<UserData, UserError> {
+    fn new(conn: Arc<Mutex<SendConn>>) -> Self {
+        Self {
+            conn,
+            new_dispatches: PathMatcher::new(),
+            reply_deferred: false,
+            new_reply_handlers: Vec::new(),
+        }
+    }
+
+    /// Send a call and let the dispatcher pass the reply (or error) to the handler once it arrives.
+    /// See `DispatchConn::call`.
+    pub fn call(
+        &mut self,
+        msg: &MarshalledMessage,
+        handler: Box<HandleFn<UserData, UserError>>,
+    ) -> Result<u32> {
+        let serial = send_call(&self.conn, msg)?;
+        self.new_reply_handlers.push((serial, handler));
+        Ok(serial)
+    }
+
+    /// Do not reply to the call now. The dispatcher will not send a response for this call, whatever the handler
+    /// returns. Instead the returned `PendingReply` has to be completed later, from any thread.
+    pub fn defer_reply(&mut self, call: &MarshalledMessage) -> PendingReply {
+        self.reply_deferred = true;
+        PendingReply::new(self.conn.clone(), call)
+    }
+}
+
+/// The name of the error a `PendingReply` sends if it is dropped without being completed
+pub const PENDING_REPLY_DROP_ERROR: &str = WellKnownError::NoReply.name();
+
+/// A reply to a call that will be sent later. Created by `HandleEnvironment::defer_reply`.
+///
+/// It remembers who made the call and which serial it had, so it can be completed from anywhere, e.g. after a user
+/// confirmed a prompt or a child process exited. If it is dropped without being completed, an error is sent to the
+/// caller (`org.freedesktop.DBus.Error.NoReply` unless changed with `set_drop_error`) so the caller does not wait forever.
+///
+/// If the caller set the NO_REPLY_EXPECTED flag nothing is sent at all.
+pub struct PendingReply {
+    conn: Arc<Mutex<SendConn>>,
+    call: DynamicHeader,
+    expects_reply: bool,
+    drop_error: Option<(String, Option<String>)>,
+}
+
+impl PendingReply {
+    pub fn new(conn: Arc<Mutex<SendConn>>, call: &MarshalledMessage) -> Self {
+        Self {
+            conn,
+            call: call.dynheader.clone(),
+            expects_reply: call.expects_reply(),
+            drop_error: Some((PENDING_REPLY_DROP_ERROR.to_owned(), None)),
+        }
+    }
+
+    /// The header of the call this will reply to
+    pub fn call(&self) -> &DynamicHeader {
+        &self.call
+    }
+
+    /// An empty reply to the call, to fill with the return values
+    pub fn make_response(&self) -> MarshalledMessage {
+        self.call.make_response()
+    }
+
+    /// An error reply to the call
+    pub fn make_error_response<S: Into<String>>(
+        &self,
+        error_name: S,
+        error_msg: Option<String>,
+    ) -> MarshalledMessage {
+        self.call.make_error_response(error_name, error_msg)
+    }
+
+    /// Change the error that is sent when this is dropped without being completed.
+    pub fn set_drop_error<S: Into<String>>(&mut self, error_name: S, error_msg: Option<String>) {
+        self.drop_error = Some((error_name.into(), error_msg));
+    }
+
+    /// Send the reply (use `make_response` or `make_error_response` to create it). Returns the serial the reply was
+    /// sent with, or None if the caller did not expect a reply. Fails with `Error::Poisoned` if another thread panicked
+    /// while sending through the connection.
+    pub fn complete(mut self, reply: MarshalledMessage) -> Result<Option<u32>> {
+        self.drop_error = None;
+        self.send(&reply, false)
+    }
+
+    fn send(&self, reply: &MarshalledMessage, ignore_poison: bool) -> Result<Option<u32>> {
+        if !self.expects_reply {
+            return Ok(None);
+        }
+        let mut conn = match self.conn.lock() {
+            Ok(conn) => conn,
+            Err(poisoned) if ignore_poison => poisoned.into_inner(),
+            Err(_) => return Err(Error::Poisoned),
+        };
+        let serial = conn
+            .send_message(reply)?
+            .write_all()
+            .map_err(ll_conn::force_finish_on_error)?;
+        Ok(Some(serial))
+    }
+}
+
+impl Drop for PendingReply {
+    fn drop(&mut self) {
+        if let Some((name, text)) = self.drop_error.take() {
+            let error = self.call.make_error_response(name, text);
+            // there is nobody left to report a failure to. This may run while unwinding, so a poisoned lock must not
+            // cause a second panic.
+            let _ = self.send(&error, true);
+        }
+    }
 }
 pub type HandleResult<UserError> =
     std::result::Result<Option<MarshalledMessage>, HandleError<UserError>>;
@@ -172,12 +497,29 @@ pub type HandleFn<UserData, UserError> = dyn FnMut(
     &mut HandleEnvironment<UserData, UserError>,
 ) -> HandleResult<UserError>;
 
+fn send_call(conn: &Mutex<SendConn>, msg: &MarshalledMessage) -> Result<u32> {
+    conn.lock()
+        .unwrap()
+        .send_message(msg)?
+        .write_all()
+        .map_err(ll_conn::force_finish_on_error)
+}
+
+/// Gets called when the dispatcher was idle for a while, see `DispatchConn::set_idle_handler`
+pub type IdleFn<UserData> = dyn FnMut(&mut UserData, &Arc<Mutex<SendConn>>);
+
 pub struct DispatchConn<HandlerCtx, HandlerError: std::fmt::Debug> {
     recv: RecvConn,
     send: Arc<Mutex<SendConn>>,
     objects: PathMatcher<HandlerCtx, HandlerError>,
     default_handler: Box<HandleFn<HandlerCtx, HandlerError>>,
     ctx: HandlerCtx,
+    signal_handlers: Vec<(MatchRule, Box<HandleFn<HandlerCtx, HandlerError>>)>,
+    reply_handlers: HashMap<u32, Box<HandleFn<HandlerCtx, HandlerError>>>,
+    // messages that arrived while waiting for something else, they are dispatched first
+    backlog: VecDeque<MarshalledMessage>,
+    idle_handler: Option<(Duration, Box<IdleFn<HandlerCtx>>)>,
+    exit_on_idle: Option<Duration>,
 }
 
 impl<UserData, UserError: std::fmt::Debug> DispatchConn<UserData, UserError> {
@@ -192,90 +534,418 @@ impl<UserData, UserError: std::fmt::Debug> DispatchConn<UserData, UserError> {
             objects: PathMatcher::new(),
             default_handler,
             ctx,
+            signal_handlers: Vec::new(),
+            reply_handlers: HashMap::new(),
+            backlog: VecDeque::new(),
+            idle_handler: None,
+            exit_on_idle: None,
         }
     }
 
+    /// Add a handler for all messages to objects matching the path pattern. See `PathMatcher::insert`.
     pub fn add_handler(&mut self, path: &str, handler: Box<HandleFn<UserData, UserError>>) {
         self.objects.insert(path, handler);
     }
 
+    /// Add a handler for one method of the objects matching the path pattern. See `PathMatcher::insert_method`.
+    pub fn add_method_handler(
+        &mut self,
+        path: &str,
+        interface: &str,
+        member: &str,
+        signature: Option<&str>,
+        handler: Box<HandleFn<UserData, UserError>>,
+    ) {
+        self.objects
+            .insert_method(path, interface, member, signature, handler);
+    }
+
+    /// Add a handler for all signals matching the rule. This sends the rule to the bus with AddMatch and waits
+    /// for the bus to accept it. Messages that arrive in the meantime are dispatched once the dispatcher runs.
+    ///
+    /// Signals are never answered, so whatever the handler returns is ignored, except for connection errors
+    /// which end `run`. Signals no handler matches go to the default handler.
+    pub fn add_signal_handler(
+        &mut self,
+        rule: MatchRule,
+        handler: Box<HandleFn<UserData, UserError>>,
+        timeout: Timeout,
+    ) -> Result<()> {
+        let start_time = Instant::now();
+        let serial = send_call(
+            &self.send,
+            &crate::standard_messages::add_match(&rule.to_string()),
+        )?;
+        loop {
+            let msg = self
+                .recv
+                .get_next_message(calc_timeout_left(&start_time, timeout)?)?;
+            let is_answer = matches!(msg.typ, MessageType::Reply | MessageType::Error)
+                && msg.dynheader.response_serial == Some(serial);
+            if !is_answer {
+                self.backlog.push_back(msg);
+                continue;
+            }
+            if msg.typ == MessageType::Error {
+                return Err(Error::MatchRuleRejected(
+                    msg.dynheader.error_name.unwrap_or_default(),
+                ));
+            }
+            break;
+        }
+        self.signal_handlers.push((rule, handler));
+        Ok(())
+    }
+
+    /// Send a call and pass the reply (or error) to the handler once it arrives. Like for signals, whatever the
+    /// handler returns is ignored, except for connection errors which end `run`.
+    ///
+    /// Replies and errors no handler waits for go to the default handler.
+    pub fn call(
+        &mut self,
+        msg: &MarshalledMessage,
+        handler: Box<HandleFn<UserData, UserError>>,
+    ) -> Result<u32> {
+        let serial = send_call(&self.send, msg)?;
+        self.reply_handlers.insert(serial, handler);
+        Ok(serial)
+    }
+
+    /// Set a handler that is called whenever no message arrived for the given time, e.g. to do periodic work
+    /// in between calls. It is called again after the same time if there still was no message.
+    pub fn set_idle_handler(&mut self, after: Duration, handler: Box<IdleFn<UserData>>) {
+        self.idle_handler = Some((after, handler));
+    }
+
+    /// Stop running if there was no call for the given time. This is useful for bus activated services, that
+    /// should exit if nobody needs them and will be started again by the bus once they are called.
+    pub fn set_exit_on_idle(&mut self, after: Option<Duration>) {
+        self.exit_on_idle = after;
+    }
+}
+
+/// Running the dispatcher needs to know how to answer failed calls, so the error type has to implement `DBusError`.
+impl<UserData, UserError: std::fmt::Debug + DBusError> DispatchConn<UserData, UserError> {
     /// Endless loop that takes messages and dispatches them to the setup
-    /// handlers. If any errors occur they will be returned. Depending on the error you may
-    /// choose to just call this function again. Note that you are expected to send a meaningful
-    /// error message. The offending message will be returned alongside the error.
+    /// handlers. Errors returned by the handlers are sent to the caller as error replies (see `DBusError`
+    /// and `HandleError::to_error_reply`) and the loop continues. Only errors of the connection itself end
+    /// the loop and are returned, alongside the message that was handled at the time if there was one.
     ///
     /// This also sends reponses back to the callers, returned by the handlers. If the handlers did
-    /// return None, it sends a default response with no content.
+    /// return None, it sends a default response with no content. If the caller set the NO_REPLY_EXPECTED
+    /// flag, or the handler deferred the reply with `HandleEnvironment::defer_reply`, no response is sent at all.
+    /// Only calls are answered, signals and replies are passed to the handlers set with `add_signal_handler` and
+    /// `call`.
+    ///
+    /// This only returns Ok if `set_exit_on_idle` was used and there were no calls for that long.
     #[allow(clippy::result_large_err)]
     pub fn run(
         &mut self,
     ) -> std::result::Result<(), (Option<MarshalledMessage>, HandleError<UserError>)> {
+        self.run_loop(None).map(|_| ())
+    }
+
+    /// Like `run` but also returns once the `StopHandle` was triggered. The message that is handled at that
+    /// time is finished first, including sending the reply.
+    #[allow(clippy::result_large_err)]
+    pub fn run_until(
+        &mut self,
+        stop: &StopHandle,
+    ) -> std::result::Result<StopReason, (Option<MarshalledMessage>, HandleError<UserError>)> {
+        self.run_loop(Some(stop))
+    }
+
+    /// Wait for one message for at most the timeout and dispatch it, like `run` does. Returns whether there was
+    /// a message. Use this to integrate the dispatching into your own loop. Idle handlers are not called by this.
+    #[allow(clippy::result_large_err)]
+    pub fn run_once(
+        &mut self,
+        timeout: Timeout,
+    ) -> std::result::Result<bool, (Option<MarshalledMessage>, HandleError<UserError>)> {
+        self.next_message(timeout, true).map(|typ| typ.is_some())
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn run_loop(
+        &mut self,
+        stop: Option<&StopHandle>,
+    ) -> std::result::Result<StopReason, (Option<MarshalledMessage>, HandleError<UserError>)> {
+        let mut last_call = Instant::now();
+        let mut last_activity = Instant::now();
         loop {
-            match self.recv.get_next_message(Timeout::Infinite) {
-                Ok(msg) => {
-                    let mut env = HandleEnvironment {
-                        conn: self.send.clone(),
-                        new_dispatches: PathMatcher::new(),
-                    };
-                    let result = {
-                        if let Some(obj) = &msg.dynheader.object {
-                            if let Some((matches, handler)) = self.objects.get_match(obj) {
-                                handler(&mut self.ctx, matches, &msg, &mut env)
-                            } else {
-                                (self.default_handler)(
-                                    &mut self.ctx,
-                                    Matches::default(),
-                                    &msg,
-                                    &mut env,
-                                )
-                            }
-                        } else {
-                            (self.default_handler)(
-                                &mut self.ctx,
-                                Matches::default(),
-                                &msg,
-                                &mut env,
-                            )
-                        }
-                    };
+            if stop.map(StopHandle::is_stopped).unwrap_or(false) {
+                return Ok(StopReason::Stopped);
+            }
 
-                    if result.is_ok() {
-                        // apply the new pathes established in the handler
-                        for (k, v) in env.new_dispatches.pathes.into_iter() {
-                            self.objects.pathes.insert(k, v);
-                        }
-                    }
+            if let Some(typ) = self.next_message(Timeout::Nonblock, false)? {
+                last_activity = Instant::now();
+                if typ == MessageType::Call {
+                    last_call = last_activity;
+                }
+                continue;
+            }
 
-                    let mut send_conn = self.send.lock().unwrap();
-
-                    match result {
-                        Ok(Some(response)) => {
-                            let ctx = match send_conn.send_message(&response) {
-                                Ok(ctx) => ctx,
-                                Err(e) => return Err((Some(msg), e.into())),
-                            };
-                            ctx.write_all()
-                                .map_err(|(ctx, e)| ll_conn::force_finish_on_error((ctx, e)))
-                                .map_err(|e| (Some(msg), e.into()))?
-                        }
+            // find out how long to wait for the next message until something else has to happen
+            let now = Instant::now();
+            let mut wait = None;
+            if let Some(after) = self.exit_on_idle {
+                let idle = now.duration_since(last_call);
+                if idle >= after {
+                    return Ok(StopReason::Idle);
+                }
+                wait = Some(after - idle);
+            }
+            if let Some((after, handler)) = &mut self.idle_handler {
+                let mut idle = now.duration_since(last_activity);
+                if idle >= *after {
+                    handler(&mut self.ctx, &self.send);
+                    last_activity = now;
+                    idle = Duration::from_secs(0);
+                }
+                let left = *after - idle;
+                wait = Some(wait.map_or(left, |wait: Duration| wait.min(left)));
+            }
 
-                        Ok(None) => {
-                            let response = msg.dynheader.make_response();
-                            let ctx = match send_conn.send_message(&response) {
-                                Ok(ctx) => ctx,
-                                Err(e) => return Err((Some(msg), e.into())),
-                            };
-                            ctx.write_all()
-                                .map_err(|(ctx, e)| ll_conn::force_finish_on_error((ctx, e)))
-                                .map_err(|e| (Some(msg), e.into()))?
-                        }
-                        Err(error) => return Err((Some(msg), error)),
-                    };
+            let readable = wait_readable(&self.recv, stop, wait)
+                .map_err(|e| (None, HandleError::Connection(e)))?;
+            if !readable {
+                continue;
+            }
+            // There is data, but maybe not the whole message yet. Whatever is there is kept for the next try.
+            if let Some(typ) = self.next_message(Timeout::Nonblock, true)? {
+                last_activity = Instant::now();
+                if typ == MessageType::Call {
+                    last_call = last_activity;
                 }
-                Err(error) => return Err((None, HandleError::Connection(error))),
             }
         }
     }
+
+    /// Dispatch the next message if there is one within the timeout and return its type. Messages in the backlog
+    /// are dispatched first, the connection is only read from if `read` is set.
+    #[allow(clippy::result_large_err)]
+    fn next_message(
+        &mut self,
+        timeout: Timeout,
+        read: bool,
+    ) -> std::result::Result<Option<MessageType>, (Option<MarshalledMessage>, HandleError<UserError>)>
+    {
+        if let Some(msg) = self.backlog.pop_front() {
+            let typ = msg.typ;
+            self.handle_message(msg)?;
+            return Ok(Some(typ));
+        }
+        if !read {
+            return Ok(None);
+        }
+        match self.recv.get_next_message(timeout) {
+            Ok(msg) => {
+                let typ = msg.typ;
+                self.handle_message(msg)?;
+                Ok(Some(typ))
+            }
+            Err(Error::TimedOut) => Ok(None),
+            Err(error) => Err((None, HandleError::Connection(error))),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn handle_message(
+        &mut self,
+        msg: MarshalledMessage,
+    ) -> std::result::Result<(), (Option<MarshalledMessage>, HandleError<UserError>)> {
+        match msg.typ {
+            MessageType::Call => self.handle_call(msg),
+            MessageType::Signal => self.handle_signal(msg),
+            MessageType::Reply | MessageType::Error => self.handle_reply(msg),
+            MessageType::Invalid => Ok(()),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn handle_signal(
+        &mut self,
+        msg: MarshalledMessage,
+    ) -> std::result::Result<(), (Option<MarshalledMessage>, HandleError<UserError>)> {
+        let mut env = HandleEnvironment::new(self.send.clone());
+        let mut handled = false;
+        let mut result = Ok(None);
+        for (rule, handler) in &mut self.signal_handlers {
+            if rule.matches(&msg) {
+                handled = true;
+                result = handler(&mut self.ctx, Matches::default(), &msg, &mut env);
+                if result.is_err() {
+                    break;
+                }
+            }
+        }
+        if !handled {
+            result = (self.default_handler)(&mut self.ctx, Matches::default(), &msg, &mut env);
+        }
+        self.finish_unanswered(msg, env, result)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn handle_reply(
+        &mut self,
+        msg: MarshalledMessage,
+    ) -> std::result::Result<(), (Option<MarshalledMessage>, HandleError<UserError>)> {
+        let mut env = HandleEnvironment::new(self.send.clone());
+        let handler = msg
+            .dynheader
+            .response_serial
+            .and_then(|serial| self.reply_handlers.remove(&serial));
+        let result = match handler {
+            Some(mut handler) => handler(&mut self.ctx, Matches::default(), &msg, &mut env),
+            None => (self.default_handler)(&mut self.ctx, Matches::default(), &msg, &mut env),
+        };
+        self.finish_unanswered(msg, env, result)
+    }
+
+    /// Apply the changes the handlers made for a message that does not get a reply
+    #[allow(clippy::result_large_err)]
+    fn finish_unanswered(
+        &mut self,
+        msg: MarshalledMessage,
+        env: HandleEnvironment<UserData, UserError>,
+        result: HandleResult<UserError>,
+    ) -> std::result::Result<(), (Option<MarshalledMessage>, HandleError<UserError>)> {
+        self.reply_handlers.extend(env.new_reply_handlers);
+        match result {
+            Ok(_) => {
+                self.objects.merge(env.new_dispatches);
+                Ok(())
+            }
+            Err(error @ HandleError::Connection(_)) => Err((Some(msg), error)),
+            // there is nobody to tell about the error
+            Err(_) => Ok(()),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn handle_call(
+        &mut self,
+        msg: MarshalledMessage,
+    ) -> std::result::Result<(), (Option<MarshalledMessage>, HandleError<UserError>)> {
+        let mut env = HandleEnvironment::new(self.send.clone());
+        let result = match self.objects.route(&msg) {
+            Route::Handler(matches, handler) => handler(&mut self.ctx, matches, &msg, &mut env),
+            Route::Error(error) => Ok(Some(*error)),
+            Route::NoObject => {
+                (self.default_handler)(&mut self.ctx, Matches::default(), &msg, &mut env)
+            }
+        };
+
+        // calls made by the handler were sent in any case
+        self.reply_handlers.extend(env.new_reply_handlers);
+        if result.is_ok() {
+            // apply the new pathes established in the handler
+            self.objects.merge(env.new_dispatches);
+        }
+
+        let response = match result {
+            Ok(Some(response)) => response,
+            Ok(None) => msg.dynheader.make_response(),
+            Err(error) => match error.to_error_reply(&msg.dynheader) {
+                Some(response) => response,
+                None => return Err((Some(msg), error)),
+            },
+        };
+
+        if !msg.expects_reply() {
+            // the caller does not want a response
+            return Ok(());
+        }
+        if env.reply_deferred {
+            // the handler will reply later through a PendingReply
+            return Ok(());
+        }
+
+        let mut send_conn = self.send.lock().unwrap();
+        let ctx = match send_conn.send_message(&response) {
+            Ok(ctx) => ctx,
+            Err(e) => return Err((Some(msg), e.into())),
+        };
+        ctx.write_all()
+            .map_err(|(ctx, e)| ll_conn::force_finish_on_error((ctx, e)))
+            .map_err(|e| (Some(msg), e.into()))?;
+        Ok(())
+    }
+}
+
+/// Why `DispatchConn::run_until` returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The `StopHandle` was triggered
+    Stopped,
+    /// There were no calls for the time set with `set_exit_on_idle`
+    Idle,
+}
+
+struct StopInner {
+    stopped: AtomicBool,
+    // writing to this wakes up the dispatcher waiting on the other end
+    wake_tx: UnixStream,
+    wake_rx: UnixStream,
+}
+
+/// Tells a running dispatcher to stop, from any thread.
+///
+/// A handle can only be stopped once. Afterwards every `run_until` using it returns right away, so use a new
+/// handle to run the dispatcher again.
+#[derive(Clone)]
+pub struct StopHandle {
+    inner: Arc<StopInner>,
+}
+
+impl StopHandle {
+    pub fn new() -> Result<Self> {
+        let (wake_tx, wake_rx) = UnixStream::pair()?;
+        wake_tx.set_nonblocking(true)?;
+        Ok(Self {
+            inner: Arc::new(StopInner {
+                stopped: AtomicBool::new(false),
+                wake_tx,
+                wake_rx,
+            }),
+        })
+    }
+
+    /// Make the dispatchers using this handle return after the message they are currently handling
+    pub fn stop(&self) {
+        self.inner.stopped.store(true, Ordering::SeqCst);
+        // if the socket is full there already is a wakeup pending
+        let _ = (&self.inner.wake_tx).write(&[0]);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.inner.stopped.load(Ordering::SeqCst)
+    }
+}
+
+/// Wait until there is data to read on the connection, the stop handle is triggered or the timeout runs out.
+/// Returns whether there is data.
+pub(crate) fn wait_readable(
+    conn: &RecvConn,
+    stop: Option<&StopHandle>,
+    timeout: Option<Duration>,
+) -> Result<bool> {
+    let mut fds = vec![PollFd::new(conn.as_raw_fd(), PollFlags::POLLIN)];
+    if let Some(stop) = stop {
+        fds.push(PollFd::new(
+            stop.inner.wake_rx.as_raw_fd(),
+            PollFlags::POLLIN,
+        ));
+    }
+    // round up, so waiting for less than a millisecond does not turn into busy looping
+    let timeout = timeout
+        .map(|timeout| timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32)
+        .unwrap_or(-1);
+    match poll(&mut fds, timeout) {
+        Ok(_) => Ok(fds[0].revents().is_some_and(|events| !events.is_empty())),
+        Err(nix::errno::Errno::EINTR) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
 }
 
 #[test]
@@ -15,13 +15,43 @@ use std::os::unix::io::RawFd;
 use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::os::unix::net::UnixStream;
 
-use nix::cmsg_space;
 use nix::sys::socket::SockaddrStorage;
 use nix::sys::socket::{
     self, connect, recvmsg, sendmsg, socket, ControlMessage, ControlMessageOwned, MsgFlags,
     UnixAddr,
 };
 
+// Make sure fds we receive do not leak into child processes. Where recvmsg can not set FD_CLOEXEC itself
+// it is set right after receiving the fds.
+#[cfg(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+const RECV_FLAGS: MsgFlags = MsgFlags::MSG_CMSG_CLOEXEC;
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+const RECV_FLAGS: MsgFlags = MsgFlags::empty();
+
+fn set_cloexec(fd: RawFd) {
+    if RECV_FLAGS.is_empty() {
+        nix::fcntl::fcntl(
+            fd,
+            nix::fcntl::FcntlArg::F_SETFD(nix::fcntl::FdFlag::FD_CLOEXEC),
+        )
+        .ok();
+    }
+}
+
 /// A lowlevel abstraction over the raw unix socket
 #[derive(Debug)]
 pub struct SendConn {
@@ -31,11 +61,19 @@ pub struct SendConn {
     serial_counter: u32,
 }
 
+/// The maximum number of fds the kernel allows to be passed with one sendmsg call (SCM_MAX_FD on linux).
+/// The dbus spec does not allow to split one message over multiple sendmsg calls if it carries fds, so this is also
+/// the maximum number of fds a message can carry.
+pub const MAX_FDS_PER_MESSAGE: usize = 253;
+
 pub struct RecvConn {
     stream: UnixStream,
 
     msg_buf_in: Vec<u8>,
-    cmsgs_in: Vec<ControlMessageOwned>,
+    fds_in: Vec<crate::wire::UnixFd>,
+    fds_truncated: bool,
+    max_fds_per_message: usize,
+    cmsg_buf: Vec<u8>,
 }
 
 pub struct DuplexConn {
@@ -44,6 +82,40 @@ pub struct DuplexConn {
 }
 
 impl RecvConn {
+    fn new(stream: UnixStream) -> Self {
+        let mut conn = RecvConn {
+            stream,
+            msg_buf_in: Vec::new(),
+            fds_in: Vec::new(),
+            fds_truncated: false,
+            max_fds_per_message: 0,
+            cmsg_buf: Vec::new(),
+        };
+        conn.set_max_fds_per_message(MAX_FDS_PER_MESSAGE);
+        conn
+    }
+
+    /// The maximum number of fds that can be received with one message
+    pub fn max_fds_per_message(&self) -> usize {
+        self.max_fds_per_message
+    }
+
+    /// Set the maximum number of fds that can be received with one message. Values bigger than
+    /// `MAX_FDS_PER_MESSAGE` are capped to that.
+    ///
+    /// If a message carries more fds than this, the surplus fds are discarded by the kernel and
+    /// `get_next_message` returns `Error::UnixFdsTruncated` for that message.
+    pub fn set_max_fds_per_message(&mut self, max: usize) {
+        self.max_fds_per_message = usize::min(max, MAX_FDS_PER_MESSAGE);
+        // CMSG_SPACE is always safe
+        let space = unsafe {
+            socket::CMSG_SPACE(
+                (self.max_fds_per_message * std::mem::size_of::<RawFd>()) as socket::c_uint,
+            )
+        } as usize;
+        self.cmsg_buf = Vec::with_capacity(space);
+    }
+
     pub fn can_read_from_source(&self) -> nix::Result<bool> {
         let mut fdset = nix::sys::select::FdSet::new();
         let fd = self.stream.as_raw_fd();
@@ -66,9 +138,6 @@ impl RecvConn {
 
         let iovec = IoSliceMut::new(&mut tmpbuf[..usize::min(bytes_to_read, BUFSIZE)]);
 
-        let mut cmsgspace = cmsg_space!([RawFd; 10]);
-        let flags = MsgFlags::empty();
-
         let old_timeout = self.stream.read_timeout()?;
         match timeout {
             Timeout::Duration(d) => {
@@ -85,8 +154,8 @@ impl RecvConn {
         let msg = recvmsg::<SockaddrStorage>(
             self.stream.as_raw_fd(),
             iovec_mut,
-            Some(&mut cmsgspace),
-            flags,
+            Some(&mut self.cmsg_buf),
+            RECV_FLAGS,
         )
         .map_err(|e| match e {
             nix::errno::Errno::EAGAIN => Error::TimedOut,
@@ -98,11 +167,30 @@ impl RecvConn {
 
         let msg = msg?;
 
+        // Take ownership of all received fds first, so they get closed if anything goes wrong from here on
+        for cmsg in msg.cmsgs() {
+            match cmsg {
+                ControlMessageOwned::ScmRights(fds) => {
+                    for fd in fds {
+                        set_cloexec(fd);
+                        self.fds_in.push(crate::wire::UnixFd::new(fd));
+                    }
+                }
+                _ => {
+                    // TODO what to do?
+                    eprintln!("Cmsg other than ScmRights: {:?}", cmsg);
+                }
+            }
+        }
+        if msg.flags.contains(MsgFlags::MSG_CTRUNC) {
+            // Reading the message continues, so the stream stays in sync. The error is reported once the message is complete.
+            self.fds_truncated = true;
+        }
+
         if msg.bytes == 0 {
             return Err(Error::ConnectionClosed);
         }
 
-        self.cmsgs_in.extend(msg.cmsgs());
         let bytes = msg.bytes;
         self.msg_buf_in.extend(&mut tmpbuf[..bytes].iter().copied());
         Ok(())
@@ -128,6 +216,12 @@ impl RecvConn {
 
         let bytes_needed =
             complete_header_size + padding_between_header_and_body + header.body_len as usize;
+        // refuse to allocate huge buffers for messages a peer might announce
+        if bytes_needed > crate::wire::MAX_MESSAGE_SIZE {
+            return Err(Error::UnmarshalError(UnmarshalError::MessageTooBig(
+                bytes_needed,
+            )));
+        }
         Ok(bytes_needed)
     }
 
@@ -189,20 +283,17 @@ impl RecvConn {
         }
         self.msg_buf_in.clear();
 
-        for cmsg in &self.cmsgs_in {
-            match cmsg {
-                ControlMessageOwned::ScmRights(fds) => {
-                    msg.body
-                        .raw_fds
-                        .extend(fds.iter().map(|fd| crate::wire::UnixFd::new(*fd)));
-                }
-                _ => {
-                    // TODO what to do?
-                    eprintln!("Cmsg other than ScmRights: {:?}", cmsg);
-                }
-            }
+        // The fds are moved out of the conn in any case. If they do not belong to a valid message
+        // they are closed when they are dropped.
+        let fds = std::mem::take(&mut self.fds_in);
+        if std::mem::replace(&mut self.fds_truncated, false) {
+            return Err(Error::UnixFdsTruncated);
+        }
+        let expected_fds = msg.dynheader.num_fds.unwrap_or(0);
+        if fds.len() != expected_fds as usize {
+            return Err(Error::UnixFdCountMismatch(expected_fds, fds.len()));
         }
-        self.cmsgs_in.clear();
+        msg.body.raw_fds = fds;
 
         Ok(msg)
     }
@@ -481,11 +572,7 @@ impl DuplexConn {
                 header_buf: Vec::new(),
                 serial_counter: 1,
             },
-            recv: RecvConn {
-                msg_buf_in: Vec::new(),
-                cmsgs_in: Vec::new(),
-                stream,
-            },
+            recv: RecvConn::new(stream),
         })
     }
 
@@ -513,6 +600,23 @@ impl DuplexConn {
     }
 }
 
+#[cfg(test)]
+impl DuplexConn {
+    /// Two connected peers without a bus in between, for tests that do not need a daemon
+    pub(crate) fn pair() -> (DuplexConn, DuplexConn) {
+        let (a, b) = UnixStream::pair().unwrap();
+        let make = |stream: UnixStream| DuplexConn {
+            send: SendConn {
+                stream: stream.try_clone().unwrap(),
+                header_buf: Vec::new(),
+                serial_counter: 1,
+            },
+            recv: RecvConn::new(stream),
+        };
+        (make(a), make(b))
+    }
+}
+
 impl AsRawFd for SendConn {
     /// Reading or writing to the `RawFd` may result in undefined behavior
     /// and break the `Conn`.
@@ -534,3 +638,72 @@ impl AsRawFd for DuplexConn {
         self.recv.stream.as_raw_fd()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message_builder::MessageBuilder;
+    use std::io::Write;
+
+    fn socketpair_conns() -> (SendConn, RecvConn) {
+        let (a, b) = UnixStream::pair().unwrap();
+        let send = SendConn {
+            stream: a,
+            header_buf: Vec::new(),
+            serial_counter: 1,
+        };
+        (send, RecvConn::new(b))
+    }
+
+    fn msg_with_fds(num: usize) -> MarshalledMessage {
+        let mut msg = MessageBuilder::new()
+            .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+            .build();
+        for _ in 0..num {
+            let file = std::fs::File::open("/dev/null").unwrap();
+            msg.body.push_param(file).unwrap();
+        }
+        msg
+    }
+
+    #[test]
+    fn test_fd_limit() {
+        let (mut send, mut recv) = socketpair_conns();
+        assert_eq!(recv.max_fds_per_message(), MAX_FDS_PER_MESSAGE);
+        recv.set_max_fds_per_message(1000);
+        assert_eq!(recv.max_fds_per_message(), MAX_FDS_PER_MESSAGE);
+
+        let msg = msg_with_fds(3);
+        send.send_message_write_all(&msg).unwrap();
+        let recvd = recv.get_next_message(Timeout::Infinite).unwrap();
+        assert_eq!(recvd.body.raw_fds.len(), 3);
+
+        // too many fds get truncated by the kernel, but the stream stays usable
+        recv.set_max_fds_per_message(1);
+        send.send_message_write_all(&msg).unwrap();
+        assert!(matches!(
+            recv.get_next_message(Timeout::Infinite),
+            Err(Error::UnixFdsTruncated)
+        ));
+        let msg = msg_with_fds(1);
+        send.send_message_write_all(&msg).unwrap();
+        let recvd = recv.get_next_message(Timeout::Infinite).unwrap();
+        assert_eq!(recvd.body.raw_fds.len(), 1);
+        assert!(recvd.body.raw_fds[0].get_raw_fd().is_some());
+    }
+
+    #[test]
+    fn test_fd_count_mismatch() {
+        let (mut send, mut recv) = socketpair_conns();
+        // write a message announcing an fd without actually passing it
+        let msg = msg_with_fds(1);
+        let mut buf = Vec::new();
+        marshal::marshal(&msg, 1, &mut buf).unwrap();
+        buf.extend_from_slice(msg.get_buf());
+        send.stream.write_all(&buf).unwrap();
+        assert!(matches!(
+            recv.get_next_message(Timeout::Infinite),
+            Err(Error::UnixFdCountMismatch(1, 0))
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+//! Observing the messages on a bus like dbus-monitor does
+//!
+//! The `MonitorConn` turns a connection into a monitor with org.freedesktop.DBus.Monitoring.BecomeMonitor. After that
+//! the bus sends it a copy of every message matching one of the rules, but the connection can not send anything
+//! anymore. Because of that the `MonitorConn` only offers reading.
+//!
+//! Becoming a monitor is usually only allowed for privileged users, especially on the system bus.
+//!
+//! ```rust,no_run
+//! use rustbus::connection::monitor_conn::MonitorConn;
+//! use rustbus::connection::Timeout;
+//! use rustbus::{MatchRule, MessageType};
+//!
+//! let rules = [MatchRule::new().message_type(MessageType::Signal)];
+//! let monitor = MonitorConn::session_conn(&rules, Timeout::Infinite).unwrap();
+//! for observed in monitor {
+//!     let observed = observed.unwrap();
+//!     println!("{:?}: {:?}", observed.received, observed.msg.dynheader.member);
+//! }
+//! ```
+
+use super::ll_conn::{DuplexConn, RecvConn};
+use super::*;
+use crate::message_builder::{MarshalledMessage, MessageBuilder, MessageType};
+use crate::params::message::Message;
+use crate::MatchRule;
+
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+/// A message seen on the bus
+#[derive(Debug)]
+pub struct MonitoredMessage {
+    /// When the monitor read the message
+    pub received: SystemTime,
+    /// The message with the whole body unmarshalled into params
+    pub msg: Message<'static, 'static>,
+}
+
+/// A connection in monitoring mode. See the module docs.
+///
+/// It can be used as an iterator that blocks until the next message arrives and ends when the bus closes the
+/// connection.
+pub struct MonitorConn {
+    recv: RecvConn,
+    unique_name: String,
+    // messages that were read while waiting for the reply to BecomeMonitor
+    backlog: VecDeque<(SystemTime, MarshalledMessage)>,
+}
+
+impl MonitorConn {
+    pub fn session_conn(rules: &[MatchRule], timeout: Timeout) -> Result<Self> {
+        let conn = DuplexConn::connect_to_bus(get_session_bus_path()?, true)?;
+        Self::new(conn, rules, timeout)
+    }
+
+    pub fn system_conn(rules: &[MatchRule], timeout: Timeout) -> Result<Self> {
+        let conn = DuplexConn::connect_to_bus(get_system_bus_path()?, true)?;
+        Self::new(conn, rules, timeout)
+    }
+
+    /// Become a monitor for the messages matching any of the rules, or for all messages if there are none.
+    ///
+    /// The connection must be fresh, the hello is sent here. If the bus refuses this returns
+    /// `Error::MonitorRejected` with the name of the error.
+    pub fn new(mut conn: DuplexConn, rules: &[MatchRule], timeout: Timeout) -> Result<Self> {
+        let start_time = time::Instant::now();
+        let unique_name = conn.send_hello(timeout)?;
+
+        let mut become_monitor = MessageBuilder::new()
+            .call("BecomeMonitor")
+            .on("/org/freedesktop/DBus")
+            .with_interface("org.freedesktop.DBus.Monitoring")
+            .at("org.freedesktop.DBus")
+            .build();
+        let rules: Vec<String> = rules.iter().map(MatchRule::to_string).collect();
+        // the flags are currently unused by the spec
+        become_monitor.body.push_param2(rules, 0u32)?;
+        let serial = conn
+            .send
+            .send_message(&become_monitor)?
+            .write(calc_timeout_left(&start_time, timeout)?)
+            .map_err(ll_conn::force_finish_on_error)?;
+
+        let mut monitor = Self {
+            recv: conn.recv,
+            unique_name,
+            backlog: VecDeque::new(),
+        };
+        loop {
+            let msg = monitor
+                .recv
+                .get_next_message(calc_timeout_left(&start_time, timeout)?)?;
+            if msg.dynheader.response_serial != Some(serial) {
+                monitor.backlog.push_back((SystemTime::now(), msg));
+                continue;
+            }
+            if msg.typ == MessageType::Error {
+                return Err(Error::MonitorRejected(
+                    msg.dynheader.error_name.unwrap_or_default(),
+                ));
+            }
+            // The connection does not need its send half anymore. The bus would disconnect it if it wrote anything.
+            return Ok(monitor);
+        }
+    }
+
+    /// The unique name the connection had before it became a monitor
+    pub fn unique_name(&self) -> &str {
+        &self.unique_name
+    }
+
+    /// Wait for the next observed message without unmarshalling its body
+    pub fn next_marshalled(&mut self, timeout: Timeout) -> Result<(SystemTime, MarshalledMessage)> {
+        let start_time = time::Instant::now();
+        loop {
+            let (received, msg) = match self.backlog.pop_front() {
+                Some(observed) => observed,
+                None => {
+                    let msg = self
+                        .recv
+                        .get_next_message(calc_timeout_left(&start_time, timeout)?)?;
+                    (SystemTime::now(), msg)
+                }
+            };
+            if !self.is_own_name_signal(&msg) {
+                return Ok((received, msg));
+            }
+        }
+    }
+
+    /// Wait for the next observed message and unmarshal its body
+    pub fn next_message(&mut self, timeout: Timeout) -> Result<MonitoredMessage> {
+        let (received, msg) = self.next_marshalled(timeout)?;
+        Ok(MonitoredMessage {
+            received,
+            msg: msg.unmarshall_all()?,
+        })
+    }
+
+    /// The bus tells the monitor that it lost its unique name. That is not traffic on the bus, so it is not reported.
+    fn is_own_name_signal(&self, msg: &MarshalledMessage) -> bool {
+        msg.typ == MessageType::Signal
+            && msg.dynheader.sender.as_deref() == Some("org.freedesktop.DBus")
+            && msg.dynheader.destination.as_deref() == Some(self.unique_name.as_str())
+            && matches!(
+                msg.dynheader.member.as_deref(),
+                Some("NameAcquired") | Some("NameLost")
+            )
+    }
+}
+
+impl Iterator for MonitorConn {
+    type Item = Result<MonitoredMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_message(Timeout::Infinite) {
+            Err(Error::ConnectionClosed) => None,
+            result => Some(result),
+        }
+    }
+}
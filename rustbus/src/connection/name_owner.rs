@@ -0,0 +1,281 @@
+//! Owning well known names on the bus
+//!
+//! The `NameOwner` requests a name with RequestName, interprets the reply and keeps track of the NameAcquired/NameLost
+//! signals the bus sends for the name afterwards. When it is dropped the name is released again.
+//!
+//! ```rust,no_run
+//! use rustbus::connection::name_owner::{NameEvent, NameOwner};
+//! use rustbus::connection::Timeout;
+//! use rustbus::standard_messages::DBUS_NAME_FLAG_ALLOW_REPLACEMENT;
+//! use rustbus::RpcConn;
+//!
+//! let mut con = RpcConn::session_conn(Timeout::Infinite).unwrap();
+//! let mut owner = NameOwner::request(
+//!     &mut con,
+//!     "io.killing.spark",
+//!     DBUS_NAME_FLAG_ALLOW_REPLACEMENT,
+//!     Timeout::Infinite,
+//! )
+//! .unwrap();
+//! while owner.is_owner() {
+//!     match owner.wait_event(Timeout::Infinite).unwrap() {
+//!         NameEvent::Acquired => println!("Got the name"),
+//!         NameEvent::Lost => println!("Somebody else took the name"),
+//!     }
+//! }
+//! ```
+
+use super::rpc_conn::{CallError, RpcConn, SubscriptionToken};
+use super::*;
+use crate::message_builder::MarshalledMessage;
+use crate::message_builder::MessageType;
+use crate::standard_messages;
+use crate::wire::errors::UnmarshalError;
+use crate::MatchRule;
+
+/// What the bus answered to RequestName
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestNameReply {
+    /// The name is now owned by this connection
+    PrimaryOwner,
+    /// Somebody else owns the name, this connection was put into the queue for it
+    InQueue,
+    /// Somebody else owns the name and this connection was not put into the queue
+    Exists,
+    /// The name was already owned by this connection
+    AlreadyOwner,
+}
+
+impl RequestNameReply {
+    pub fn from_u32(reply: u32) -> Option<Self> {
+        match reply {
+            standard_messages::DBUS_REQUEST_NAME_REPLY_PRIMARY_OWNER => Some(Self::PrimaryOwner),
+            standard_messages::DBUS_REQUEST_NAME_REPLY_IN_QUEUE => Some(Self::InQueue),
+            standard_messages::DBUS_REQUEST_NAME_REPLY_EXISTS => Some(Self::Exists),
+            standard_messages::DBUS_REQUEST_NAME_REPLY_ALREADY_OWNER => Some(Self::AlreadyOwner),
+            _ => None,
+        }
+    }
+}
+
+/// What the bus answered to ReleaseName
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReleaseNameReply {
+    /// The name was released, or this connection was removed from its queue
+    Released,
+    /// Nobody owned the name
+    NonExistent,
+    /// The name is owned by somebody else and this connection was not in its queue
+    NotOwner,
+}
+
+impl ReleaseNameReply {
+    pub fn from_u32(reply: u32) -> Option<Self> {
+        match reply {
+            standard_messages::DBUS_RELEASE_NAME_REPLY_RELEASED => Some(Self::Released),
+            standard_messages::DBUS_RELEASE_NAME_REPLY_NON_EXISTENT => Some(Self::NonExistent),
+            standard_messages::DBUS_RELEASE_NAME_REPLY_NOT_OWNER => Some(Self::NotOwner),
+            _ => None,
+        }
+    }
+}
+
+/// Whether this connection currently owns the name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameState {
+    Owned,
+    /// Waiting in the queue to get the name once the current owner releases it
+    Queued,
+    /// The name was lost and this connection is not in the queue for it
+    NotOwned,
+}
+
+/// A change of the ownership, see `NameOwner::try_get_event`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameEvent {
+    Acquired,
+    Lost,
+}
+
+/// Owns a name on the bus for as long as it lives. See the module docs.
+///
+/// It borrows the `RpcConn` because the name belongs to the connection, use `conn` to keep using the connection.
+/// NameAcquired/NameLost signals for the name are taken out of the general signal queue of the connection.
+pub struct NameOwner<'a> {
+    conn: &'a mut RpcConn,
+    name: String,
+    flags: u32,
+    reply: RequestNameReply,
+    state: NameState,
+    token: Option<SubscriptionToken>,
+}
+
+impl<'a> NameOwner<'a> {
+    /// Request the name with the `DBUS_NAME_FLAG_*` flags from `standard_messages`.
+    ///
+    /// If the name is owned by somebody else and the connection was not queued (the reply was `Exists`)
+    /// this returns `Error::NameTaken`. Being put into the queue is not an error, check `state` for that.
+    pub fn request(
+        conn: &'a mut RpcConn,
+        name: &str,
+        flags: u32,
+        timeout: Timeout,
+    ) -> std::result::Result<Self, CallError> {
+        let start_time = time::Instant::now();
+
+        // subscribe first, the bus sends NameAcquired before the reply to RequestName
+        let rule = MatchRule::new()
+            .message_type(MessageType::Signal)
+            .sender("org.freedesktop.DBus")
+            .interface("org.freedesktop.DBus")
+            .path("/org/freedesktop/DBus")
+            .arg(0, name);
+        let token = conn.subscribe_queued(rule, timeout)?;
+
+        let (reply,): (u32,) = conn.call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "RequestName",
+            (name, flags),
+            calc_timeout_left(&start_time, timeout)?,
+        )?;
+        let reply = RequestNameReply::from_u32(reply)
+            .ok_or(CallError::Unmarshal(UnmarshalError::NoMatchingVariantFound))?;
+        let state = match reply {
+            RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => NameState::Owned,
+            RequestNameReply::InQueue => NameState::Queued,
+            RequestNameReply::Exists => {
+                conn.unsubscribe(token)?;
+                return Err(CallError::Connection(Error::NameTaken));
+            }
+        };
+
+        Ok(Self {
+            conn,
+            name: name.to_owned(),
+            flags,
+            reply,
+            state,
+            token: Some(token),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The reply to the request of the name
+    pub fn reply(&self) -> RequestNameReply {
+        self.reply
+    }
+
+    /// The state as of the last processed event. Use `try_get_event` or `wait_event` to process the events.
+    pub fn state(&self) -> NameState {
+        self.state
+    }
+
+    pub fn is_owner(&self) -> bool {
+        self.state == NameState::Owned
+    }
+
+    /// The connection the name belongs to
+    pub fn conn(&mut self) -> &mut RpcConn {
+        self.conn
+    }
+
+    /// Process the signals about the name that arrived and return the first that changed the state, without blocking
+    pub fn try_get_event(&mut self) -> Result<Option<NameEvent>> {
+        loop {
+            // read everything that is there already
+            match self.conn.try_refill_once(Timeout::Nonblock) {
+                Ok(_) => {}
+                Err(Error::TimedOut) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(self.process_queued())
+    }
+
+    /// Process the signals about the name until the state changes
+    pub fn wait_event(&mut self, timeout: Timeout) -> Result<NameEvent> {
+        let start_time = time::Instant::now();
+        loop {
+            if let Some(event) = self.process_queued() {
+                return Ok(event);
+            }
+            self.conn
+                .refill_once(calc_timeout_left(&start_time, timeout)?)?;
+        }
+    }
+
+    fn process_queued(&mut self) -> Option<NameEvent> {
+        loop {
+            let signal = self.conn.try_get_subscribed_signal(self.token.as_ref()?)?;
+            if let Some(event) = self.process_signal(&signal) {
+                return Some(event);
+            }
+        }
+    }
+
+    /// Update the state with a signal. Signals that do not change the state (e.g. the NameAcquired the bus sends
+    /// before the reply to RequestName) do not produce events.
+    fn process_signal(&mut self, signal: &MarshalledMessage) -> Option<NameEvent> {
+        let name = signal.body.parser().get::<&str>().ok()?;
+        if name != self.name {
+            return None;
+        }
+        match signal.dynheader.member.as_deref() {
+            Some("NameAcquired") if self.state != NameState::Owned => {
+                self.state = NameState::Owned;
+                Some(NameEvent::Acquired)
+            }
+            Some("NameLost") if self.state == NameState::Owned => {
+                // connections that may be queued get put back into the queue by the bus
+                self.state = if self.flags & standard_messages::DBUS_NAME_FLAG_DO_NOT_QUEUE != 0 {
+                    NameState::NotOwned
+                } else {
+                    NameState::Queued
+                };
+                Some(NameEvent::Lost)
+            }
+            _ => None,
+        }
+    }
+
+    /// Release the name and wait for the answer of the bus
+    pub fn release(mut self, timeout: Timeout) -> std::result::Result<ReleaseNameReply, CallError> {
+        let start_time = time::Instant::now();
+        if let Some(token) = self.token.take() {
+            self.conn.unsubscribe(token)?;
+        }
+        let (reply,): (u32,) = self.conn.call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "ReleaseName",
+            (self.name.as_str(),),
+            calc_timeout_left(&start_time, timeout)?,
+        )?;
+        self.state = NameState::NotOwned;
+        ReleaseNameReply::from_u32(reply)
+            .ok_or(CallError::Unmarshal(UnmarshalError::NoMatchingVariantFound))
+    }
+}
+
+impl Drop for NameOwner<'_> {
+    fn drop(&mut self) {
+        if self.token.is_none() {
+            // already released
+            return;
+        }
+        self.token = None;
+        let mut release = standard_messages::release_name(&self.name);
+        release.set_flag(crate::message_builder::HeaderFlags::NoReplyExpected);
+        // there is nobody left to report a failure to
+        let _ = self
+            .conn
+            .send_message(&mut release)
+            .and_then(|ctx| ctx.write_all().map_err(ll_conn::force_finish_on_error));
+    }
+}
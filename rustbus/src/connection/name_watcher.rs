@@ -0,0 +1,262 @@
+//! Tracking the owners of names on the bus
+//!
+//! Clients of services that may restart need to know when the service appears, vanishes or changes its unique name.
+//! The `NameWatcher` asks the bus for the current owner of each watched name with GetNameOwner and then follows the
+//! NameOwnerChanged signals for it. The signals are processed whenever the `RpcConn` reads messages.
+//!
+//! ```rust,no_run
+//! use rustbus::connection::name_watcher::NameWatcher;
+//! use rustbus::connection::Timeout;
+//! use rustbus::RpcConn;
+//!
+//! let mut con = RpcConn::session_conn(Timeout::Infinite).unwrap();
+//! let watcher = NameWatcher::new();
+//! watcher
+//!     .watch(&mut con, "org.freedesktop.Notifications", Timeout::Infinite)
+//!     .unwrap();
+//! loop {
+//!     let change = watcher.wait_change(&mut con, Timeout::Infinite).unwrap();
+//!     if change.vanished() {
+//!         println!("{} is gone", change.name);
+//!     }
+//! }
+//! ```
+
+use super::rpc_conn::{CallError, RpcConn, SubscriptionToken};
+use super::*;
+use crate::message_builder::MarshalledMessage;
+use crate::message_builder::MessageType;
+use crate::standard_messages::WellKnownError;
+use crate::MatchRule;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, Weak};
+
+/// The owner of a watched name changed. An owner of None means the name had no owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameOwnerChange {
+    pub name: String,
+    pub old_owner: Option<String>,
+    pub new_owner: Option<String>,
+}
+
+impl NameOwnerChange {
+    /// The name has an owner now (it might have had a different one before)
+    pub fn appeared(&self) -> bool {
+        self.new_owner.is_some()
+    }
+
+    /// The name has no owner anymore
+    pub fn vanished(&self) -> bool {
+        self.new_owner.is_none()
+    }
+}
+
+/// Gets called with every change of the owner of a watched name, see `NameWatcher::with_callback`
+pub type NameChangeCallback = Box<dyn FnMut(&NameOwnerChange) + Send>;
+
+struct WatchedName {
+    owner: Option<String>,
+    // keeps the subscription alive
+    token: SubscriptionToken,
+}
+
+struct State {
+    names: HashMap<String, WatchedName>,
+    changes: VecDeque<NameOwnerChange>,
+    queue_changes: bool,
+}
+
+impl State {
+    /// Apply the change, returns it if it changed anything
+    fn apply(&mut self, change: NameOwnerChange) -> Option<NameOwnerChange> {
+        let watched = self.names.get_mut(&change.name)?;
+        if watched.owner == change.new_owner {
+            return None;
+        }
+        watched.owner = change.new_owner.clone();
+        if self.queue_changes {
+            self.changes.push_back(change.clone());
+        }
+        Some(change)
+    }
+}
+
+/// Watches the owners of any number of names. See the module docs.
+///
+/// The watcher can be cloned, all clones share the same state. Proxies can keep a clone and compare the owner they
+/// cached state for with `owner` to find out when the cached state became invalid.
+#[derive(Clone)]
+pub struct NameWatcher {
+    state: Arc<Mutex<State>>,
+    callback: Arc<Mutex<Option<NameChangeCallback>>>,
+}
+
+impl Default for NameWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NameWatcher {
+    /// The changes will be queued, get them with `try_get_change` or `wait_change`
+    pub fn new() -> Self {
+        Self::create(true, None)
+    }
+
+    /// The changes will be passed to the callback instead of being queued. This includes the owners that are found
+    /// when a name is watched.
+    pub fn with_callback(callback: NameChangeCallback) -> Self {
+        Self::create(false, Some(callback))
+    }
+
+    fn create(queue_changes: bool, callback: Option<NameChangeCallback>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                names: HashMap::new(),
+                changes: VecDeque::new(),
+                queue_changes,
+            })),
+            callback: Arc::new(Mutex::new(callback)),
+        }
+    }
+
+    /// Start watching the name and return its current owner. If the name has an owner, this is reported as the first
+    /// change for the name.
+    pub fn watch(
+        &self,
+        conn: &mut RpcConn,
+        name: &str,
+        timeout: Timeout,
+    ) -> std::result::Result<Option<String>, CallError> {
+        let start_time = time::Instant::now();
+        if let Some(watched) = self.state.lock().unwrap().names.get(name) {
+            return Ok(watched.owner.clone());
+        }
+
+        // subscribe first, so no change between the GetNameOwner and the subscription is missed
+        let rule = MatchRule::new()
+            .message_type(MessageType::Signal)
+            .sender("org.freedesktop.DBus")
+            .interface("org.freedesktop.DBus")
+            .member("NameOwnerChanged")
+            .path("/org/freedesktop/DBus")
+            .arg(0, name);
+        let state = Arc::downgrade(&self.state);
+        let callback = Arc::downgrade(&self.callback);
+        let token = conn.subscribe(
+            rule,
+            Box::new(move |signal| Self::handle_signal(&state, &callback, signal)),
+            timeout,
+        )?;
+
+        let owner = match conn.call::<_, (String,)>(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "GetNameOwner",
+            (name,),
+            calc_timeout_left(&start_time, timeout)?,
+        ) {
+            Ok((owner,)) => Some(owner),
+            Err(err) if err.well_known() == Some(WellKnownError::NameHasNoOwner) => None,
+            Err(err) => return Err(err),
+        };
+
+        // signals that arrived before this point are older than the reply
+        let change = {
+            let mut state = self.state.lock().unwrap();
+            state
+                .names
+                .insert(name.to_owned(), WatchedName { owner: None, token });
+            state.apply(NameOwnerChange {
+                name: name.to_owned(),
+                old_owner: None,
+                new_owner: owner.clone(),
+            })
+        };
+        if let Some(change) = change {
+            Self::notify(&self.callback, &change);
+        }
+        Ok(owner)
+    }
+
+    /// Stop watching the name. The changes for it that are queued already are kept.
+    pub fn unwatch(&self, conn: &mut RpcConn, name: &str) -> Result<()> {
+        let watched = self.state.lock().unwrap().names.remove(name);
+        match watched {
+            Some(watched) => conn.unsubscribe(watched.token),
+            None => Ok(()),
+        }
+    }
+
+    /// The names that are watched
+    pub fn names(&self) -> Vec<String> {
+        self.state.lock().unwrap().names.keys().cloned().collect()
+    }
+
+    /// The owner of a watched name as of the last processed signal. None if the name has no owner or is not watched.
+    pub fn owner(&self, name: &str) -> Option<String> {
+        self.state
+            .lock()
+            .unwrap()
+            .names
+            .get(name)
+            .and_then(|watched| watched.owner.clone())
+    }
+
+    /// Get a queued change without doing any IO
+    pub fn try_get_change(&self) -> Option<NameOwnerChange> {
+        self.state.lock().unwrap().changes.pop_front()
+    }
+
+    /// Read messages from the connection until a change is queued. Only useful if the changes are queued,
+    /// see `new`.
+    pub fn wait_change(&self, conn: &mut RpcConn, timeout: Timeout) -> Result<NameOwnerChange> {
+        let start_time = time::Instant::now();
+        loop {
+            if let Some(change) = self.try_get_change() {
+                return Ok(change);
+            }
+            conn.refill_once(calc_timeout_left(&start_time, timeout)?)?;
+        }
+    }
+
+    fn handle_signal(
+        state: &Weak<Mutex<State>>,
+        callback: &Weak<Mutex<Option<NameChangeCallback>>>,
+        signal: &MarshalledMessage,
+    ) {
+        // the watcher might be gone already, with the subscription being removed soon
+        let (state, callback) = match (state.upgrade(), callback.upgrade()) {
+            (Some(state), Some(callback)) => (state, callback),
+            _ => return,
+        };
+        let (name, old_owner, new_owner) = match signal.body.parser().get3::<&str, &str, &str>() {
+            Ok(args) => args,
+            Err(_) => return,
+        };
+        let owner = |owner: &str| {
+            if owner.is_empty() {
+                None
+            } else {
+                Some(owner.to_owned())
+            }
+        };
+        let change = state.lock().unwrap().apply(NameOwnerChange {
+            name: name.to_owned(),
+            old_owner: owner(old_owner),
+            new_owner: owner(new_owner),
+        });
+        if let Some(change) = change {
+            Self::notify(&callback, &change);
+        }
+    }
+
+    /// Call the callback without holding the lock on the state, so it can use the watcher
+    fn notify(callback: &Mutex<Option<NameChangeCallback>>, change: &NameOwnerChange) {
+        if let Some(callback) = callback.lock().unwrap().as_mut() {
+            callback(change);
+        }
+    }
+}
@@ -3,10 +3,16 @@
 
 use super::ll_conn::DuplexConn;
 use super::*;
+use crate::match_rule::MatchRule;
+use crate::message_builder::HeaderFlags;
 use crate::message_builder::MarshalledMessage;
 use crate::message_builder::MessageType;
+use crate::message_builder::{MarshalArgs, UnmarshalArgs};
+use crate::standard_messages::WellKnownError;
+use crate::wire::errors::UnmarshalError;
 use std::collections::HashMap;
 use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
 use std::time;
 
 /// Convenience wrapper around the lowlevel connection
@@ -38,6 +44,106 @@ pub struct RpcConn {
     responses: HashMap<u32, MarshalledMessage>,
     conn: DuplexConn,
     filter: MessageFilter,
+    subscriptions: Vec<Subscription>,
+    /// How many subscriptions use each rule that has been added to the bus
+    match_rules: HashMap<String, usize>,
+    next_subscription_id: u64,
+    dropped_subscriptions: Arc<Mutex<Vec<u64>>>,
+    /// Serials of the most recent calls sent with NO_REPLY_EXPECTED. Replies to these are dropped.
+    no_reply_serials: VecDeque<u32>,
+}
+
+/// How many serials of calls without expected replies are remembered. The bus drops replies to these calls anyway,
+/// this only protects against peers that reply to them.
+const MAX_NO_REPLY_SERIALS: usize = 64;
+
+/// An error reply returned by the called service (or the bus)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteError {
+    /// The error name, e.g. `org.freedesktop.DBus.Error.UnknownMethod`
+    pub name: String,
+    /// The well known error the name refers to, if any
+    pub kind: Option<WellKnownError>,
+    /// The error message, if the first parameter of the error reply is a string
+    pub message: Option<String>,
+}
+
+impl RemoteError {
+    pub fn from_message(msg: &MarshalledMessage) -> Self {
+        let name = msg.dynheader.error_name.clone().unwrap_or_default();
+        RemoteError {
+            kind: WellKnownError::from_name(&name),
+            name,
+            message: msg.body.parser().get::<String>().ok(),
+        }
+    }
+}
+
+impl std::fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "{}: {}", self.name, message),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Errors that can occur in `RpcConn::call`
+#[derive(Debug, thiserror::Error)]
+pub enum CallError {
+    /// Sending the call or receiving the reply failed
+    #[error("Connection error: {0}")]
+    Connection(#[from] Error),
+    /// The reply did not contain the expected values
+    #[error("Could not unmarshal the reply: {0}")]
+    Unmarshal(UnmarshalError),
+    /// The callee returned an error
+    #[error("The call returned an error: {0}")]
+    Remote(RemoteError),
+}
+
+impl CallError {
+    /// The well known error the callee returned, if it did return one
+    pub fn well_known(&self) -> Option<WellKnownError> {
+        match self {
+            CallError::Remote(err) => err.kind,
+            _ => None,
+        }
+    }
+}
+
+/// Called with every signal that matches the rule of a subscription, see `RpcConn::subscribe`
+pub type SignalCallback = Box<dyn FnMut(&MarshalledMessage) + Sync + Send>;
+
+enum SignalHandler {
+    Callback(SignalCallback),
+    Queue(VecDeque<MarshalledMessage>),
+}
+
+struct Subscription {
+    id: u64,
+    rule: MatchRule,
+    rule_str: String,
+    handler: SignalHandler,
+}
+
+/// Keeps a subscription made with `RpcConn::subscribe` or `RpcConn::subscribe_queued` alive.
+///
+/// When the token is dropped the subscription is removed. If no other subscription uses the same rule,
+/// RemoveMatch is sent to the bus the next time the RpcConn reads a message or subscribes, or when
+/// `RpcConn::flush_subscriptions` is called. Use `RpcConn::unsubscribe` to send it immediately.
+#[derive(Debug)]
+pub struct SubscriptionToken {
+    id: u64,
+    dropped: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Drop for SubscriptionToken {
+    fn drop(&mut self) {
+        if let Ok(mut dropped) = self.dropped.lock() {
+            dropped.push(self.id);
+        }
+    }
 }
 
 /// Filter out messages you dont want in your RpcConn.
@@ -84,6 +190,11 @@ impl RpcConn {
             responses: HashMap::new(),
             conn,
             filter: Box::new(|_| true),
+            subscriptions: Vec::new(),
+            match_rules: HashMap::new(),
+            next_subscription_id: 0,
+            dropped_subscriptions: Arc::new(Mutex::new(Vec::new())),
+            no_reply_serials: VecDeque::new(),
         }
     }
     pub fn conn(&self) -> &DuplexConn {
@@ -158,6 +269,236 @@ impl RpcConn {
         }
     }
 
+    /// Call a method and wait for the reply. `args` and the return type are tuples of the parameters,
+    /// e.g. `("name",)` for a single parameter and `()` for none. Error replies are returned as `CallError::Remote`.
+    ///
+    /// ```rust,no_run
+    /// use rustbus::{connection::Timeout, RpcConn};
+    ///
+    /// let mut rpc_con = RpcConn::session_conn(Timeout::Infinite).unwrap();
+    /// let (owner,): (String,) = rpc_con
+    ///     .call(
+    ///         "org.freedesktop.DBus",
+    ///         "/org/freedesktop/DBus",
+    ///         "org.freedesktop.DBus",
+    ///         "GetNameOwner",
+    ///         ("org.freedesktop.DBus",),
+    ///         Timeout::Infinite,
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn call<Args, Ret>(
+        &mut self,
+        destination: &str,
+        object: &str,
+        interface: &str,
+        member: &str,
+        args: Args,
+        timeout: Timeout,
+    ) -> std::result::Result<Ret, CallError>
+    where
+        Args: MarshalArgs,
+        Ret: for<'a> UnmarshalArgs<'a>,
+    {
+        let start_time = time::Instant::now();
+        let mut call = crate::message_builder::MessageBuilder::new()
+            .call(member)
+            .with_interface(interface)
+            .on(object)
+            .at(destination)
+            .build();
+        call.body.push_args(args).map_err(Error::from)?;
+
+        let serial = self
+            .send_message(&mut call)?
+            .write(calc_timeout_left(&start_time, timeout)?)
+            .map_err(ll_conn::force_finish_on_error)?;
+        let reply = self.wait_response(serial, calc_timeout_left(&start_time, timeout)?)?;
+        match reply.typ {
+            MessageType::Error => Err(CallError::Remote(RemoteError::from_message(&reply))),
+            _ => reply
+                .body
+                .parser()
+                .get_args::<Ret>()
+                .map_err(CallError::Unmarshal),
+        }
+    }
+
+    /// Subscribe to signals matching `rule`. The rule is added to the bus with AddMatch, unless another
+    /// subscription already uses the same rule. Matching signals are passed to `callback` when they are read
+    /// by any of the io-performing functions, instead of being put into the queue of `try_get_signal`/`wait_signal`.
+    ///
+    /// Signals still have to pass the filter set with `set_filter`.
+    ///
+    /// ```rust,no_run
+    /// use rustbus::{connection::Timeout, MatchRule, MessageType, RpcConn};
+    ///
+    /// let mut rpc_con = RpcConn::session_conn(Timeout::Infinite).unwrap();
+    /// let rule = MatchRule::new()
+    ///     .message_type(MessageType::Signal)
+    ///     .interface("org.freedesktop.DBus")
+    ///     .member("NameOwnerChanged");
+    /// let _token = rpc_con
+    ///     .subscribe(
+    ///         rule,
+    ///         Box::new(|sig| println!("{:?}", sig.dynheader)),
+    ///         Timeout::Infinite,
+    ///     )
+    ///     .unwrap();
+    /// loop {
+    ///     rpc_con.refill_once(Timeout::Infinite).unwrap();
+    /// }
+    /// ```
+    pub fn subscribe(
+        &mut self,
+        rule: MatchRule,
+        callback: SignalCallback,
+        timeout: Timeout,
+    ) -> Result<SubscriptionToken> {
+        self.add_subscription(rule, SignalHandler::Callback(callback), timeout)
+    }
+
+    /// Like `subscribe` but matching signals are put into a queue for this subscription.
+    /// Get them with `try_get_subscribed_signal`/`wait_subscribed_signal`.
+    pub fn subscribe_queued(
+        &mut self,
+        rule: MatchRule,
+        timeout: Timeout,
+    ) -> Result<SubscriptionToken> {
+        self.add_subscription(rule, SignalHandler::Queue(VecDeque::new()), timeout)
+    }
+
+    /// Remove the subscription immediately, sending RemoveMatch to the bus if no other subscription uses the same rule
+    pub fn unsubscribe(&mut self, token: SubscriptionToken) -> Result<()> {
+        self.remove_subscription(token.id)
+    }
+
+    /// Return a signal of a subscription made with `subscribe_queued` if one is there but dont block
+    pub fn try_get_subscribed_signal(
+        &mut self,
+        token: &SubscriptionToken,
+    ) -> Option<MarshalledMessage> {
+        let sub = self
+            .subscriptions
+            .iter_mut()
+            .find(|sub| sub.id == token.id)?;
+        match &mut sub.handler {
+            SignalHandler::Queue(queue) => queue.pop_front(),
+            SignalHandler::Callback(_) => None,
+        }
+    }
+
+    /// Return a signal of a subscription made with `subscribe_queued` if one is there or block until it arrives
+    pub fn wait_subscribed_signal(
+        &mut self,
+        token: &SubscriptionToken,
+        timeout: Timeout,
+    ) -> Result<MarshalledMessage> {
+        let start_time = time::Instant::now();
+        loop {
+            if let Some(msg) = self.try_get_subscribed_signal(token) {
+                return Ok(msg);
+            }
+            self.refill_once(calc_timeout_left(&start_time, timeout)?)?;
+        }
+    }
+
+    fn add_subscription(
+        &mut self,
+        rule: MatchRule,
+        handler: SignalHandler,
+        timeout: Timeout,
+    ) -> Result<SubscriptionToken> {
+        let start_time = time::Instant::now();
+        self.flush_subscriptions()?;
+
+        let rule_str = rule.to_string();
+        if !self.match_rules.contains_key(&rule_str) {
+            let add_match = crate::standard_messages::add_match(&rule_str);
+            let serial = self
+                .conn
+                .send
+                .send_message(&add_match)?
+                .write(calc_timeout_left(&start_time, timeout)?)
+                .map_err(ll_conn::force_finish_on_error)?;
+            let resp = self.wait_response(serial, calc_timeout_left(&start_time, timeout)?)?;
+            if resp.typ == MessageType::Error {
+                return Err(Error::MatchRuleRejected(
+                    resp.dynheader.error_name.unwrap_or_default(),
+                ));
+            }
+        }
+        *self.match_rules.entry(rule_str.clone()).or_insert(0) += 1;
+
+        let id = self.next_subscription_id;
+        self.next_subscription_id += 1;
+        self.subscriptions.push(Subscription {
+            id,
+            rule,
+            rule_str,
+            handler,
+        });
+        Ok(SubscriptionToken {
+            id,
+            dropped: self.dropped_subscriptions.clone(),
+        })
+    }
+
+    fn remove_subscription(&mut self, id: u64) -> Result<()> {
+        let idx = match self.subscriptions.iter().position(|sub| sub.id == id) {
+            Some(idx) => idx,
+            // already removed
+            None => return Ok(()),
+        };
+        let sub = self.subscriptions.remove(idx);
+        let count = self.match_rules.get_mut(&sub.rule_str).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.match_rules.remove(&sub.rule_str);
+            let mut remove_match = crate::standard_messages::remove_match(&sub.rule_str);
+            // nobody would collect the response
+            HeaderFlags::NoReplyExpected.set(&mut remove_match.flags);
+            self.conn
+                .send
+                .send_message(&remove_match)?
+                .write_all()
+                .map_err(ll_conn::force_finish_on_error)?;
+        }
+        Ok(())
+    }
+
+    /// Send RemoveMatch for the rules of all dropped `SubscriptionToken`s that no other subscription uses.
+    ///
+    /// Dropping a token can not send anything itself, so this happens the next time the RpcConn reads a message
+    /// or subscribes. Call this to not wait for that, e.g. if the connection is idle for a long time.
+    pub fn flush_subscriptions(&mut self) -> Result<()> {
+        let dropped = match self.dropped_subscriptions.lock() {
+            Ok(mut dropped) => std::mem::take(&mut *dropped),
+            Err(_) => return Ok(()),
+        };
+        for id in dropped {
+            self.remove_subscription(id)?;
+        }
+        Ok(())
+    }
+
+    /// Pass the signal to all subscriptions it matches or put it into the general signal queue if there are none
+    fn dispatch_signal(&mut self, msg: MarshalledMessage) {
+        let mut matched = false;
+        for sub in &mut self.subscriptions {
+            if sub.rule.matches(&msg) {
+                matched = true;
+                match &mut sub.handler {
+                    SignalHandler::Callback(callback) => callback(&msg),
+                    SignalHandler::Queue(queue) => queue.push_back(msg.clone()),
+                }
+            }
+        }
+        if !matched {
+            self.signals.push_back(msg);
+        }
+    }
+
     /// Return a call if one is there but dont block
     pub fn try_get_call(&mut self) -> Option<MarshalledMessage> {
         self.calls.pop_front()
@@ -175,11 +516,31 @@ impl RpcConn {
     }
 
     /// Send a message to the bus
+    ///
+    /// If the message is a call with the NO_REPLY_EXPECTED flag, replies to it are not kept by the RpcConn,
+    /// so don't wait for one.
     pub fn send_message<'a>(
         &'a mut self,
         msg: &'a mut crate::message_builder::MarshalledMessage,
     ) -> Result<super::ll_conn::SendMessageContext<'a>> {
-        self.conn.send.send_message(msg)
+        let ctx = self.conn.send.send_message(msg)?;
+        if msg.typ == MessageType::Call && !msg.expects_reply() {
+            if self.no_reply_serials.len() >= MAX_NO_REPLY_SERIALS {
+                self.no_reply_serials.pop_front();
+            }
+            self.no_reply_serials.push_back(ctx.serial());
+        }
+        Ok(ctx)
+    }
+
+    /// Store a reply or error so it can be retrieved with `wait_response`, unless nobody will ask for it
+    fn insert_response(&mut self, msg: MarshalledMessage) {
+        let serial = msg.dynheader.response_serial.unwrap();
+        if let Some(idx) = self.no_reply_serials.iter().position(|s| *s == serial) {
+            self.no_reply_serials.remove(idx);
+            return;
+        }
+        self.responses.insert(serial, msg);
     }
 
     fn insert_message_or_send_error(&mut self, msg: MarshalledMessage) -> Result<()> {
@@ -189,20 +550,18 @@ impl RpcConn {
                     self.calls.push_back(msg);
                 }
                 MessageType::Invalid => return Err(Error::UnexpectedMessageTypeReceived),
-                MessageType::Error => {
-                    self.responses
-                        .insert(msg.dynheader.response_serial.unwrap(), msg);
-                }
-                MessageType::Reply => {
-                    self.responses
-                        .insert(msg.dynheader.response_serial.unwrap(), msg);
+                MessageType::Error | MessageType::Reply => {
+                    self.insert_response(msg);
                 }
                 MessageType::Signal => {
-                    self.signals.push_back(msg);
+                    self.dispatch_signal(msg);
                 }
             }
         } else {
             match msg.typ {
+                MessageType::Call if !msg.expects_reply() => {
+                    // just drop it, the caller does not want an error
+                }
                 MessageType::Call => {
                     let reply = crate::standard_messages::unknown_method(&msg.dynheader);
                     self.conn
@@ -232,6 +591,7 @@ impl RpcConn {
     /// If a call is received that should be filtered out an error message is sent automatically
     pub fn try_refill_once(&mut self, timeout: Timeout) -> Result<Option<MessageType>> {
         let start_time = time::Instant::now();
+        self.flush_subscriptions()?;
         let msg = self
             .conn
             .recv
@@ -279,20 +639,18 @@ impl RpcConn {
                         self.calls.push_back(msg);
                     }
                     MessageType::Invalid => return Err(Error::UnexpectedMessageTypeReceived),
-                    MessageType::Error => {
-                        self.responses
-                            .insert(msg.dynheader.response_serial.unwrap(), msg);
-                    }
-                    MessageType::Reply => {
-                        self.responses
-                            .insert(msg.dynheader.response_serial.unwrap(), msg);
+                    MessageType::Error | MessageType::Reply => {
+                        self.insert_response(msg);
                     }
                     MessageType::Signal => {
-                        self.signals.push_back(msg);
+                        self.dispatch_signal(msg);
                     }
                 }
             } else {
                 match msg.typ {
+                    MessageType::Call if !msg.expects_reply() => {
+                        // just drop it, the caller does not want an error
+                    }
                     MessageType::Call => {
                         let reply = crate::standard_messages::unknown_method(&msg.dynheader);
                         filtered_out.push(reply);
@@ -0,0 +1,329 @@
+//! A variant of the `DispatchConn` that runs the handlers on a pool of worker threads.
+//!
+//! Routing works exactly like in the `DispatchConn`, but the handlers share one context that needs to be `Send + Sync`
+//! instead of getting a `&mut` to it. That way one slow handler does not stall the whole service. One thread reads the
+//! messages from the connection and puts them into the queues of the workers. The replies are written by the workers
+//! through the shared `SendConn`, one whole message at a time.
+//!
+//! By default calls to the same object may be handled in any order. If that matters, `set_per_object_ordering(true)`
+//! makes sure all messages for one object path are handled by the same worker in the order they arrived.
+
+use super::dispatch_conn::{
+    wait_readable, HandleError, HandleResult, Matches, PendingReply, Route, Router, StopHandle,
+};
+use super::ll_conn::DuplexConn;
+use super::ll_conn::RecvConn;
+use super::ll_conn::SendConn;
+use super::*;
+use crate::message_builder::MarshalledMessage;
+use crate::standard_messages::DBusError;
+use crate::standard_messages::WellKnownError;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+pub struct SharedHandleEnvironment<Ctx, UserError: std::fmt::Debug> {
+    pub conn: Arc<Mutex<SendConn>>,
+    pub new_dispatches: SharedPathMatcher<Ctx, UserError>,
+    reply_deferred: bool,
+}
+
+impl<Ctx, UserError: std::fmt::Debug> SharedHandleEnvironment<Ctx, UserError> {
+    /// Do not reply to the call now, see `HandleEnvironment::defer_reply`.
+    pub fn defer_reply(&mut self, call: &MarshalledMessage) -> PendingReply {
+        self.reply_deferred = true;
+        PendingReply::new(self.conn.clone(), call)
+    }
+}
+pub type SharedHandleFn<Ctx, UserError> = dyn Fn(
+        &Ctx,
+        Matches,
+        &MarshalledMessage,
+        &mut SharedHandleEnvironment<Ctx, UserError>,
+    ) -> HandleResult<UserError>
+    + Send
+    + Sync;
+/// The router used by the `ThreadedDispatchConn`
+pub type SharedPathMatcher<Ctx, UserError> = Router<Arc<SharedHandleFn<Ctx, UserError>>>;
+/// Gets called with the errors that happen on the worker threads. The returned message is sent as the reply
+/// to the call that failed.
+pub type ErrorHandleFn<UserError> =
+    dyn Fn(&MarshalledMessage, HandleError<UserError>) -> Option<MarshalledMessage> + Send + Sync;
+
+pub struct ThreadedDispatchConn<Ctx, UserError: std::fmt::Debug> {
+    recv: RecvConn,
+    send: Arc<Mutex<SendConn>>,
+    objects: Mutex<SharedPathMatcher<Ctx, UserError>>,
+    default_handler: Box<SharedHandleFn<Ctx, UserError>>,
+    // None answers with the DBusError of the error, see default_error_handler
+    error_handler: Option<Box<ErrorHandleFn<UserError>>>,
+    ctx: Ctx,
+    worker_threads: usize,
+    per_object_ordering: bool,
+}
+
+/// The number of worker threads if not configured otherwise
+pub const DEFAULT_WORKER_THREADS: usize = 4;
+
+/// Answers failed calls with the error reply the `DBusError` impl of the error describes
+fn default_error_handler<UserError: std::fmt::Debug + DBusError>(
+    msg: &MarshalledMessage,
+    err: HandleError<UserError>,
+) -> Option<MarshalledMessage> {
+    err.to_error_reply(&msg.dynheader)
+}
+
+impl<Ctx: Send + Sync, UserError: std::fmt::Debug + 'static> ThreadedDispatchConn<Ctx, UserError> {
+    pub fn new(
+        conn: DuplexConn,
+        ctx: Ctx,
+        default_handler: Box<SharedHandleFn<Ctx, UserError>>,
+    ) -> Self {
+        Self {
+            recv: conn.recv,
+            send: Arc::new(Mutex::new(conn.send)),
+            objects: Mutex::new(Router::new()),
+            default_handler,
+            error_handler: None,
+            ctx,
+            worker_threads: DEFAULT_WORKER_THREADS,
+            per_object_ordering: false,
+        }
+    }
+
+    /// Add a handler for all messages to objects matching the path pattern. See `Router::insert`.
+    pub fn add_handler(&mut self, path: &str, handler: Box<SharedHandleFn<Ctx, UserError>>) {
+        self.objects
+            .get_mut()
+            .unwrap()
+            .insert(path, Arc::from(handler));
+    }
+
+    /// Add a handler for one method of the objects matching the path pattern. See `Router::insert_method`.
+    pub fn add_method_handler(
+        &mut self,
+        path: &str,
+        interface: &str,
+        member: &str,
+        signature: Option<&str>,
+        handler: Box<SharedHandleFn<Ctx, UserError>>,
+    ) {
+        self.objects.get_mut().unwrap().insert_method(
+            path,
+            interface,
+            member,
+            signature,
+            Arc::from(handler),
+        );
+    }
+
+    /// Replace the handler for errors on the worker threads. For errors returned by handlers the message this returns
+    /// is sent as reply. If sending a reply fails it is called with a `HandleError::Connection` and the returned
+    /// message is dropped.
+    ///
+    /// The default answers failed calls with the reply created by `HandleError::to_error_reply`.
+    pub fn set_error_handler(&mut self, handler: Box<ErrorHandleFn<UserError>>) {
+        self.error_handler = Some(handler);
+    }
+
+    pub fn worker_threads(&self) -> usize {
+        self.worker_threads
+    }
+
+    /// Set the number of threads the handlers are run on. Needs at least one.
+    pub fn set_worker_threads(&mut self, threads: usize) {
+        self.worker_threads = threads.max(1);
+    }
+
+    pub fn per_object_ordering(&self) -> bool {
+        self.per_object_ordering
+    }
+
+    /// If enabled all messages to the same object path are handled one after another in the order they arrived.
+    /// The object paths are spread over one queue per worker thread by their hash, so messages to other objects
+    /// that end up in the same queue also wait for each other.
+    pub fn set_per_object_ordering(&mut self, ordered: bool) {
+        self.per_object_ordering = ordered;
+    }
+}
+
+/// Running the dispatcher needs to know how to answer failed calls, so the error type has to implement `DBusError`.
+impl<Ctx: Send + Sync, UserError: std::fmt::Debug + DBusError + 'static>
+    ThreadedDispatchConn<Ctx, UserError>
+{
+    /// Starts the workers and then loops endlessly, reading messages and handing them to the workers. This only returns
+    /// if reading from the connection fails, or with `Error::WorkersStopped` if the workers for a message are gone. The
+    /// messages that were already read will be handled before this returns.
+    /// Errors in the handlers are passed to the error handler (see `set_error_handler`). Calls whose handler panicked
+    /// are answered with `org.freedesktop.DBus.Error.Failed`.
+    ///
+    /// Like the `DispatchConn` this sends the responses returned by the handlers back to the callers, or a default
+    /// response with no content if they returned None. Only calls are answered, and only if the caller did not set the
+    /// NO_REPLY_EXPECTED flag and the handler did not defer the reply.
+    pub fn run(&mut self) -> Result<()> {
+        self.run_loop(None)
+    }
+
+    /// Like `run` but also returns Ok once the `StopHandle` was triggered. All messages that were read until then are
+    /// handled and answered before this returns.
+    pub fn run_until(&mut self, stop: &StopHandle) -> Result<()> {
+        self.run_loop(Some(stop))
+    }
+
+    fn run_loop(&mut self, stop: Option<&StopHandle>) -> Result<()> {
+        let queues = if self.per_object_ordering {
+            self.worker_threads
+        } else {
+            1
+        };
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..queues)
+            .map(|_| {
+                let (tx, rx) = mpsc::channel::<MarshalledMessage>();
+                (tx, Arc::new(Mutex::new(rx)))
+            })
+            .unzip();
+
+        let worker_threads = self.worker_threads;
+        let recv = &mut self.recv;
+        let worker = Worker {
+            send: &self.send,
+            objects: &self.objects,
+            default_handler: self.default_handler.as_ref(),
+            error_handler: self
+                .error_handler
+                .as_deref()
+                .unwrap_or(&default_error_handler),
+            ctx: &self.ctx,
+        };
+
+        std::thread::scope(|scope| {
+            // owned by this closure, so they are dropped when it returns. That lets the workers finish before the
+            // scope waits for them.
+            let senders = senders;
+            for idx in 0..worker_threads {
+                let jobs = receivers[idx % queues].clone();
+                let worker = &worker;
+                scope.spawn(move || worker.work(&jobs));
+            }
+            // only the workers keep the queues open, so sending fails if all workers of a queue are gone
+            drop(receivers);
+
+            loop {
+                if stop.map(StopHandle::is_stopped).unwrap_or(false) {
+                    return Ok(());
+                }
+                if !wait_readable(recv, stop, None)? {
+                    continue;
+                }
+                // There is data, but maybe not the whole message yet. Whatever is there is kept for the next try.
+                let msg = match recv.get_next_message(Timeout::Nonblock) {
+                    Ok(msg) => msg,
+                    Err(Error::TimedOut) => continue,
+                    Err(e) => return Err(e),
+                };
+                let idx = if queues == 1 {
+                    0
+                } else {
+                    let mut hasher = DefaultHasher::new();
+                    msg.dynheader.object.hash(&mut hasher);
+                    (hasher.finish() % queues as u64) as usize
+                };
+                if senders[idx].send(msg).is_err() {
+                    return Err(Error::WorkersStopped);
+                }
+            }
+        })
+    }
+}
+
+struct Worker<'a, Ctx, UserError: std::fmt::Debug> {
+    send: &'a Arc<Mutex<SendConn>>,
+    objects: &'a Mutex<SharedPathMatcher<Ctx, UserError>>,
+    default_handler: &'a SharedHandleFn<Ctx, UserError>,
+    error_handler: &'a ErrorHandleFn<UserError>,
+    ctx: &'a Ctx,
+}
+
+impl<Ctx, UserError: std::fmt::Debug> Worker<'_, Ctx, UserError> {
+    fn work(&self, jobs: &Mutex<mpsc::Receiver<MarshalledMessage>>) {
+        loop {
+            // only hold the lock while waiting, so other workers can take the next message while this one is busy
+            let msg = jobs.lock().unwrap_or_else(PoisonError::into_inner).recv();
+            match msg {
+                Ok(msg) => self.handle(msg),
+                Err(_) => return,
+            }
+        }
+    }
+
+    fn handle(&self, msg: MarshalledMessage) {
+        let mut env = SharedHandleEnvironment {
+            conn: self.send.clone(),
+            new_dispatches: Router::new(),
+            reply_deferred: false,
+        };
+
+        // do not hold the lock on the router while the handler runs
+        // a handler that panicked while holding one of the locks must not take the other workers down with it
+        let route = match self
+            .objects
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .route(&msg)
+        {
+            Route::Handler(matches, handler) => Ok(Some((matches, handler.clone()))),
+            Route::Error(error) => Err(error),
+            Route::NoObject => Ok(None),
+        };
+        // a panicking handler would take the worker and with it the whole queue down
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| match route {
+            Ok(Some((matches, handler))) => handler(self.ctx, matches, &msg, &mut env),
+            Ok(None) => (self.default_handler)(self.ctx, Matches::default(), &msg, &mut env),
+            Err(error) => Ok(Some(*error)),
+        }));
+
+        let response = match result {
+            Ok(Ok(response)) => {
+                // apply the new pathes established in the handler
+                self.objects
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .merge(env.new_dispatches);
+                Some(response.unwrap_or_else(|| msg.dynheader.make_response()))
+            }
+            Ok(Err(error)) => (self.error_handler)(&msg, error),
+            Err(_) => Some(msg.dynheader.make_error_response(
+                WellKnownError::Failed.name(),
+                Some("The handler for this call panicked".to_owned()),
+            )),
+        };
+
+        if !msg.expects_reply() {
+            // signals and replies are never answered, and the caller may not want a response
+            return;
+        }
+        if env.reply_deferred {
+            // the handler will reply later through a PendingReply
+            return;
+        }
+        if let Some(response) = response {
+            let sent = self
+                .send
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .send_message(&response)
+                .and_then(|ctx| {
+                    ctx.write_all()
+                        .map_err(|(ctx, e)| ll_conn::force_finish_on_error((ctx, e)))
+                });
+            if let Err(error) = sent {
+                (self.error_handler)(&msg, HandleError::Connection(error));
+            }
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! Human readable renderings of messages for debugging
+//!
+//! * `dbus_monitor` renders a message like the dbus-monitor tool does, a header line followed by the typed and indented
+//!   values of the body. `dbus_monitor_body` renders only the values.
+//! * `busctl_json` renders a message like `busctl --json=pretty` does, using the conversion in `params::json`
+//!
+//! Dicts are unmarshalled into hashmaps, so their entries are printed sorted by key instead of in the order they had
+//! on the wire. Unix fds are printed as their index into the fds of the message.
+//!
+//! ```rust
+//! use rustbus::format;
+//! use rustbus::MessageBuilder;
+//!
+//! let mut msg = MessageBuilder::new()
+//!     .signal("io.killing.spark", "Ping", "/io/killing/spark")
+//!     .build();
+//! msg.body.push_param("hello").unwrap();
+//! assert_eq!(
+//!     format::dbus_monitor(&msg, None).unwrap(),
+//!     "signal sender=(null sender) -> destination=(null destination) serial=0 path=/io/killing/spark; \
+//!      interface=io.killing.spark; member=Ping\n   string \"hello\"\n"
+//! );
+//! ```
+
+use crate::message_builder::{MarshalledMessage, MessageType};
+use crate::params::json::{self, Json};
+use crate::params::{Base, Container, DictMap, Param};
+use crate::wire::errors::UnmarshalError;
+use crate::wire::UnixFd;
+
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MONITOR_INDENT: usize = 3;
+const BYTES_PER_LINE: usize = 16;
+
+/// The index of the fd in the fds of the message. The unmarshalled fds share their state with the ones in the message.
+fn fd_index(fd: &UnixFd, fds: &[UnixFd]) -> Option<usize> {
+    fds.iter().position(|other| other == fd)
+}
+
+/// Seconds and microseconds since the epoch
+fn split_time(time: SystemTime) -> (u64, u32) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    (since_epoch.as_secs(), since_epoch.subsec_micros())
+}
+
+/// Render the message like dbus-monitor does. The time is only printed if it is given.
+pub fn dbus_monitor(
+    msg: &MarshalledMessage,
+    received: Option<SystemTime>,
+) -> Result<String, UnmarshalError> {
+    let params = msg.body.unmarshal_params()?;
+    let hdr = &msg.dynheader;
+    let opt = |val: &Option<String>| val.clone().unwrap_or_else(|| "(null)".to_owned());
+
+    let mut out = String::new();
+    out.push_str(match msg.typ {
+        MessageType::Call => "method call",
+        MessageType::Reply => "method return",
+        MessageType::Error => "error",
+        MessageType::Signal => "signal",
+        MessageType::Invalid => "invalid",
+    });
+    if let Some(received) = received {
+        let (secs, micros) = split_time(received);
+        write!(out, " time={}.{:06}", secs, micros).unwrap();
+    }
+    write!(
+        out,
+        " sender={} -> destination={}",
+        hdr.sender.as_deref().unwrap_or("(null sender)"),
+        hdr.destination.as_deref().unwrap_or("(null destination)"),
+    )
+    .unwrap();
+    match msg.typ {
+        MessageType::Call | MessageType::Signal => write!(
+            out,
+            " serial={} path={}; interface={}; member={}",
+            hdr.serial.unwrap_or(0),
+            opt(&hdr.object),
+            opt(&hdr.interface),
+            opt(&hdr.member),
+        ),
+        MessageType::Reply => write!(
+            out,
+            " serial={} reply_serial={}",
+            hdr.serial.unwrap_or(0),
+            hdr.response_serial.unwrap_or(0),
+        ),
+        MessageType::Error => write!(
+            out,
+            " error_name={} reply_serial={}",
+            opt(&hdr.error_name),
+            hdr.response_serial.unwrap_or(0),
+        ),
+        MessageType::Invalid => Ok(()),
+    }
+    .unwrap();
+    out.push('\n');
+    monitor_body(&params, &msg.body.raw_fds, &mut out);
+    Ok(out)
+}
+
+/// Render only the body of the message like dbus-monitor does, without the header line
+pub fn dbus_monitor_body(msg: &MarshalledMessage) -> Result<String, UnmarshalError> {
+    let params = msg.body.unmarshal_params()?;
+    let mut out = String::new();
+    monitor_body(&params, &msg.body.raw_fds, &mut out);
+    Ok(out)
+}
+
+fn monitor_body(params: &[Param], fds: &[UnixFd], out: &mut String) {
+    for param in params {
+        monitor_param(param, fds, 1, out);
+    }
+}
+
+fn indent(depth: usize, out: &mut String) {
+    for _ in 0..depth * MONITOR_INDENT {
+        out.push(' ');
+    }
+}
+
+fn monitor_param(param: &Param, fds: &[UnixFd], depth: usize, out: &mut String) {
+    indent(depth, out);
+    match param {
+        Param::Base(base) => {
+            monitor_base(base, fds, out);
+            out.push('\n');
+        }
+        Param::Container(Container::Array(array)) => {
+            monitor_array(&array.element_sig, &array.values, fds, depth, out)
+        }
+        Param::Container(Container::ArrayRef(array)) => {
+            monitor_array(&array.element_sig, array.values, fds, depth, out)
+        }
+        Param::Container(Container::Struct(fields)) => monitor_struct(fields, fds, depth, out),
+        Param::Container(Container::StructRef(fields)) => monitor_struct(fields, fds, depth, out),
+        Param::Container(Container::Dict(dict)) => monitor_dict(&dict.map, fds, depth, out),
+        Param::Container(Container::DictRef(dict)) => monitor_dict(dict.map, fds, depth, out),
+        Param::Container(Container::Variant(variant)) => {
+            // dbus-monitor does not break the line after "variant", the value is just indented one level deeper
+            out.push_str("variant ");
+            monitor_param(&variant.value, fds, depth + 1, out);
+        }
+    }
+}
+
+fn monitor_base(base: &Base, fds: &[UnixFd], out: &mut String) {
+    match base {
+        &Base::Double(bits) | &Base::DoubleRef(&bits) => {
+            write!(out, "double {}", f64::from_bits(bits))
+        }
+        &Base::Byte(val) | &Base::ByteRef(&val) => write!(out, "byte {}", val),
+        &Base::Int16(val) | &Base::Int16Ref(&val) => write!(out, "int16 {}", val),
+        &Base::Uint16(val) | &Base::Uint16Ref(&val) => write!(out, "uint16 {}", val),
+        &Base::Int32(val) | &Base::Int32Ref(&val) => write!(out, "int32 {}", val),
+        &Base::Uint32(val) | &Base::Uint32Ref(&val) => write!(out, "uint32 {}", val),
+        &Base::Int64(val) | &Base::Int64Ref(&val) => write!(out, "int64 {}", val),
+        &Base::Uint64(val) | &Base::Uint64Ref(&val) => write!(out, "uint64 {}", val),
+        &Base::Boolean(val) | &Base::BooleanRef(&val) => write!(out, "boolean {}", val),
+        Base::String(val) => write!(out, "string \"{}\"", val),
+        Base::StringRef(val) => write!(out, "string \"{}\"", val),
+        Base::Signature(val) => write!(out, "signature \"{}\"", val),
+        Base::SignatureRef(val) => write!(out, "signature \"{}\"", val),
+        Base::ObjectPath(val) => write!(out, "object path \"{}\"", val),
+        Base::ObjectPathRef(val) => write!(out, "object path \"{}\"", val),
+        Base::UnixFd(fd) => monitor_fd(fd, fds, out),
+        Base::UnixFdRef(fd) => monitor_fd(fd, fds, out),
+    }
+    .unwrap();
+}
+
+fn monitor_fd(fd: &UnixFd, fds: &[UnixFd], out: &mut String) -> std::fmt::Result {
+    match fd_index(fd, fds) {
+        Some(idx) => write!(out, "file descriptor {}", idx),
+        None => write!(out, "file descriptor (unknown)"),
+    }
+}
+
+fn monitor_array(
+    element_sig: &crate::signature::Type,
+    values: &[Param],
+    fds: &[UnixFd],
+    depth: usize,
+    out: &mut String,
+) {
+    if *element_sig == crate::signature::Type::Base(crate::signature::Base::Byte) {
+        return monitor_bytes(values, depth, out);
+    }
+    out.push_str("array [\n");
+    for value in values {
+        monitor_param(value, fds, depth + 1, out);
+    }
+    indent(depth, out);
+    out.push_str("]\n");
+}
+
+/// Byte arrays are printed as a string if they only contain printable characters, otherwise as hex
+fn monitor_bytes(values: &[Param], depth: usize, out: &mut String) {
+    let bytes: Vec<u8> = values
+        .iter()
+        .filter_map(|value| match value {
+            &Param::Base(Base::Byte(byte)) | &Param::Base(Base::ByteRef(&byte)) => Some(byte),
+            _ => None,
+        })
+        .collect();
+    if !bytes.is_empty() && bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+        // only printable ascii, so this is valid utf8
+        writeln!(
+            out,
+            "array of bytes \"{}\"",
+            String::from_utf8_lossy(&bytes)
+        )
+        .unwrap();
+        return;
+    }
+    out.push_str("array of bytes [\n");
+    for line in bytes.chunks(BYTES_PER_LINE) {
+        indent(depth + 1, out);
+        let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+        out.push_str(&hex.join(" "));
+        out.push('\n');
+    }
+    indent(depth, out);
+    out.push_str("]\n");
+}
+
+fn monitor_struct(fields: &[Param], fds: &[UnixFd], depth: usize, out: &mut String) {
+    out.push_str("struct {\n");
+    for field in fields {
+        monitor_param(field, fds, depth + 1, out);
+    }
+    indent(depth, out);
+    out.push_str("}\n");
+}
+
+fn monitor_dict(map: &DictMap, fds: &[UnixFd], depth: usize, out: &mut String) {
+    out.push_str("array [\n");
+    for (key, value) in sorted_entries(map, fds) {
+        indent(depth + 1, out);
+        out.push_str("dict entry(\n");
+        indent(depth + 2, out);
+        monitor_base(key, fds, out);
+        out.push('\n');
+        monitor_param(value, fds, depth + 2, out);
+        indent(depth + 1, out);
+        out.push_str(")\n");
+    }
+    indent(depth, out);
+    out.push_str("]\n");
+}
+
+/// The entries of a dict sorted by their rendered key, so the output does not depend on the order of the hashmap
+fn sorted_entries<'m, 'a, 'e>(
+    map: &'m DictMap<'a, 'e>,
+    fds: &[UnixFd],
+) -> Vec<(&'m Base<'a>, &'m Param<'a, 'e>)> {
+    let mut entries: Vec<_> = map
+        .iter()
+        .map(|(key, value)| {
+            let mut rendered = String::new();
+            monitor_base(key, fds, &mut rendered);
+            (rendered, key, value)
+        })
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+        .into_iter()
+        .map(|(_, key, value)| (key, value))
+        .collect()
+}
+
+/// Render the message like `busctl --json=pretty` does. The timestamp is only included if the time is given.
+pub fn busctl_json(
+    msg: &MarshalledMessage,
+    received: Option<SystemTime>,
+) -> Result<String, UnmarshalError> {
+    let timestamp = received.map(|received| {
+        let (secs, micros) = split_time(received);
+        secs * 1_000_000 + micros as u64
+    });
+    let root = json::message_object(msg, timestamp)?;
+    let mut out = String::new();
+    write_busctl(&root, 0, &mut out);
+    out.push('\n');
+    Ok(out)
+}
+
+/// Write the value indented with tabs like busctl does
+fn write_busctl(value: &Json, depth: usize, out: &mut String) {
+    let tabs = |depth: usize, out: &mut String| {
+        for _ in 0..depth {
+            out.push('\t');
+        }
+    };
+    match value {
+        Json::Array(values) if values.is_empty() => out.push_str("[]"),
+        Json::Object(members) if members.is_empty() => out.push_str("{}"),
+        Json::Array(values) => {
+            out.push_str("[\n");
+            for (idx, value) in values.iter().enumerate() {
+                tabs(depth + 1, out);
+                write_busctl(value, depth + 1, out);
+                if idx + 1 < values.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            tabs(depth, out);
+            out.push(']');
+        }
+        Json::Object(members) => {
+            out.push_str("{\n");
+            for (idx, (key, value)) in members.iter().enumerate() {
+                tabs(depth + 1, out);
+                json::write_string(key, out).unwrap();
+                out.push_str(" : ");
+                write_busctl(value, depth + 1, out);
+                if idx + 1 < members.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            tabs(depth, out);
+            out.push('}');
+        }
+        other => write!(out, "{}", other).unwrap(),
+    }
+}
@@ -95,6 +95,8 @@
 
 pub mod auth;
 pub mod connection;
+pub mod format;
+pub mod match_rule;
 pub mod message_builder;
 pub mod params;
 pub mod peer;
@@ -108,6 +110,7 @@ pub use rustbus_derive::*;
 // TODO create a rustbus::prelude
 
 // needed to make own filters in RpcConn
+pub use match_rule::MatchRule;
 pub use message_builder::MessageType;
 
 // needed to create a connection
@@ -124,6 +127,9 @@ pub use wire::marshal::traits::Marshal;
 pub use wire::marshal::traits::Signature;
 pub use wire::unmarshal::traits::Unmarshal;
 
+// needed to answer failed calls
+pub use standard_messages::DBusError;
+
 #[cfg(test)]
 mod tests;
 
@@ -0,0 +1,612 @@
+//! Match rules as used by the `AddMatch` and `RemoveMatch` calls of the bus
+//!
+//! A `MatchRule` can be serialized into the string the bus expects, parsed back from such a string and evaluated
+//! against messages locally. That way the same rule can be sent to the bus and used to route the messages
+//! that arrive because of it.
+//!
+//! ```rust
+//! use rustbus::match_rule::MatchRule;
+//! use rustbus::MessageType;
+//!
+//! let rule = MatchRule::new()
+//!     .message_type(MessageType::Signal)
+//!     .interface("org.freedesktop.DBus")
+//!     .member("NameOwnerChanged")
+//!     .arg(0, "io.killing.spark");
+//! let rule_str = rule.to_string();
+//! assert_eq!(
+//!     rule_str,
+//!     "type='signal',interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0='io.killing.spark'"
+//! );
+//! assert_eq!(rule, rule_str.parse().unwrap());
+//!
+//! // send this to the bus
+//! let add_match = rustbus::standard_messages::add_match(&rule_str);
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::message_builder::MarshalledMessage;
+use crate::message_builder::MessageType;
+use crate::params::validation;
+use crate::wire::ObjectPath;
+
+use thiserror::Error;
+
+/// The highest argument index the spec allows in `argN` and `argNpath` keys
+pub const MAX_ARG_INDEX: u8 = 63;
+
+/// Errors that can occur while parsing a match rule
+#[derive(Debug, Eq, PartialEq, Error)]
+pub enum Error {
+    #[error("The key {0} has no value")]
+    MissingValue(String),
+    #[error("A quoted value is not terminated")]
+    UnterminatedQuote,
+    #[error("Unknown key: {0}")]
+    UnknownKey(String),
+    #[error("The key {0} appears more than once")]
+    DuplicatedKey(String),
+    #[error("The key {0} has an invalid value")]
+    InvalidValue(String),
+    #[error("A rule can not contain both path and path_namespace")]
+    PathAndPathNamespace,
+    #[error("Invalid value: {0}")]
+    Validation(validation::Error),
+}
+
+impl From<validation::Error> for Error {
+    fn from(e: validation::Error) -> Self {
+        Error::Validation(e)
+    }
+}
+
+/// A match rule. All keys that are set must match for the rule to match a message.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MatchRule {
+    pub typ: Option<MessageType>,
+    pub sender: Option<String>,
+    pub interface: Option<String>,
+    pub member: Option<String>,
+    pub path: Option<String>,
+    pub path_namespace: Option<String>,
+    pub destination: Option<String>,
+    /// `argN` keys, matching string arguments by equality
+    pub args: BTreeMap<u8, String>,
+    /// `argNpath` keys, matching string or object path arguments as paths
+    pub arg_paths: BTreeMap<u8, String>,
+    pub arg0_namespace: Option<String>,
+    pub eavesdrop: Option<bool>,
+}
+
+impl MatchRule {
+    /// A rule without any keys, which matches every message
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match messages of this type. `MessageType::Invalid` does not restrict the type, like leaving the key out.
+    pub fn message_type(mut self, typ: MessageType) -> Self {
+        self.typ = match typ {
+            MessageType::Invalid => None,
+            typ => Some(typ),
+        };
+        self
+    }
+    pub fn sender<S: Into<String>>(mut self, sender: S) -> Self {
+        self.sender = Some(sender.into());
+        self
+    }
+    pub fn interface<S: Into<String>>(mut self, interface: S) -> Self {
+        self.interface = Some(interface.into());
+        self
+    }
+    pub fn member<S: Into<String>>(mut self, member: S) -> Self {
+        self.member = Some(member.into());
+        self
+    }
+    /// Match messages with exactly this object path. This replaces a `path_namespace` that was set before.
+    pub fn path<S: Into<String>>(mut self, path: S) -> Self {
+        self.path = Some(path.into());
+        self.path_namespace = None;
+        self
+    }
+    /// Match messages with this object path or any path below it. This replaces a `path` that was set before.
+    pub fn path_namespace<S: Into<String>>(mut self, namespace: S) -> Self {
+        self.path_namespace = Some(namespace.into());
+        self.path = None;
+        self
+    }
+    pub fn destination<S: Into<String>>(mut self, destination: S) -> Self {
+        self.destination = Some(destination.into());
+        self
+    }
+    /// Match messages whose argument number `idx` is a string equal to `value`.
+    ///
+    /// Panics if `idx` is bigger than `MAX_ARG_INDEX`
+    pub fn arg<S: Into<String>>(mut self, idx: u8, value: S) -> Self {
+        assert!(idx <= MAX_ARG_INDEX, "Argument index out of range");
+        self.args.insert(idx, value.into());
+        self
+    }
+    /// Match messages whose argument number `idx` is a string or object path that is equal to `path`, or where either
+    /// one ends with a '/' and is a prefix of the other.
+    ///
+    /// Panics if `idx` is bigger than `MAX_ARG_INDEX`
+    pub fn arg_path<S: Into<String>>(mut self, idx: u8, path: S) -> Self {
+        assert!(idx <= MAX_ARG_INDEX, "Argument index out of range");
+        self.arg_paths.insert(idx, path.into());
+        self
+    }
+    /// Match messages whose first argument is a string that is a bus name or interface in this namespace
+    pub fn arg0_namespace<S: Into<String>>(mut self, namespace: S) -> Self {
+        self.arg0_namespace = Some(namespace.into());
+        self
+    }
+    pub fn eavesdrop(mut self, eavesdrop: bool) -> Self {
+        self.eavesdrop = Some(eavesdrop);
+        self
+    }
+
+    /// Evaluate the rule against a message.
+    ///
+    /// Note that the bus matches `sender` against unique and well-known names of the sender, but locally only the
+    /// sender field of the message (which is always a unique name) is compared. `eavesdrop` is ignored.
+    pub fn matches(&self, msg: &MarshalledMessage) -> bool {
+        let hdr = &msg.dynheader;
+        fn eq(rule: &Option<String>, field: &Option<String>) -> bool {
+            rule.is_none() || rule == field
+        }
+        if let Some(typ) = self.typ.filter(|typ| *typ != MessageType::Invalid) {
+            if typ != msg.typ {
+                return false;
+            }
+        }
+        if !eq(&self.sender, &hdr.sender)
+            || !eq(&self.interface, &hdr.interface)
+            || !eq(&self.member, &hdr.member)
+            || !eq(&self.path, &hdr.object)
+            || !eq(&self.destination, &hdr.destination)
+        {
+            return false;
+        }
+        if let Some(namespace) = &self.path_namespace {
+            match &hdr.object {
+                Some(path) if path_in_namespace(path, namespace) => {}
+                _ => return false,
+            }
+        }
+        if self.args.is_empty() && self.arg_paths.is_empty() && self.arg0_namespace.is_none() {
+            return true;
+        }
+        self.args_match(msg)
+    }
+
+    fn args_match(&self, msg: &MarshalledMessage) -> bool {
+        let last_idx = [
+            self.args.keys().next_back(),
+            self.arg_paths.keys().next_back(),
+            self.arg0_namespace.as_ref().map(|_| &0),
+        ]
+        .iter()
+        .flatten()
+        .map(|idx| **idx)
+        .max()
+        .unwrap_or(0);
+
+        let mut parser = msg.body.parser();
+        for idx in 0..=last_idx {
+            let arg = self.args.get(&idx);
+            let arg_path = self.arg_paths.get(&idx);
+            let namespace = if idx == 0 {
+                self.arg0_namespace.as_ref()
+            } else {
+                None
+            };
+            if arg.is_none() && arg_path.is_none() && namespace.is_none() {
+                if parser.skip().is_err() {
+                    return false;
+                }
+                continue;
+            }
+
+            let value = match parser.get_next_sig() {
+                Some("s") => parser.get::<&str>(),
+                // only argNpath can match object paths
+                Some("o") if arg.is_none() && namespace.is_none() => {
+                    parser.get::<ObjectPath<&str>>().map(ObjectPath::into_inner)
+                }
+                _ => return false,
+            };
+            let value = match value {
+                Ok(value) => value,
+                Err(_) => return false,
+            };
+
+            if let Some(arg) = arg {
+                if arg != value {
+                    return false;
+                }
+            }
+            if let Some(arg_path) = arg_path {
+                if !arg_path_matches(arg_path, value) {
+                    return false;
+                }
+            }
+            if let Some(namespace) = namespace {
+                if !name_in_namespace(value, namespace) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn set_key(&mut self, key: &str, value: String) -> Result<(), Error> {
+        fn set(field: &mut Option<String>, key: &str, value: String) -> Result<(), Error> {
+            if field.is_some() {
+                return Err(Error::DuplicatedKey(key.to_owned()));
+            }
+            *field = Some(value);
+            Ok(())
+        }
+        match key {
+            "type" => {
+                if self.typ.is_some() {
+                    return Err(Error::DuplicatedKey(key.to_owned()));
+                }
+                self.typ = Some(match value.as_str() {
+                    "signal" => MessageType::Signal,
+                    "method_call" => MessageType::Call,
+                    "method_return" => MessageType::Reply,
+                    "error" => MessageType::Error,
+                    _ => return Err(Error::InvalidValue(key.to_owned())),
+                });
+            }
+            "sender" => {
+                validation::validate_busname(&value)?;
+                set(&mut self.sender, key, value)?;
+            }
+            "interface" => {
+                validation::validate_interface(&value)?;
+                set(&mut self.interface, key, value)?;
+            }
+            "member" => {
+                validation::validate_membername(&value)?;
+                set(&mut self.member, key, value)?;
+            }
+            "path" => {
+                validation::validate_object_path(&value)?;
+                set(&mut self.path, key, value)?;
+            }
+            "path_namespace" => {
+                validation::validate_object_path(&value)?;
+                set(&mut self.path_namespace, key, value)?;
+            }
+            "destination" => {
+                validation::validate_busname(&value)?;
+                set(&mut self.destination, key, value)?;
+            }
+            "arg0namespace" => set(&mut self.arg0_namespace, key, value)?,
+            "eavesdrop" => {
+                if self.eavesdrop.is_some() {
+                    return Err(Error::DuplicatedKey(key.to_owned()));
+                }
+                self.eavesdrop = Some(match value.as_str() {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(Error::InvalidValue(key.to_owned())),
+                });
+            }
+            _ => {
+                let (idx, map) = if let Some(idx) = key
+                    .strip_prefix("arg")
+                    .and_then(|rest| rest.strip_suffix("path"))
+                {
+                    (idx, &mut self.arg_paths)
+                } else if let Some(idx) = key.strip_prefix("arg") {
+                    (idx, &mut self.args)
+                } else {
+                    return Err(Error::UnknownKey(key.to_owned()));
+                };
+                // reject things like "arg+1" or "arg01" that u8::from_str would accept
+                if idx.is_empty()
+                    || !idx.bytes().all(|b| b.is_ascii_digit())
+                    || (idx.len() > 1 && idx.starts_with('0'))
+                {
+                    return Err(Error::UnknownKey(key.to_owned()));
+                }
+                let idx = match u8::from_str(idx) {
+                    Ok(idx) if idx <= MAX_ARG_INDEX => idx,
+                    _ => return Err(Error::UnknownKey(key.to_owned())),
+                };
+                if map.insert(idx, value).is_some() {
+                    return Err(Error::DuplicatedKey(key.to_owned()));
+                }
+            }
+        }
+        if self.path.is_some() && self.path_namespace.is_some() {
+            return Err(Error::PathAndPathNamespace);
+        }
+        Ok(())
+    }
+}
+
+fn path_in_namespace(path: &str, namespace: &str) -> bool {
+    if namespace == "/" {
+        return true;
+    }
+    match path.strip_prefix(namespace) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+fn arg_path_matches(rule: &str, arg: &str) -> bool {
+    rule == arg
+        || (rule.ends_with('/') && arg.starts_with(rule))
+        || (arg.ends_with('/') && rule.starts_with(arg))
+}
+
+fn name_in_namespace(name: &str, namespace: &str) -> bool {
+    match name.strip_prefix(namespace) {
+        Some(rest) => rest.is_empty() || rest.starts_with('.'),
+        None => false,
+    }
+}
+
+/// Write a value in quotes. Quotes in the value are written as `'\''`, which ends the quoted part, adds an escaped
+/// quote and starts a new quoted part.
+fn write_quoted(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_str("'")?;
+    f.write_str(&value.replace('\'', "'\\''"))?;
+    f.write_str("'")
+}
+
+impl fmt::Display for MatchRule {
+    /// Serialize the rule into the format the bus expects
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        let mut key = |f: &mut fmt::Formatter<'_>, key: &dyn fmt::Display| {
+            if !first {
+                f.write_str(",")?;
+            }
+            first = false;
+            write!(f, "{}=", key)
+        };
+
+        // there is no rule for the invalid type, it does not restrict the type like in `matches`
+        let typ = match self.typ {
+            Some(MessageType::Signal) => Some("signal"),
+            Some(MessageType::Call) => Some("method_call"),
+            Some(MessageType::Reply) => Some("method_return"),
+            Some(MessageType::Error) => Some("error"),
+            Some(MessageType::Invalid) | None => None,
+        };
+        if let Some(typ) = typ {
+            key(f, &"type")?;
+            write_quoted(f, typ)?;
+        }
+        let fields = [
+            ("sender", &self.sender),
+            ("interface", &self.interface),
+            ("member", &self.member),
+            ("path", &self.path),
+            ("path_namespace", &self.path_namespace),
+            ("destination", &self.destination),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                key(f, &name)?;
+                write_quoted(f, value)?;
+            }
+        }
+        for (idx, value) in &self.args {
+            key(f, &format_args!("arg{}", idx))?;
+            write_quoted(f, value)?;
+        }
+        for (idx, value) in &self.arg_paths {
+            key(f, &format_args!("arg{}path", idx))?;
+            write_quoted(f, value)?;
+        }
+        if let Some(namespace) = &self.arg0_namespace {
+            key(f, &"arg0namespace")?;
+            write_quoted(f, namespace)?;
+        }
+        if let Some(eavesdrop) = self.eavesdrop {
+            key(f, &"eavesdrop")?;
+            write_quoted(f, if eavesdrop { "true" } else { "false" })?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MatchRule {
+    type Err = Error;
+
+    /// Parse a rule in the format the bus uses. Values may be quoted with `'`, outside of quotes `\'` is a literal quote.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rule = MatchRule::new();
+        let mut chars = s.chars().peekable();
+
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+
+            let mut key = String::new();
+            loop {
+                match chars.next() {
+                    Some('=') => break,
+                    Some(',') | None => return Err(Error::MissingValue(key.trim().to_owned())),
+                    Some(c) => key.push(c),
+                }
+            }
+
+            let mut value = String::new();
+            let mut in_quotes = false;
+            loop {
+                match chars.next() {
+                    Some('\'') => in_quotes = !in_quotes,
+                    Some('\\') if !in_quotes && chars.peek() == Some(&'\'') => {
+                        chars.next();
+                        value.push('\'');
+                    }
+                    Some(',') if !in_quotes => break,
+                    Some(c) => value.push(c),
+                    None if in_quotes => return Err(Error::UnterminatedQuote),
+                    None => break,
+                }
+            }
+
+            rule.set_key(key.trim(), value)?;
+        }
+        Ok(rule)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message_builder::MessageBuilder;
+
+    #[test]
+    fn test_match_rule_string() {
+        let rule = MatchRule::new()
+            .message_type(MessageType::Signal)
+            .sender("org.freedesktop.DBus")
+            .path_namespace("/org/freedesktop")
+            .arg(2, "it's")
+            .arg_path(1, "/aa/")
+            .arg0_namespace("org.freedesktop")
+            .eavesdrop(false);
+        let s = rule.to_string();
+        assert_eq!(
+            s,
+            "type='signal',sender='org.freedesktop.DBus',path_namespace='/org/freedesktop',arg2='it'\\''s',arg1path='/aa/',arg0namespace='org.freedesktop',eavesdrop='false'"
+        );
+        assert_eq!(rule, s.parse().unwrap());
+
+        // unquoted values, whitespace and escaped quotes outside of quotes
+        let rule: MatchRule = " type=signal, member=Foo,arg0=it\\'s,arg1='a,b'"
+            .parse()
+            .unwrap();
+        assert_eq!(rule.typ, Some(MessageType::Signal));
+        assert_eq!(rule.member.as_deref(), Some("Foo"));
+        assert_eq!(rule.args.get(&0).map(String::as_str), Some("it's"));
+        assert_eq!(rule.args.get(&1).map(String::as_str), Some("a,b"));
+
+        assert_eq!(Ok(MatchRule::new()), "".parse());
+
+        // the invalid type can not be written, so it does not restrict the type
+        let rule = MatchRule::new()
+            .message_type(MessageType::Invalid)
+            .member("Foo");
+        assert_eq!(rule.typ, None);
+        assert_eq!(rule.to_string(), "member='Foo'");
+        let rule = MatchRule {
+            typ: Some(MessageType::Invalid),
+            ..MatchRule::new().member("Foo")
+        };
+        assert_eq!(rule.to_string(), "member='Foo'");
+        assert_eq!(
+            Err(Error::UnterminatedQuote),
+            "member='Foo".parse::<MatchRule>()
+        );
+        assert_eq!(
+            Err(Error::MissingValue("member".into())),
+            "member".parse::<MatchRule>()
+        );
+        assert_eq!(
+            Err(Error::UnknownKey("arg64".into())),
+            "arg64='a'".parse::<MatchRule>()
+        );
+        assert_eq!(
+            Err(Error::UnknownKey("color".into())),
+            "color='red'".parse::<MatchRule>()
+        );
+        assert_eq!(
+            Err(Error::DuplicatedKey("member".into())),
+            "member='A',member='B'".parse::<MatchRule>()
+        );
+        assert_eq!(
+            Err(Error::InvalidValue("type".into())),
+            "type='nothing'".parse::<MatchRule>()
+        );
+        assert_eq!(
+            Err(Error::PathAndPathNamespace),
+            "path='/a',path_namespace='/a'".parse::<MatchRule>()
+        );
+        assert_eq!(
+            Err(Error::Validation(validation::Error::InvalidObjectPath)),
+            "path='a/b'".parse::<MatchRule>()
+        );
+    }
+
+    #[test]
+    fn test_match_rule_matches() {
+        let mut msg = MessageBuilder::new()
+            .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+            .build();
+        msg.body.push_param(10u32).unwrap();
+        msg.body.push_param("io.killing.spark.Name").unwrap();
+        msg.body
+            .push_param(ObjectPath::new("/io/killing").unwrap())
+            .unwrap();
+
+        assert!(MatchRule::new().matches(&msg));
+        assert!(MatchRule::new()
+            .message_type(MessageType::Signal)
+            .interface("io.killing.spark")
+            .member("TestSignal")
+            .path("/io/killing/spark")
+            .matches(&msg));
+        assert!(!MatchRule::new()
+            .message_type(MessageType::Call)
+            .matches(&msg));
+        assert!(MatchRule {
+            typ: Some(MessageType::Invalid),
+            ..MatchRule::new()
+        }
+        .matches(&msg));
+        assert!(!MatchRule::new().member("Other").matches(&msg));
+        assert!(!MatchRule::new().sender(":1.1").matches(&msg));
+
+        assert!(MatchRule::new().path_namespace("/").matches(&msg));
+        assert!(MatchRule::new().path_namespace("/io/killing").matches(&msg));
+        assert!(!MatchRule::new().path_namespace("/io/kill").matches(&msg));
+
+        assert!(MatchRule::new()
+            .arg(1, "io.killing.spark.Name")
+            .matches(&msg));
+        assert!(!MatchRule::new().arg(1, "io.killing").matches(&msg));
+        // not a string
+        assert!(!MatchRule::new().arg(0, "10").matches(&msg));
+        assert!(!MatchRule::new().arg(2, "/io/killing").matches(&msg));
+        // out of range
+        assert!(!MatchRule::new().arg(3, "").matches(&msg));
+
+        assert!(MatchRule::new().arg_path(2, "/io/killing").matches(&msg));
+        assert!(MatchRule::new().arg_path(2, "/io/").matches(&msg));
+        assert!(!MatchRule::new().arg_path(2, "/io").matches(&msg));
+
+        let mut msg = MessageBuilder::new()
+            .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+            .build();
+        msg.body.push_param("io.killing.spark.Name").unwrap();
+        assert!(MatchRule::new()
+            .arg0_namespace("io.killing.spark")
+            .matches(&msg));
+        assert!(MatchRule::new()
+            .arg0_namespace("io.killing.spark.Name")
+            .matches(&msg));
+        assert!(!MatchRule::new().arg0_namespace("io.kill").matches(&msg));
+        assert!(!MatchRule::new().arg_path(0, "io.").matches(&msg));
+        assert!(MatchRule::new()
+            .arg_path(0, "io.killing.spark.Name")
+            .matches(&msg));
+    }
+}
@@ -21,7 +21,7 @@ pub enum MessageType {
 }
 
 /// Flags that can be set in the message header
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum HeaderFlags {
     NoReplyExpected,
     NoAutoStart,
@@ -38,7 +38,7 @@ impl HeaderFlags {
     }
 
     pub fn is_set(self, flags: u8) -> bool {
-        flags & self.into_raw() == 1
+        flags & self.into_raw() != 0
     }
 
     pub fn set(self, flags: &mut u8) {
@@ -80,7 +80,7 @@ impl DynamicHeader {
         error_msg: Option<String>,
     ) -> crate::message_builder::MarshalledMessage {
         let mut err_resp = crate::message_builder::MarshalledMessage {
-            typ: MessageType::Reply,
+            typ: MessageType::Error,
             dynheader: DynamicHeader {
                 interface: None,
                 member: None,
@@ -173,7 +173,36 @@ impl MessageBuilder {
     }
 }
 
+macro_rules! flag_methods {
+    () => {
+        /// Tell the receiver that no reply is wanted. The receiver should not send one, and the bus will not forward one.
+        pub fn no_reply_expected(mut self) -> Self {
+            self.msg.set_flag(HeaderFlags::NoReplyExpected);
+            self
+        }
+
+        /// Tell the bus to not start a service to receive this message, if the destination does not exist yet
+        pub fn no_auto_start(mut self) -> Self {
+            self.msg.set_flag(HeaderFlags::NoAutoStart);
+            self
+        }
+
+        /// Tell the receiver that the caller is prepared to wait for interactive authorization (e.g. a password prompt)
+        pub fn allow_interactive_authorization(mut self) -> Self {
+            self.msg
+                .set_flag(HeaderFlags::AllowInteractiveAuthorization);
+            self
+        }
+    };
+}
+
+impl MessageBuilder {
+    flag_methods!();
+}
+
 impl CallBuilder {
+    flag_methods!();
+
     pub fn on<S: Into<String>>(mut self, object_path: S) -> Self {
         self.msg.dynheader.object = Some(object_path.into());
         self
@@ -211,7 +240,7 @@ impl SignalBuilder {
 /// The body accepts everything that implements the Marshal trait (e.g. all basic types, strings, slices, Hashmaps,.....)
 /// And you can of course write an Marshal impl for your own datastructures. See the doc on the Marshal trait what you have
 /// to look out for when doing this though.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MarshalledMessage {
     pub body: MarshalledMessageBody,
 
@@ -235,6 +264,24 @@ impl MarshalledMessage {
         &self.body.sig
     }
 
+    pub fn has_flag(&self, flag: HeaderFlags) -> bool {
+        flag.is_set(self.flags)
+    }
+    pub fn set_flag(&mut self, flag: HeaderFlags) {
+        flag.set(&mut self.flags)
+    }
+    pub fn unset_flag(&mut self, flag: HeaderFlags) {
+        flag.unset(&mut self.flags)
+    }
+    pub fn toggle_flag(&mut self, flag: HeaderFlags) {
+        flag.toggle(&mut self.flags)
+    }
+
+    /// Whether this is a call the sender wants a reply to
+    pub fn expects_reply(&self) -> bool {
+        self.typ == MessageType::Call && !self.has_flag(HeaderFlags::NoReplyExpected)
+    }
+
     /// New message with the default native byteorder
     pub fn new() -> Self {
         MarshalledMessage {
@@ -264,20 +311,7 @@ impl MarshalledMessage {
     }
 
     pub fn unmarshall_all<'a, 'e>(self) -> Result<message::Message<'a, 'e>, UnmarshalError> {
-        let params = if self.body.sig.is_empty() {
-            vec![]
-        } else {
-            let sigs: Vec<_> = crate::signature::Type::parse_description(&self.body.sig)?;
-
-            let (_, params) = crate::wire::unmarshal::unmarshal_body(
-                self.body.byteorder,
-                &sigs,
-                &self.body.buf,
-                &self.body.raw_fds,
-                0,
-            )?;
-            params
-        };
+        let params = self.body.unmarshal_params()?;
         Ok(message::Message {
             dynheader: self.dynheader,
             params,
@@ -289,7 +323,7 @@ impl MarshalledMessage {
 }
 /// The body accepts everything that implements the Marshal trait (e.g. all basic types, strings, slices, Hashmaps,.....)
 /// And you can of course write an Marshal impl for your own datastrcutures
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MarshalledMessageBody {
     pub(crate) buf: Vec<u8>,
 
@@ -408,7 +442,23 @@ impl MarshalledMessageBody {
         }
         Ok(())
     }
-    fn create_ctx(&mut self) -> MarshalContext {
+
+    /// Parse the tokens as values for the signature in the syntax busctl uses and append them.
+    /// See `params::text` for the syntax. If anything fails the body is left unchanged.
+    pub fn push_text_args<I>(
+        &mut self,
+        sig: &str,
+        tokens: I,
+    ) -> Result<(), crate::params::text::TextError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let params = crate::params::text::parse_args(sig, tokens)?;
+        self.push_mult_helper(|body| body.push_old_params(&params))?;
+        Ok(())
+    }
+    fn create_ctx(&mut self) -> MarshalContext<'_, '_> {
         MarshalContext {
             buf: &mut self.buf,
             fds: &mut self.raw_fds,
@@ -520,6 +570,23 @@ impl MarshalledMessageBody {
         let mut ctx = self.create_ctx();
         p.marshal_as_variant(&mut ctx)
     }
+
+    /// Append a serde value with the signature, see `wire::serde`. Unlike `SerdeParam` this works for any type that
+    /// implements `Serialize`, `wire::serde::signature_of_value` can provide the signature. If this fails the body is
+    /// left unchanged.
+    #[cfg(feature = "serde")]
+    pub fn push_serde<T: serde::Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+        sig: &crate::signature::Type,
+    ) -> Result<(), MarshalError> {
+        self.push_mult_helper(|body| {
+            let mut ctx = body.create_ctx();
+            crate::wire::serde::to_context(value, sig, &mut ctx)?;
+            sig.to_str(body.sig.to_string_mut());
+            Ok(())
+        })
+    }
     /// Validate the all the marshalled elements of the body.
     pub fn validate(&self) -> Result<(), UnmarshalError> {
         if self.sig.is_empty() && self.buf.is_empty() {
@@ -537,9 +604,27 @@ impl MarshalledMessageBody {
             Err(UnmarshalError::NotAllBytesUsed)
         }
     }
+    /// Unmarshal all params of the body into the old style `Param`s. The fds in the params share their state with the
+    /// ones in the body.
+    pub fn unmarshal_params<'a, 'e>(
+        &self,
+    ) -> Result<Vec<crate::params::Param<'a, 'e>>, UnmarshalError> {
+        if self.sig.is_empty() {
+            return Ok(Vec::new());
+        }
+        let sigs = crate::signature::Type::parse_description(&self.sig)?;
+        let (_, params) = crate::wire::unmarshal::unmarshal_body(
+            self.byteorder,
+            &sigs,
+            &self.buf,
+            &self.raw_fds,
+            0,
+        )?;
+        Ok(params)
+    }
     /// Create a parser to retrieve parameters from the body.
     #[inline]
-    pub fn parser(&self) -> MessageBodyParser {
+    pub fn parser(&self) -> MessageBodyParser<'_> {
         MessageBodyParser::new(self)
     }
 }
@@ -894,9 +979,49 @@ impl<'fds, 'body: 'fds> MessageBodyParser<'body> {
         self.get_mult_helper(5, get_calls)
     }
 
+    /// Skip the next param without unmarshalling it
+    pub fn skip(&mut self) -> Result<(), UnmarshalError> {
+        if let Some(sig_str) = self.get_next_sig() {
+            let sig = &crate::signature::Type::parse_description(sig_str)?[0];
+            let bytes = crate::wire::validate_raw::validate_marshalled(
+                self.body.byteorder,
+                self.buf_idx,
+                &self.body.buf,
+                sig,
+            )
+            .map_err(|(_, e)| e)?;
+            self.buf_idx += bytes;
+            self.sig_idx += sig_str.len();
+            Ok(())
+        } else {
+            Err(UnmarshalError::EndOfMessage)
+        }
+    }
+
+    /// Get the next param as a serde type, using the signature of the message. See `wire::serde`. Unlike `SerdeParam`
+    /// this also works for types that borrow from the message.
+    #[cfg(feature = "serde")]
+    pub fn get_serde<T: serde::Deserialize<'body>>(&mut self) -> Result<T, UnmarshalError> {
+        if let Some(sig_str) = self.get_next_sig() {
+            let sig = &crate::signature::Type::parse_description(sig_str)?[0];
+            let mut ctx = UnmarshalContext {
+                byteorder: self.body.byteorder,
+                buf: &self.body.buf,
+                offset: self.buf_idx,
+                fds: &self.body.raw_fds,
+            };
+            let value = crate::wire::serde::from_context(sig, &mut ctx)?;
+            self.buf_idx = ctx.offset;
+            self.sig_idx += sig_str.len();
+            Ok(value)
+        } else {
+            Err(UnmarshalError::EndOfMessage)
+        }
+    }
+
     /// Get the next (old_style) param.
     /// This checks if there are params left in the message and if the type you requested fits the signature of the message.
-    pub fn get_param(&mut self) -> Result<crate::params::Param, UnmarshalError> {
+    pub fn get_param(&mut self) -> Result<crate::params::Param<'_, '_>, UnmarshalError> {
         if let Some(sig_str) = self.get_next_sig() {
             let mut ctx = UnmarshalContext {
                 byteorder: self.body.byteorder,
@@ -921,8 +1046,112 @@ impl<'fds, 'body: 'fds> MessageBodyParser<'body> {
     }
 }
 
+/// A list of parameters that can be pushed into a message body one by one, e.g. as the arguments of a call.
+///
+/// This is implemented for tuples of up to five elements that implement `Marshal`. Note that a tuple passed to
+/// `push_param` is marshalled as one struct, while `push_args` pushes every element as a separate parameter.
+pub trait MarshalArgs {
+    fn marshal_args(self, body: &mut MarshalledMessageBody) -> Result<(), MarshalError>;
+}
+
+/// A list of parameters that can be read from a message body one by one, e.g. the return values of a call.
+///
+/// This is implemented for tuples of up to five elements that implement `Unmarshal`.
+pub trait UnmarshalArgs<'body>: Sized {
+    fn unmarshal_args(parser: &mut MessageBodyParser<'body>) -> Result<Self, UnmarshalError>;
+}
+
+macro_rules! args_impls {
+    ($($name:ident)*) => {
+        impl<$($name: Marshal),*> MarshalArgs for ($($name,)*) {
+            #[allow(non_snake_case, unused_variables)]
+            fn marshal_args(self, body: &mut MarshalledMessageBody) -> Result<(), MarshalError> {
+                let ($($name,)*) = self;
+                $(body.push_param($name)?;)*
+                Ok(())
+            }
+        }
+        impl<'body, $($name: Unmarshal<'body, 'body>),*> UnmarshalArgs<'body> for ($($name,)*) {
+            #[allow(unused_variables)]
+            fn unmarshal_args(parser: &mut MessageBodyParser<'body>) -> Result<Self, UnmarshalError> {
+                Ok(($(parser.get::<$name>()?,)*))
+            }
+        }
+    };
+}
+
+args_impls!();
+args_impls!(T1);
+args_impls!(T1 T2);
+args_impls!(T1 T2 T3);
+args_impls!(T1 T2 T3 T4);
+args_impls!(T1 T2 T3 T4 T5);
+
+impl MarshalledMessageBody {
+    /// Push all elements of a tuple as separate parameters. See `MarshalArgs`.
+    pub fn push_args<A: MarshalArgs>(&mut self, args: A) -> Result<(), MarshalError> {
+        args.marshal_args(self)
+    }
+}
+
+impl<'body> MessageBodyParser<'body> {
+    /// Get all remaining params as a tuple. See `UnmarshalArgs`. If there are more params left than the tuple has
+    /// elements this returns `UnmarshalError::WrongSignature`.
+    /// If this fails the parser stays at the same position.
+    pub fn get_args<A: UnmarshalArgs<'body>>(&mut self) -> Result<A, UnmarshalError> {
+        let start_sig_idx = self.sig_idx;
+        let start_buf_idx = self.buf_idx;
+        A::unmarshal_args(self)
+            .and_then(|args| {
+                if self.sig_idx == self.body.sig.len() {
+                    Ok(args)
+                } else {
+                    Err(UnmarshalError::WrongSignature)
+                }
+            })
+            .inspect_err(|_| {
+                self.sig_idx = start_sig_idx;
+                self.buf_idx = start_buf_idx;
+            })
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn header_flags() {
+        use super::{HeaderFlags, MessageBuilder};
+
+        let mut flags = 0;
+        for flag in [
+            HeaderFlags::NoReplyExpected,
+            HeaderFlags::NoAutoStart,
+            HeaderFlags::AllowInteractiveAuthorization,
+        ] {
+            assert!(!flag.is_set(flags));
+            flag.set(&mut flags);
+            assert!(flag.is_set(flags));
+        }
+        assert_eq!(flags, 7);
+        HeaderFlags::NoAutoStart.toggle(&mut flags);
+        assert!(!HeaderFlags::NoAutoStart.is_set(flags));
+        assert!(HeaderFlags::AllowInteractiveAuthorization.is_set(flags));
+
+        let call = MessageBuilder::new().call("Member").on("/").build();
+        assert!(call.expects_reply());
+        let call = MessageBuilder::new()
+            .no_auto_start()
+            .call("Member")
+            .on("/")
+            .no_reply_expected()
+            .allow_interactive_authorization()
+            .build();
+        assert_eq!(call.flags, 7);
+        assert!(!call.expects_reply());
+        let signal = MessageBuilder::new().signal("a.b", "C", "/").build();
+        assert!(!signal.expects_reply());
+    }
+
     #[test]
     fn parser_get() {
         use crate::wire::errors::UnmarshalError;
@@ -960,4 +1189,31 @@ mod tests {
         assert!(parser.get::<(u32, i32, &str)>().is_ok());
         assert!(parser.get2::<(u32, i32, &str), (u32, i32, &str)>().is_ok());
     }
+
+    #[test]
+    fn parser_get_args() {
+        use crate::wire::errors::UnmarshalError;
+
+        let mut sig = super::MessageBuilder::new()
+            .signal("io.killingspark", "Signal", "/io/killingspark/Signaler")
+            .build();
+        sig.body.push_args((100u32, "ABCDEFGH")).unwrap();
+
+        // all params have to be read
+        let mut parser = sig.body.parser();
+        assert_eq!(
+            parser.get_args::<(u32,)>(),
+            Err(UnmarshalError::WrongSignature)
+        );
+        assert_eq!(parser.get_args::<()>(), Err(UnmarshalError::WrongSignature));
+        assert_eq!(
+            parser.get_args::<(u32, &str, u8)>(),
+            Err(UnmarshalError::EndOfMessage)
+        );
+        assert_eq!(parser.get_args(), Ok((100u32, "ABCDEFGH")));
+
+        let mut parser = sig.body.parser();
+        assert_eq!(parser.get(), Ok(100u32));
+        assert_eq!(parser.get_args(), Ok(("ABCDEFGH",)));
+    }
 }
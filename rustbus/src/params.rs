@@ -6,7 +6,9 @@
 
 mod container_constructors;
 mod conversion;
+pub mod json;
 pub mod message;
+pub mod text;
 mod types;
 pub mod validation;
 
@@ -0,0 +1,894 @@
+//! Convert params and whole messages to JSON and back
+//!
+//! The values are mapped like `busctl --json` does:
+//! * Numbers and booleans map to JSON numbers and booleans. Doubles that are not finite become `null`, which is read
+//!   back as NaN.
+//! * Strings, object paths and signatures map to JSON strings
+//! * Unix fds map to their index into the fds of the message
+//! * Arrays and structs map to JSON arrays
+//! * Dicts map to JSON objects. JSON only has string keys, so keys that are not strings are written as their JSON text.
+//! * Variants map to an object with the signature of the content in `type` and the content in `data`
+//!
+//! JSON does not carry the dbus types, so converting back needs the signature the value should have.
+//!
+//! ```rust
+//! use rustbus::params::json::{self, Json};
+//! use rustbus::params::Param;
+//! use rustbus::signature::Type;
+//!
+//! let sig = Type::parse_description("a{sv}").unwrap().remove(0);
+//! let value = Json::parse(r#"{"answer": {"type": "u", "data": 42}}"#).unwrap();
+//! let param = json::param_from_json(&value, &sig, &[]).unwrap();
+//! assert_eq!(json::param_to_json(&param, &[]), value);
+//! assert_eq!(value.to_string(), r#"{"answer":{"type":"u","data":42}}"#);
+//! ```
+
+use crate::message_builder::{MarshalledMessage, MessageType};
+use crate::params::{self, Array, Base, Container, Dict, DictMap, Param, Variant};
+use crate::signature;
+use crate::wire::errors::{MarshalError, UnmarshalError};
+use crate::wire::UnixFd;
+use crate::ByteOrder;
+
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum JsonError {
+    /// The text is not valid JSON
+    #[error("Invalid JSON at byte {offset}: {reason}")]
+    Parse { offset: usize, reason: &'static str },
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(#[from] signature::Error),
+    /// The JSON value is not of the kind the signature requires
+    #[error("Expected {expected} for signature {sig}, found {found}")]
+    WrongType {
+        sig: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// A number does not fit into the type of the signature
+    #[error("{value} is out of range for signature {sig}")]
+    OutOfRange { sig: String, value: String },
+    /// A JSON array has not as many values as the struct has fields
+    #[error("Expected {expected} values for signature {sig}, found {found}")]
+    WrongLength {
+        sig: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Error encountered while validating input: {0}")]
+    Validation(#[from] params::validation::Error),
+    /// A unix fd index is not smaller than the number of fds given
+    #[error("The unix fd index {0} is bigger than the list of unix fds")]
+    BadFdIndex(u64),
+    /// A variant or message object lacks a member
+    #[error("Missing member {0:?}")]
+    MissingMember(&'static str),
+    /// A member of a message object has a value that does not describe a message
+    #[error("Invalid value {value} for member {member:?}")]
+    InvalidMember { member: &'static str, value: String },
+    #[error("An error occured while marshalling: {0}")]
+    Marshal(#[from] MarshalError),
+}
+
+/// A JSON value. The order of the members of objects is kept.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// A JSON number. Integers are kept exactly, JSON itself does not limit them to the precision of a double.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Number::Unsigned(val) => write!(f, "{}", val),
+            Number::Signed(val) => write!(f, "{}", val),
+            Number::Float(val) if val.is_finite() => write!(f, "{}", val),
+            // JSON has no representation for these
+            Number::Float(_) => f.write_str("null"),
+        }
+    }
+}
+
+impl Json {
+    /// Parse a JSON text
+    pub fn parse(text: &str) -> Result<Json, JsonError> {
+        let mut parser = Parser {
+            text: text.as_bytes(),
+            offset: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.offset != text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    /// The value of the member with this key, if this is an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(member, _)| member == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::Bool(_) => "a boolean",
+            Json::Number(_) => "a number",
+            Json::String(_) => "a string",
+            Json::Array(_) => "an array",
+            Json::Object(_) => "an object",
+        }
+    }
+}
+
+/// Writes the value without any whitespace
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(val) => write!(f, "{}", val),
+            Json::Number(val) => write!(f, "{}", val),
+            Json::String(val) => write_string(val, f),
+            Json::Array(values) => {
+                f.write_char('[')?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(members) => {
+                f.write_char('{')?;
+                for (idx, (key, value)) in members.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(key, f)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+pub(crate) fn write_string<W: Write>(val: &str, out: &mut W) -> fmt::Result {
+    out.write_char('"')?;
+    for c in val.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+// Deeper nesting than dbus allows, but still far from overflowing the stack
+const MAX_PARSE_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    text: &'a [u8],
+    offset: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &'static str) -> JsonError {
+        JsonError::Parse {
+            offset: self.offset,
+            reason,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.text.get(self.offset) {
+            self.offset += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.offset).copied()
+    }
+
+    fn expect(&mut self, byte: u8, reason: &'static str) -> Result<(), JsonError> {
+        self.skip_whitespace();
+        if self.peek() != Some(byte) {
+            return Err(self.error(reason));
+        }
+        self.offset += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &str, value: Json) -> Result<Json, JsonError> {
+        if !self.text[self.offset..].starts_with(literal.as_bytes()) {
+            return Err(self.error("invalid literal"));
+        }
+        self.offset += literal.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'-' | b'0'..=b'9') => self.number().map(Json::Number),
+            Some(b'[') => self.nested(Self::array),
+            Some(b'{') => self.nested(Self::object),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of the text")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Json, JsonError>,
+    ) -> Result<Json, JsonError> {
+        if self.depth >= MAX_PARSE_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.depth += 1;
+        let value = parse(self)?;
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    fn array(&mut self) -> Result<Json, JsonError> {
+        self.offset += 1;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.offset += 1;
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b']') => {
+                    self.offset += 1;
+                    return Ok(Json::Array(values));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, JsonError> {
+        self.offset += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.offset += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a string as key"));
+            }
+            let key = self.string()?;
+            self.expect(b':', "expected ':'")?;
+            members.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b'}') => {
+                    self.offset += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self
+            .text
+            .get(self.offset..self.offset + 4)
+            // from_str_radix would also accept a sign
+            .filter(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.offset += 4;
+        Ok(digits)
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.offset += 1;
+        let mut string = String::new();
+        loop {
+            // copy everything up to the next quote or escape at once
+            let start = self.offset;
+            while let Some(byte) = self.peek() {
+                if byte == b'"' || byte == b'\\' || byte < 0x20 {
+                    break;
+                }
+                self.offset += 1;
+            }
+            // the text is a str and the stops are ascii, so this is on a char boundary
+            string.push_str(std::str::from_utf8(&self.text[start..self.offset]).unwrap());
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.offset += 1;
+                    return Ok(string);
+                }
+                Some(b'\\') => self.offset += 1,
+                Some(_) => return Err(self.error("control character in string")),
+                None => return Err(self.error("unterminated string")),
+            }
+            let escape = self
+                .peek()
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.offset += 1;
+            match escape {
+                b'"' => string.push('"'),
+                b'\\' => string.push('\\'),
+                b'/' => string.push('/'),
+                b'b' => string.push('\u{8}'),
+                b'f' => string.push('\u{c}'),
+                b'n' => string.push('\n'),
+                b'r' => string.push('\r'),
+                b't' => string.push('\t'),
+                b'u' => {
+                    let mut code = self.hex4()?;
+                    // characters outside the BMP are escaped as a surrogate pair
+                    if (0xD800..0xDC00).contains(&code)
+                        && self.text[self.offset..].starts_with(b"\\u")
+                    {
+                        self.offset += 2;
+                        let low = self.hex4()?;
+                        if !(0xDC00..0xE000).contains(&low) {
+                            return Err(self.error("invalid surrogate pair"));
+                        }
+                        code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                    }
+                    let c =
+                        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))?;
+                    string.push(c);
+                }
+                _ => return Err(self.error("invalid escape")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Number, JsonError> {
+        let start = self.offset;
+        let digits = |parser: &mut Self| {
+            let start = parser.offset;
+            while let Some(b'0'..=b'9') = parser.peek() {
+                parser.offset += 1;
+            }
+            parser.offset - start
+        };
+
+        if self.peek() == Some(b'-') {
+            self.offset += 1;
+        }
+        let int_start = self.offset;
+        let int_digits = digits(self);
+        if int_digits == 0 || (int_digits > 1 && self.text[int_start] == b'0') {
+            return Err(self.error("invalid number"));
+        }
+        let mut integer = true;
+        if self.peek() == Some(b'.') {
+            self.offset += 1;
+            integer = false;
+            if digits(self) == 0 {
+                return Err(self.error("invalid number"));
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.offset += 1;
+            integer = false;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.offset += 1;
+            }
+            if digits(self) == 0 {
+                return Err(self.error("invalid number"));
+            }
+        }
+
+        let text = std::str::from_utf8(&self.text[start..self.offset]).unwrap();
+        if integer {
+            if let Ok(val) = text.parse() {
+                return Ok(Number::Unsigned(val));
+            }
+            if let Ok(val) = text.parse() {
+                return Ok(Number::Signed(val));
+            }
+        }
+        // integers that are too big for 64 bits loose precision like in most other JSON implementations
+        text.parse()
+            .map(Number::Float)
+            .map_err(|_| self.error("invalid number"))
+    }
+}
+
+//--------
+// Params to JSON
+//--------
+
+/// The index of the fd in the fds of the message. The unmarshalled fds share their state with the ones in the message.
+fn fd_index(fd: &UnixFd, fds: &[UnixFd]) -> Json {
+    fds.iter()
+        .position(|other| other == fd)
+        .map_or(Json::Null, |idx| Json::Number(Number::Unsigned(idx as u64)))
+}
+
+/// Convert the param. Unix fds are converted to their index in `fds`, or `null` if they are not in there.
+pub fn param_to_json(param: &Param, fds: &[UnixFd]) -> Json {
+    match param {
+        Param::Base(base) => base_to_json(base, fds),
+        Param::Container(Container::Array(array)) => {
+            Json::Array(array.values.iter().map(|v| param_to_json(v, fds)).collect())
+        }
+        Param::Container(Container::ArrayRef(array)) => {
+            Json::Array(array.values.iter().map(|v| param_to_json(v, fds)).collect())
+        }
+        Param::Container(Container::Struct(fields)) => {
+            Json::Array(fields.iter().map(|v| param_to_json(v, fds)).collect())
+        }
+        Param::Container(Container::StructRef(fields)) => {
+            Json::Array(fields.iter().map(|v| param_to_json(v, fds)).collect())
+        }
+        Param::Container(Container::Dict(dict)) => dict_to_json(&dict.map, fds),
+        Param::Container(Container::DictRef(dict)) => dict_to_json(dict.map, fds),
+        Param::Container(Container::Variant(variant)) => {
+            let mut sig = String::new();
+            variant.sig.to_str(&mut sig);
+            Json::Object(vec![
+                ("type".to_owned(), Json::String(sig)),
+                ("data".to_owned(), param_to_json(&variant.value, fds)),
+            ])
+        }
+    }
+}
+
+/// Convert the base param. Unix fds are converted to their index in `fds`, or `null` if they are not in there.
+pub fn base_to_json(base: &Base, fds: &[UnixFd]) -> Json {
+    let unsigned = |val: u64| Json::Number(Number::Unsigned(val));
+    let signed = |val: i64| Json::Number(Number::Signed(val));
+    match base {
+        &Base::Double(bits) | &Base::DoubleRef(&bits) => {
+            let val = f64::from_bits(bits);
+            if val.is_finite() {
+                Json::Number(Number::Float(val))
+            } else {
+                Json::Null
+            }
+        }
+        &Base::Byte(val) | &Base::ByteRef(&val) => unsigned(val.into()),
+        &Base::Int16(val) | &Base::Int16Ref(&val) => signed(val.into()),
+        &Base::Uint16(val) | &Base::Uint16Ref(&val) => unsigned(val.into()),
+        &Base::Int32(val) | &Base::Int32Ref(&val) => signed(val.into()),
+        &Base::Uint32(val) | &Base::Uint32Ref(&val) => unsigned(val.into()),
+        &Base::Int64(val) | &Base::Int64Ref(&val) => signed(val),
+        &Base::Uint64(val) | &Base::Uint64Ref(&val) => unsigned(val),
+        &Base::Boolean(val) | &Base::BooleanRef(&val) => Json::Bool(val),
+        Base::String(val) | Base::Signature(val) | Base::ObjectPath(val) => {
+            Json::String(val.clone())
+        }
+        Base::StringRef(val) | Base::SignatureRef(val) | Base::ObjectPathRef(val) => {
+            Json::String((*val).to_owned())
+        }
+        Base::UnixFd(fd) => fd_index(fd, fds),
+        Base::UnixFdRef(fd) => fd_index(fd, fds),
+    }
+}
+
+/// The entries are sorted by key, so the output does not depend on the order of the hashmap
+fn dict_to_json(map: &DictMap, fds: &[UnixFd]) -> Json {
+    let mut members: Vec<_> = map
+        .iter()
+        .map(|(key, value)| {
+            let key = match base_to_json(key, fds) {
+                Json::String(key) => key,
+                other => other.to_string(),
+            };
+            (key, param_to_json(value, fds))
+        })
+        .collect();
+    members.sort_by(|a, b| a.0.cmp(&b.0));
+    Json::Object(members)
+}
+
+//--------
+// JSON to params
+//--------
+
+fn sig_string(typ: &signature::Type) -> String {
+    let mut sig = String::new();
+    typ.to_str(&mut sig);
+    sig
+}
+
+fn wrong_type(typ: &signature::Type, expected: &'static str, found: &Json) -> JsonError {
+    JsonError::WrongType {
+        sig: sig_string(typ),
+        expected,
+        found: found.kind(),
+    }
+}
+
+/// Convert the JSON value to a param of the type. Unix fds are taken from `fds` by their index.
+pub fn param_from_json(
+    json: &Json,
+    typ: &signature::Type,
+    fds: &[UnixFd],
+) -> Result<Param<'static, 'static>, JsonError> {
+    match typ {
+        signature::Type::Base(base) => base_from_json(json, *base, fds).map(Param::Base),
+        signature::Type::Container(container) => {
+            container_from_json(json, typ, container, fds).map(Param::Container)
+        }
+    }
+}
+
+/// Convert the JSON value to a base param of the type. Unix fds are taken from `fds` by their index.
+pub fn base_from_json(
+    json: &Json,
+    base: signature::Base,
+    fds: &[UnixFd],
+) -> Result<Base<'static>, JsonError> {
+    let typ = signature::Type::Base(base);
+    match base {
+        signature::Base::Byte => integer(json, &typ).map(Base::Byte),
+        signature::Base::Int16 => integer(json, &typ).map(Base::Int16),
+        signature::Base::Uint16 => integer(json, &typ).map(Base::Uint16),
+        signature::Base::Int32 => integer(json, &typ).map(Base::Int32),
+        signature::Base::Uint32 => integer(json, &typ).map(Base::Uint32),
+        signature::Base::Int64 => integer(json, &typ).map(Base::Int64),
+        signature::Base::Uint64 => integer(json, &typ).map(Base::Uint64),
+        signature::Base::Double => {
+            let val = match json {
+                Json::Number(Number::Unsigned(val)) => *val as f64,
+                Json::Number(Number::Signed(val)) => *val as f64,
+                Json::Number(Number::Float(val)) => *val,
+                Json::Null => f64::NAN,
+                other => return Err(wrong_type(&typ, "a number", other)),
+            };
+            Ok(Base::Double(val.to_bits()))
+        }
+        signature::Base::Boolean => match json {
+            Json::Bool(val) => Ok(Base::Boolean(*val)),
+            other => Err(wrong_type(&typ, "a boolean", other)),
+        },
+        signature::Base::String => string(json, &typ).map(Base::String),
+        signature::Base::ObjectPath => {
+            let path = string(json, &typ)?;
+            params::validate_object_path(&path)?;
+            Ok(Base::ObjectPath(path))
+        }
+        signature::Base::Signature => {
+            let sig = string(json, &typ)?;
+            params::validate_signature(&sig)?;
+            Ok(Base::Signature(sig))
+        }
+        // the unmarshalled fds share their state with the ones in the message, so the clone does too
+        signature::Base::UnixFd => {
+            let idx: u64 = integer(json, &typ)?;
+            fds.get(idx as usize)
+                .map(|fd| Base::UnixFd(fd.clone()))
+                .ok_or(JsonError::BadFdIndex(idx))
+        }
+    }
+}
+
+fn integer<T>(json: &Json, typ: &signature::Type) -> Result<T, JsonError>
+where
+    T: TryFrom<u64> + TryFrom<i64>,
+{
+    let out_of_range = |value: &dyn fmt::Display| JsonError::OutOfRange {
+        sig: sig_string(typ),
+        value: value.to_string(),
+    };
+    match json {
+        Json::Number(Number::Unsigned(val)) => T::try_from(*val).map_err(|_| out_of_range(val)),
+        Json::Number(Number::Signed(val)) => T::try_from(*val).map_err(|_| out_of_range(val)),
+        Json::Number(Number::Float(val)) => Err(out_of_range(val)),
+        other => Err(wrong_type(typ, "an integer", other)),
+    }
+}
+
+fn string(json: &Json, typ: &signature::Type) -> Result<String, JsonError> {
+    match json {
+        Json::String(val) => Ok(val.clone()),
+        other => Err(wrong_type(typ, "a string", other)),
+    }
+}
+
+/// Dict keys are always strings in JSON, the keys of other types are parsed as JSON first
+fn key_from_json(
+    key: &str,
+    key_sig: signature::Base,
+    fds: &[UnixFd],
+) -> Result<Base<'static>, JsonError> {
+    let key = match key_sig {
+        signature::Base::String | signature::Base::ObjectPath | signature::Base::Signature => {
+            Json::String(key.to_owned())
+        }
+        _ => Json::parse(key).unwrap_or_else(|_| Json::String(key.to_owned())),
+    };
+    base_from_json(&key, key_sig, fds)
+}
+
+fn container_from_json(
+    json: &Json,
+    typ: &signature::Type,
+    container: &signature::Container,
+    fds: &[UnixFd],
+) -> Result<Container<'static, 'static>, JsonError> {
+    match container {
+        signature::Container::Array(element_sig) => match json {
+            Json::Array(values) => Ok(Container::Array(Array {
+                element_sig: element_sig.as_ref().clone(),
+                values: values
+                    .iter()
+                    .map(|value| param_from_json(value, element_sig, fds))
+                    .collect::<Result<Vec<_>, _>>()?,
+            })),
+            other => Err(wrong_type(typ, "an array", other)),
+        },
+        signature::Container::Struct(types) => match json {
+            Json::Array(values) if values.len() == types.as_ref().len() => Ok(Container::Struct(
+                values
+                    .iter()
+                    .zip(types.as_ref())
+                    .map(|(value, field)| param_from_json(value, field, fds))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+            Json::Array(values) => Err(JsonError::WrongLength {
+                sig: sig_string(typ),
+                expected: types.as_ref().len(),
+                found: values.len(),
+            }),
+            other => Err(wrong_type(typ, "an array", other)),
+        },
+        signature::Container::Dict(key_sig, value_sig) => match json {
+            Json::Object(members) => {
+                let mut map = DictMap::new();
+                for (key, value) in members {
+                    let key = key_from_json(key, *key_sig, fds)?;
+                    map.insert(key, param_from_json(value, value_sig, fds)?);
+                }
+                Ok(Container::Dict(Dict {
+                    key_sig: *key_sig,
+                    value_sig: value_sig.as_ref().clone(),
+                    map,
+                }))
+            }
+            other => Err(wrong_type(typ, "an object", other)),
+        },
+        signature::Container::Variant => {
+            if !matches!(json, Json::Object(_)) {
+                return Err(wrong_type(typ, "an object", json));
+            }
+            let sig = match json.get("type").ok_or(JsonError::MissingMember("type"))? {
+                Json::String(sig) => sig,
+                other => return Err(wrong_type(typ, "a signature in \"type\"", other)),
+            };
+            let mut types = signature::Type::parse_description(sig)?;
+            if types.len() != 1 {
+                return Err(JsonError::Validation(
+                    params::validation::Error::InvalidSignature(signature::Error::InvalidSignature),
+                ));
+            }
+            let sig = types.remove(0);
+            let data = json.get("data").ok_or(JsonError::MissingMember("data"))?;
+            let value = param_from_json(data, &sig, fds)?;
+            Ok(Container::Variant(Box::new(Variant { sig, value })))
+        }
+    }
+}
+
+//--------
+// Messages
+//--------
+
+fn message_type_name(typ: MessageType) -> &'static str {
+    match typ {
+        MessageType::Call => "method_call",
+        MessageType::Reply => "method_return",
+        MessageType::Error => "error",
+        MessageType::Signal => "signal",
+        MessageType::Invalid => "invalid",
+    }
+}
+
+/// Convert the message with its header fields, using the same members as `busctl --json` does:
+/// `type`, `endian`, `flags`, `version`, `cookie` (the serial), `reply_cookie`, `sender`, `destination`, `path`,
+/// `interface`, `member`, `error_name` and `payload`. The payload has the signature in `type` and the params in
+/// `data`. Unix fds are converted to their index in the fds of the message.
+pub fn message_to_json(msg: &MarshalledMessage) -> Result<Json, UnmarshalError> {
+    message_object(msg, None)
+}
+
+/// Like `message_to_json`, with the timestamp in microseconds as `timestamp-realtime` if given
+pub(crate) fn message_object(
+    msg: &MarshalledMessage,
+    timestamp: Option<u64>,
+) -> Result<Json, UnmarshalError> {
+    let params = msg.body.unmarshal_params()?;
+    let fds = &msg.body.raw_fds;
+    let hdr = &msg.dynheader;
+    let number = |val: u64| Json::Number(Number::Unsigned(val));
+
+    let mut members: Vec<(&str, Json)> = vec![
+        ("type", Json::String(message_type_name(msg.typ).to_owned())),
+        (
+            "endian",
+            Json::String(
+                match msg.body.byteorder {
+                    ByteOrder::LittleEndian => "l",
+                    ByteOrder::BigEndian => "B",
+                }
+                .to_owned(),
+            ),
+        ),
+        ("flags", number(msg.flags.into())),
+        ("version", number(1)),
+        ("cookie", number(hdr.serial.unwrap_or(0).into())),
+    ];
+    if let Some(reply_serial) = hdr.response_serial {
+        members.push(("reply_cookie", number(reply_serial.into())));
+    }
+    if let Some(timestamp) = timestamp {
+        members.push(("timestamp-realtime", number(timestamp)));
+    }
+    for (key, value) in [
+        ("sender", &hdr.sender),
+        ("destination", &hdr.destination),
+        ("path", &hdr.object),
+        ("interface", &hdr.interface),
+        ("member", &hdr.member),
+        ("error_name", &hdr.error_name),
+    ] {
+        if let Some(value) = value {
+            members.push((key, Json::String(value.clone())));
+        }
+    }
+    members.push((
+        "payload",
+        Json::Object(vec![
+            ("type".to_owned(), Json::String(msg.get_sig().to_owned())),
+            (
+                "data".to_owned(),
+                Json::Array(
+                    params
+                        .iter()
+                        .map(|param| param_to_json(param, fds))
+                        .collect(),
+                ),
+            ),
+        ]),
+    ));
+
+    Ok(Json::Object(
+        members
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect(),
+    ))
+}
+
+/// Build a message from the JSON `message_to_json` produces. `version` and `timestamp-realtime` are ignored, a
+/// `cookie` of 0 means the message has no serial yet. Unix fds are taken from `fds` by their index. They are not
+/// duplicated, the fds in the message share their state with the ones in `fds`.
+pub fn message_from_json(json: &Json, fds: &[UnixFd]) -> Result<MarshalledMessage, JsonError> {
+    let invalid = |member: &'static str, value: &Json| JsonError::InvalidMember {
+        member,
+        value: value.to_string(),
+    };
+    let string_member = |member: &'static str| match json.get(member) {
+        None => Ok(None),
+        Some(Json::String(value)) => Ok(Some(value.clone())),
+        Some(other) => Err(invalid(member, other)),
+    };
+    let number_member = |member: &'static str| match json.get(member) {
+        None => Ok(None),
+        Some(value) => match value {
+            Json::Number(Number::Unsigned(val)) => u32::try_from(*val).ok(),
+            _ => None,
+        }
+        .map(Some)
+        .ok_or_else(|| invalid(member, value)),
+    };
+
+    let byteorder = match json.get("endian") {
+        None => ByteOrder::NATIVE,
+        Some(Json::String(endian)) if endian == "l" => ByteOrder::LittleEndian,
+        Some(Json::String(endian)) if endian == "B" => ByteOrder::BigEndian,
+        Some(other) => return Err(invalid("endian", other)),
+    };
+    let mut msg = MarshalledMessage::with_byteorder(byteorder);
+
+    let typ = json.get("type").ok_or(JsonError::MissingMember("type"))?;
+    msg.typ = match typ {
+        Json::String(name) if name == "method_call" => MessageType::Call,
+        Json::String(name) if name == "method_return" => MessageType::Reply,
+        Json::String(name) if name == "error" => MessageType::Error,
+        Json::String(name) if name == "signal" => MessageType::Signal,
+        other => return Err(invalid("type", other)),
+    };
+    if let Some(flags) = number_member("flags")? {
+        msg.flags =
+            u8::try_from(flags).map_err(|_| invalid("flags", json.get("flags").unwrap()))?;
+    }
+    msg.dynheader.serial = number_member("cookie")?.filter(|serial| *serial != 0);
+    msg.dynheader.response_serial = number_member("reply_cookie")?;
+    msg.dynheader.sender = string_member("sender")?;
+    msg.dynheader.destination = string_member("destination")?;
+    msg.dynheader.object = string_member("path")?;
+    msg.dynheader.interface = string_member("interface")?;
+    msg.dynheader.member = string_member("member")?;
+    msg.dynheader.error_name = string_member("error_name")?;
+
+    let payload = json
+        .get("payload")
+        .ok_or(JsonError::MissingMember("payload"))?;
+    let sig = match payload.get("type") {
+        Some(Json::String(sig)) => sig,
+        Some(other) => return Err(invalid("payload", other)),
+        None => return Err(JsonError::MissingMember("type")),
+    };
+    let types = if sig.is_empty() {
+        Vec::new()
+    } else {
+        signature::Type::parse_description(sig)?
+    };
+    let data = match payload.get("data") {
+        Some(Json::Array(data)) if data.len() == types.len() => data,
+        Some(Json::Array(data)) => {
+            return Err(JsonError::WrongLength {
+                sig: sig.clone(),
+                expected: types.len(),
+                found: data.len(),
+            })
+        }
+        Some(other) => return Err(invalid("payload", other)),
+        None => return Err(JsonError::MissingMember("data")),
+    };
+    let params = data
+        .iter()
+        .zip(&types)
+        .map(|(value, typ)| param_from_json(value, typ, fds))
+        .collect::<Result<Vec<_>, _>>()?;
+    msg.body.push_old_params(&params)?;
+    Ok(msg)
+}
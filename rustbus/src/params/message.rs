@@ -68,6 +68,9 @@ impl<'a, 'e> Message<'a, 'e> {
         self.dynheader.make_response()
     }
 
+    pub fn has_flag(&self, flag: HeaderFlags) -> bool {
+        flag.is_set(self.flags)
+    }
     pub fn set_flag(&mut self, flag: HeaderFlags) {
         flag.set(&mut self.flags)
     }
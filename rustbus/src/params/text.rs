@@ -0,0 +1,290 @@
+//! Build params from textual arguments in the syntax busctl uses
+//!
+//! The values are given as a list of tokens that are consumed one after another, following the signature:
+//! * Base types are a single token. Booleans may be `true`/`false`, `yes`/`no`, `on`/`off` or `1`/`0`. Unix fds are
+//!   the number of an fd that is open in this process, it gets duplicated.
+//! * Arrays are the number of elements followed by the elements
+//! * Dicts are the number of entries followed by key and value of each entry
+//! * Structs are just their fields one after another
+//! * Variants are the signature of the contained value followed by the value
+//!
+//! So the signature `a{sv}(ii)as` with the tokens `2 key1 s hello key2 i 5 3 4 2 a b` results in
+//! `{"key1": <"hello">, "key2": <5>}, (3, 4), ["a", "b"]`.
+//!
+//! ```rust
+//! use rustbus::MessageBuilder;
+//!
+//! let mut msg = MessageBuilder::new()
+//!     .call("Set")
+//!     .on("/io/killing/spark")
+//!     .with_interface("io.killing.spark")
+//!     .at("io.killing.spark")
+//!     .build();
+//! msg.body
+//!     .push_text_args("a{sv}(ii)as", "2 key1 s hello key2 i 5 3 4 2 a b".split(' '))
+//!     .unwrap();
+//! assert_eq!(msg.get_sig(), "a{sv}(ii)as");
+//! ```
+
+use crate::params::{self, Array, Base, Container, Dict, DictMap, Param, Variant};
+use crate::signature;
+use crate::wire::errors::MarshalError;
+use crate::wire::UnixFd;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TextError {
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(#[from] signature::Error),
+    /// The tokens ended before the signature was satisfied
+    #[error("Missing token {index}, expected {expected}")]
+    MissingToken { index: usize, expected: String },
+    /// The token could not be parsed as the type the signature requires at this point
+    #[error("Token {index} ({token:?}) is not a valid {expected}")]
+    InvalidToken {
+        index: usize,
+        token: String,
+        expected: String,
+    },
+    /// There were more tokens than the signature describes
+    #[error("Token {index} ({token:?}) is not described by the signature")]
+    TooManyTokens { index: usize, token: String },
+    /// The variant at this token nests the values deeper than dbus allows
+    #[error("Token {index} nests the values deeper than {MAX_NESTING_DEPTH} containers")]
+    NestedTooDeeply { index: usize },
+    #[error("An error occured while marshalling: {0}")]
+    Marshal(#[from] MarshalError),
+}
+
+// Dbus allows 32 nested arrays plus 32 nested structs. The signatures are checked against that already,
+// but variants can nest values without a limit.
+const MAX_NESTING_DEPTH: usize = 64;
+
+/// Parse the tokens into one param for each type in the signature. All tokens must be used.
+pub fn parse_args<I>(sig: &str, tokens: I) -> Result<Vec<Param<'static, 'static>>, TextError>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let types = signature::Type::parse_description(sig)?;
+    let mut tokens = Tokens {
+        tokens: tokens.into_iter(),
+        index: 0,
+    };
+    let params = types
+        .iter()
+        .map(|typ| parse_type(typ, &mut tokens, 0))
+        .collect::<Result<Vec<_>, _>>()?;
+    match tokens.tokens.next() {
+        Some(token) => Err(TextError::TooManyTokens {
+            index: tokens.index,
+            token: token.as_ref().to_owned(),
+        }),
+        None => Ok(params),
+    }
+}
+
+/// Parse the tokens needed for one value of the type. The tokens that follow are left untouched.
+pub fn parse_value<I>(
+    typ: &signature::Type,
+    tokens: I,
+) -> Result<Param<'static, 'static>, TextError>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut tokens = Tokens {
+        tokens: tokens.into_iter(),
+        index: 0,
+    };
+    parse_type(typ, &mut tokens, 0)
+}
+
+struct Tokens<I> {
+    tokens: I,
+    // the index of the next token, used in the errors
+    index: usize,
+}
+
+impl<I> Tokens<I>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    /// Apply the conversion to the next token. If it fails the error names the token and what was expected.
+    fn next<T, F>(&mut self, expected: &dyn Fn() -> String, convert: F) -> Result<T, TextError>
+    where
+        F: FnOnce(&str) -> Option<T>,
+    {
+        let index = self.index;
+        let token = self.tokens.next().ok_or_else(|| TextError::MissingToken {
+            index,
+            expected: expected(),
+        })?;
+        self.index += 1;
+        let token = token.as_ref();
+        convert(token).ok_or_else(|| TextError::InvalidToken {
+            index,
+            token: token.to_owned(),
+            expected: expected(),
+        })
+    }
+}
+
+fn sig_string(typ: &signature::Type) -> String {
+    let mut sig = String::new();
+    typ.to_str(&mut sig);
+    sig
+}
+
+fn base_name(base: signature::Base) -> &'static str {
+    match base {
+        signature::Base::Byte => "byte",
+        signature::Base::Int16 => "int16",
+        signature::Base::Uint16 => "uint16",
+        signature::Base::Int32 => "int32",
+        signature::Base::Uint32 => "uint32",
+        signature::Base::UnixFd => "unix fd",
+        signature::Base::Int64 => "int64",
+        signature::Base::Uint64 => "uint64",
+        signature::Base::Double => "double",
+        signature::Base::String => "string",
+        signature::Base::Signature => "signature",
+        signature::Base::ObjectPath => "object path",
+        signature::Base::Boolean => "boolean",
+    }
+}
+
+/// The depth counts the containers around the value
+fn parse_type<I>(
+    typ: &signature::Type,
+    tokens: &mut Tokens<I>,
+    depth: usize,
+) -> Result<Param<'static, 'static>, TextError>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    match typ {
+        signature::Type::Base(base) => parse_base(*base, tokens).map(Param::Base),
+        signature::Type::Container(container) => {
+            parse_container(typ, container, tokens, depth).map(Param::Container)
+        }
+    }
+}
+
+fn parse_base<I>(base: signature::Base, tokens: &mut Tokens<I>) -> Result<Base<'static>, TextError>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    let expected = || {
+        let sig = sig_string(&signature::Type::Base(base));
+        format!("{} ({})", base_name(base), sig)
+    };
+    tokens.next(&expected, |token| match base {
+        signature::Base::Byte => token.parse().ok().map(Base::Byte),
+        signature::Base::Int16 => token.parse().ok().map(Base::Int16),
+        signature::Base::Uint16 => token.parse().ok().map(Base::Uint16),
+        signature::Base::Int32 => token.parse().ok().map(Base::Int32),
+        signature::Base::Uint32 => token.parse().ok().map(Base::Uint32),
+        signature::Base::Int64 => token.parse().ok().map(Base::Int64),
+        signature::Base::Uint64 => token.parse().ok().map(Base::Uint64),
+        signature::Base::Double => token
+            .parse::<f64>()
+            .ok()
+            .map(|val| Base::Double(val.to_bits())),
+        signature::Base::Boolean => match token {
+            "true" | "yes" | "on" | "1" => Some(Base::Boolean(true)),
+            "false" | "no" | "off" | "0" => Some(Base::Boolean(false)),
+            _ => None,
+        },
+        signature::Base::String => Some(Base::String(token.to_owned())),
+        signature::Base::ObjectPath => params::validate_object_path(token)
+            .ok()
+            .map(|_| Base::ObjectPath(token.to_owned())),
+        signature::Base::Signature => params::validate_signature(token)
+            .ok()
+            .map(|_| Base::Signature(token.to_owned())),
+        // the fd stays open for the caller, the message gets its own copy
+        signature::Base::UnixFd => token
+            .parse()
+            .ok()
+            .and_then(|fd| nix::unistd::dup(fd).ok())
+            .map(|fd| Base::UnixFd(UnixFd::new(fd))),
+    })
+}
+
+fn parse_count<I>(typ: &signature::Type, tokens: &mut Tokens<I>) -> Result<usize, TextError>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    let expected = || format!("element count for {}", sig_string(typ));
+    tokens.next(&expected, |token| token.parse().ok())
+}
+
+fn parse_container<I>(
+    typ: &signature::Type,
+    container: &signature::Container,
+    tokens: &mut Tokens<I>,
+    depth: usize,
+) -> Result<Container<'static, 'static>, TextError>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    if depth >= MAX_NESTING_DEPTH {
+        return Err(TextError::NestedTooDeeply {
+            index: tokens.index,
+        });
+    }
+    match container {
+        signature::Container::Array(element_sig) => {
+            let count = parse_count(typ, tokens)?;
+            let values = (0..count)
+                .map(|_| parse_type(element_sig, tokens, depth + 1))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Container::Array(Array {
+                element_sig: element_sig.as_ref().clone(),
+                values,
+            }))
+        }
+        signature::Container::Dict(key_sig, value_sig) => {
+            let count = parse_count(typ, tokens)?;
+            let mut map = DictMap::new();
+            for _ in 0..count {
+                let key = parse_base(*key_sig, tokens)?;
+                let value = parse_type(value_sig, tokens, depth + 1)?;
+                map.insert(key, value);
+            }
+            Ok(Container::Dict(Dict {
+                key_sig: *key_sig,
+                value_sig: value_sig.as_ref().clone(),
+                map,
+            }))
+        }
+        signature::Container::Struct(types) => {
+            let fields = types
+                .as_ref()
+                .iter()
+                .map(|field| parse_type(field, tokens, depth + 1))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Container::Struct(fields))
+        }
+        signature::Container::Variant => {
+            let expected = || "single complete type for the variant (g)".to_owned();
+            let sig = tokens.next(&expected, |token| {
+                let mut types = signature::Type::parse_description(token).ok()?;
+                if types.len() == 1 {
+                    types.pop()
+                } else {
+                    None
+                }
+            })?;
+            let value = parse_type(&sig, tokens, depth + 1)?;
+            Ok(Container::Variant(Box::new(Variant { sig, value })))
+        }
+    }
+}
@@ -60,7 +60,9 @@ fn get_machine_id() -> Result<String, std::io::Error> {
 }
 
 /// Handles messages that are of the org.freedesktop.DBus.Peer interface. Returns as a bool whether the message was actually
-/// of that interface and an Error if there were any while handling the message
+/// of that interface and an Error if there were any while handling the message.
+///
+/// No reply is sent if the caller set the NO_REPLY_EXPECTED flag.
 pub fn handle_peer_message(
     msg: &MarshalledMessage,
     con: &mut DuplexConn,
@@ -70,20 +72,24 @@ pub fn handle_peer_message(
             if let Some(member) = &msg.dynheader.member {
                 match member.as_str() {
                     "Ping" => {
-                        let reply = msg.dynheader.make_response();
-                        con.send
-                            .send_message(&reply)?
-                            .write_all()
-                            .map_err(crate::connection::ll_conn::force_finish_on_error)?;
+                        if msg.expects_reply() {
+                            let reply = msg.dynheader.make_response();
+                            con.send
+                                .send_message(&reply)?
+                                .write_all()
+                                .map_err(crate::connection::ll_conn::force_finish_on_error)?;
+                        }
                         Ok(true)
                     }
                     "GetMachineId" => {
-                        let mut reply = msg.dynheader.make_response();
-                        reply.body.push_param(get_machine_id().unwrap()).unwrap();
-                        con.send
-                            .send_message(&reply)?
-                            .write_all()
-                            .map_err(crate::connection::ll_conn::force_finish_on_error)?;
+                        if msg.expects_reply() {
+                            let mut reply = msg.dynheader.make_response();
+                            reply.body.push_param(get_machine_id().unwrap()).unwrap();
+                            con.send
+                                .send_message(&reply)?
+                                .write_all()
+                                .map_err(crate::connection::ll_conn::force_finish_on_error)?;
+                        }
                         Ok(true)
                     }
 
@@ -2,7 +2,9 @@
 
 use crate::message_builder::DynamicHeader;
 use crate::message_builder::MarshalledMessage;
+use crate::message_builder::MarshalledMessageBody;
 use crate::message_builder::MessageBuilder;
+use crate::wire::errors::MarshalError;
 
 pub fn hello() -> MarshalledMessage {
     make_standard_msg("Hello")
@@ -29,6 +31,109 @@ pub fn list_names() -> MarshalledMessage {
     make_standard_msg("ListNames")
 }
 
+macro_rules! well_known_errors {
+    ($($variant:ident => $name:literal,)*) => {
+        /// Error names defined by the dbus spec and the reference implementation, that are commonly
+        /// returned by the bus and by services
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum WellKnownError {
+            $($variant,)*
+        }
+
+        impl WellKnownError {
+            /// The full error name, e.g. `org.freedesktop.DBus.Error.Failed`
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(WellKnownError::$variant => $name,)*
+                }
+            }
+
+            /// Find the well known error with this name
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($name => Some(WellKnownError::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+well_known_errors! {
+    Failed => "org.freedesktop.DBus.Error.Failed",
+    NoMemory => "org.freedesktop.DBus.Error.NoMemory",
+    ServiceUnknown => "org.freedesktop.DBus.Error.ServiceUnknown",
+    NameHasNoOwner => "org.freedesktop.DBus.Error.NameHasNoOwner",
+    NoReply => "org.freedesktop.DBus.Error.NoReply",
+    IOError => "org.freedesktop.DBus.Error.IOError",
+    BadAddress => "org.freedesktop.DBus.Error.BadAddress",
+    NotSupported => "org.freedesktop.DBus.Error.NotSupported",
+    LimitsExceeded => "org.freedesktop.DBus.Error.LimitsExceeded",
+    AccessDenied => "org.freedesktop.DBus.Error.AccessDenied",
+    AuthFailed => "org.freedesktop.DBus.Error.AuthFailed",
+    NoServer => "org.freedesktop.DBus.Error.NoServer",
+    Timeout => "org.freedesktop.DBus.Error.Timeout",
+    NoNetwork => "org.freedesktop.DBus.Error.NoNetwork",
+    AddressInUse => "org.freedesktop.DBus.Error.AddressInUse",
+    Disconnected => "org.freedesktop.DBus.Error.Disconnected",
+    InvalidArgs => "org.freedesktop.DBus.Error.InvalidArgs",
+    FileNotFound => "org.freedesktop.DBus.Error.FileNotFound",
+    FileExists => "org.freedesktop.DBus.Error.FileExists",
+    UnknownMethod => "org.freedesktop.DBus.Error.UnknownMethod",
+    UnknownObject => "org.freedesktop.DBus.Error.UnknownObject",
+    UnknownInterface => "org.freedesktop.DBus.Error.UnknownInterface",
+    UnknownProperty => "org.freedesktop.DBus.Error.UnknownProperty",
+    PropertyReadOnly => "org.freedesktop.DBus.Error.PropertyReadOnly",
+    TimedOut => "org.freedesktop.DBus.Error.TimedOut",
+    MatchRuleNotFound => "org.freedesktop.DBus.Error.MatchRuleNotFound",
+    MatchRuleInvalid => "org.freedesktop.DBus.Error.MatchRuleInvalid",
+    InvalidSignature => "org.freedesktop.DBus.Error.InvalidSignature",
+    InconsistentMessage => "org.freedesktop.DBus.Error.InconsistentMessage",
+    InteractiveAuthorizationRequired => "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired",
+}
+
+/// Errors that can be sent as an error reply to a call.
+///
+/// Implement this for the error type of your handlers, so the `DispatchConn` can answer failed calls with a proper error
+/// instead of stopping. For enums it can be derived, see the `DBusError` derive macro in rustbus_derive.
+pub trait DBusError {
+    /// The error name, e.g. `org.freedesktop.DBus.Error.Failed`
+    fn error_name(&self) -> &str;
+
+    /// A human readable description, sent as the first argument of the error
+    fn error_message(&self) -> Option<String> {
+        None
+    }
+
+    /// Push additional arguments to the error reply, they follow the message
+    fn marshal_extra(&self, _body: &mut MarshalledMessageBody) -> Result<(), MarshalError> {
+        Ok(())
+    }
+
+    /// Create the error reply to this call
+    fn to_error_reply(&self, call: &DynamicHeader) -> MarshalledMessage {
+        let mut reply = call.make_error_response(self.error_name(), self.error_message());
+        if self.marshal_extra(&mut reply.body).is_err() {
+            // better send the error without the extras than none at all
+            reply = call.make_error_response(self.error_name(), self.error_message());
+        }
+        reply
+    }
+}
+
+impl DBusError for WellKnownError {
+    fn error_name(&self) -> &str {
+        self.name()
+    }
+}
+
+/// Handlers that do not have their own error type fail with `org.freedesktop.DBus.Error.Failed`
+impl DBusError for () {
+    fn error_name(&self) -> &str {
+        WellKnownError::Failed.name()
+    }
+}
+
 pub const DBUS_NAME_FLAG_ALLOW_REPLACEMENT: u32 = 1;
 pub const DBUS_NAME_FLAG_REPLACE_EXISTING: u32 = 1 << 1;
 pub const DBUS_NAME_FLAG_DO_NOT_QUEUE: u32 = 1 << 2;
@@ -38,6 +143,13 @@ pub const DBUS_REQUEST_NAME_REPLY_IN_QUEUE: u32 = 2;
 pub const DBUS_REQUEST_NAME_REPLY_EXISTS: u32 = 3;
 pub const DBUS_REQUEST_NAME_REPLY_ALREADY_OWNER: u32 = 4;
 
+pub const DBUS_RELEASE_NAME_REPLY_RELEASED: u32 = 1;
+pub const DBUS_RELEASE_NAME_REPLY_NON_EXISTENT: u32 = 2;
+pub const DBUS_RELEASE_NAME_REPLY_NOT_OWNER: u32 = 3;
+
+pub const DBUS_START_REPLY_SUCCESS: u32 = 1;
+pub const DBUS_START_REPLY_ALREADY_RUNNING: u32 = 2;
+
 fn make_standard_msg(name: &str) -> MarshalledMessage {
     MessageBuilder::new()
         .call(name)
@@ -62,6 +174,8 @@ pub fn release_name(name: &str) -> MarshalledMessage {
 }
 
 /// Add a match rule to receive signals. e.g. match_rule = "type='signal'" to get all signals
+///
+/// A `MatchRule` can be used to build the string with `rule.to_string()`
 pub fn add_match(match_rule: &str) -> MarshalledMessage {
     let mut msg = make_standard_msg("AddMatch");
     msg.body.push_param(match_rule).unwrap();
@@ -81,10 +195,17 @@ pub fn unknown_method(call: &DynamicHeader) -> MarshalledMessage {
         call.member.clone().unwrap_or_else(|| "".to_owned()),
         call.object.clone().unwrap_or_else(|| "".to_owned()),
     );
-    call.make_error_response(
-        "org.freedesktop.DBus.Error.UnknownMethod".to_owned(),
-        Some(text),
-    )
+    call.make_error_response(WellKnownError::UnknownMethod.name(), Some(text))
+}
+
+/// Error message to tell the caller that the object does not implement the interface
+pub fn unknown_interface(call: &DynamicHeader) -> MarshalledMessage {
+    let text = format!(
+        "Interface {} is not implemented by object {}",
+        call.interface.clone().unwrap_or_else(|| "".to_owned()),
+        call.object.clone().unwrap_or_else(|| "".to_owned()),
+    );
+    call.make_error_response(WellKnownError::UnknownInterface.name(), Some(text))
 }
 
 /// Error message to tell the caller that this method uses a different interface than what the caller provided as parameters
@@ -101,8 +222,5 @@ pub fn invalid_args(call: &DynamicHeader, sig: Option<&str>) -> MarshalledMessag
         }
     );
 
-    call.make_error_response(
-        "org.freedesktop.DBus.Error.InvalidArgs".to_owned(),
-        Some(text),
-    )
+    call.make_error_response(WellKnownError::InvalidArgs.name(), Some(text))
 }
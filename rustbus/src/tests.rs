@@ -5,25 +5,44 @@ use crate::wire::unmarshal::unmarshal_dynamic_header;
 use crate::wire::unmarshal::unmarshal_header;
 use crate::wire::unmarshal::unmarshal_next_message;
 
+mod bus_proxy;
 mod dbus_send;
+mod dispatch_lifecycle;
+mod dispatch_routing;
+mod dispatch_signals;
+mod fake_bus;
 mod fdpassing;
+mod format;
+mod json;
+mod monitor_conn;
+mod name_owner;
+mod name_watcher;
+mod no_reply;
+mod pcap;
+mod pending_reply;
+mod rpc_call;
+#[cfg(feature = "serde")]
+mod serde_support;
+mod subscriptions;
+mod text_args;
+mod threaded_dispatch;
 mod verify_marshalling;
 mod verify_padding;
 
 // this tests the happy path
 #[test]
 fn test_marshal_unmarshal() {
-    let mut params: Vec<Param> = Vec::new();
-
-    params.push(128u8.into());
-    params.push(128u16.into());
-    params.push((-128i16).into());
-    params.push(1212128u32.into());
-    params.push((-1212128i32).into());
-    params.push(1212121212128u64.into());
-    params.push((-1212121212128i64).into());
-    params.push("TesttestTesttest".to_owned().into());
-    params.push(Base::ObjectPath("/this/object/path".into()).into());
+    let mut params: Vec<Param> = vec![
+        128u8.into(),
+        128u16.into(),
+        (-128i16).into(),
+        1212128u32.into(),
+        (-1212128i32).into(),
+        1212121212128u64.into(),
+        (-1212121212128i64).into(),
+        "TesttestTesttest".to_owned().into(),
+        Base::ObjectPath("/this/object/path".into()).into(),
+    ];
 
     let mut msg = crate::message_builder::MessageBuilder::new()
         .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
@@ -41,7 +60,7 @@ fn test_marshal_unmarshal() {
 
     msg.dynheader.serial = Some(1);
     let mut buf = Vec::new();
-    marshal(&msg, 0, &mut buf).unwrap();
+    marshal(&msg, 1, &mut buf).unwrap();
     let (hdrbytes, header) = unmarshal_header(&buf, 0).unwrap();
     let (dynhdrbytes, dynheader) = unmarshal_dynamic_header(&header, &buf, hdrbytes).unwrap();
 
@@ -99,7 +118,7 @@ fn test_invalid_stuff() {
         Err(crate::wire::errors::MarshalError::Validation(
             crate::params::validation::Error::InvalidInterface
         )),
-        marshal(&msg, 0, &mut buf)
+        marshal(&msg, 1, &mut buf)
     );
 
     // invalid member
@@ -116,6 +135,104 @@ fn test_invalid_stuff() {
         Err(crate::wire::errors::MarshalError::Validation(
             crate::params::validation::Error::InvalidMembername
         )),
+        marshal(&msg, 1, &mut buf)
+    );
+}
+
+// this tests that headers violating the spec are rejected on both sides
+#[test]
+fn test_header_validation() {
+    use crate::message_builder::MessageType;
+    use crate::wire::errors::{HeaderError, MarshalError, UnmarshalError};
+
+    let mut msg = crate::message_builder::MessageBuilder::new()
+        .call("Member")
+        .at("io.killing.spark")
+        .on("/io/killing/spark")
+        .build();
+    let mut buf = Vec::new();
+    assert_eq!(
+        Err(MarshalError::InvalidHeader(HeaderError::ZeroSerial)),
         marshal(&msg, 0, &mut buf)
     );
+
+    msg.dynheader.object = None;
+    buf.clear();
+    assert_eq!(
+        Err(MarshalError::InvalidHeader(HeaderError::MissingPath)),
+        marshal(&msg, 1, &mut buf)
+    );
+
+    let mut msg = crate::message_builder::MessageBuilder::new()
+        .signal("io.killing.spark", "TestSignal", "/io/killing/spark")
+        .build();
+    msg.dynheader.interface = None;
+    buf.clear();
+    assert_eq!(
+        Err(MarshalError::InvalidHeader(HeaderError::MissingInterface)),
+        marshal(&msg, 1, &mut buf)
+    );
+
+    let mut err = msg
+        .dynheader
+        .make_error_response("io.killing.spark.Error", None);
+    assert_eq!(MessageType::Error, err.typ);
+    err.dynheader.response_serial = None;
+    buf.clear();
+    assert_eq!(
+        Err(MarshalError::InvalidHeader(HeaderError::MissingReplySerial)),
+        marshal(&err, 1, &mut buf)
+    );
+
+    // an error name has to survive the round trip
+    err.dynheader.response_serial = Some(1);
+    buf.clear();
+    marshal(&err, 2, &mut buf).unwrap();
+    let (hdrbytes, header) = unmarshal_header(&buf, 0).unwrap();
+    let (_, dynheader) = unmarshal_dynamic_header(&header, &buf, hdrbytes).unwrap();
+    assert_eq!(
+        Some("io.killing.spark.Error"),
+        dynheader.error_name.as_deref()
+    );
+
+    // patch the message type in the marshalled header to 'call', which lacks PATH and MEMBER
+    buf[1] = 1;
+    let (hdrbytes, header) = unmarshal_header(&buf, 0).unwrap();
+    assert_eq!(
+        Err(UnmarshalError::InvalidHeader(HeaderError::MissingPath)),
+        unmarshal_dynamic_header(&header, &buf, hdrbytes).map(|_| ())
+    );
+    // and the serial to zero
+    buf[1] = 3;
+    buf[8..12].copy_from_slice(&[0, 0, 0, 0]);
+    let (hdrbytes, header) = unmarshal_header(&buf, 0).unwrap();
+    assert_eq!(
+        Err(UnmarshalError::InvalidHeader(HeaderError::ZeroSerial)),
+        unmarshal_dynamic_header(&header, &buf, hdrbytes).map(|_| ())
+    );
+
+    // a body length over the maximum message size
+    buf[4..8].copy_from_slice(&(crate::wire::MAX_MESSAGE_SIZE as u32 + 1).to_ne_bytes());
+    assert_eq!(
+        Err(UnmarshalError::MessageTooBig(
+            crate::wire::MAX_MESSAGE_SIZE + 1
+        )),
+        unmarshal_header(&buf, 0).map(|_| ())
+    );
+
+    // an array length over the maximum array size
+    let mut fake_array = Vec::new();
+    fake_array.extend_from_slice(&(crate::wire::MAX_ARRAY_SIZE as u32 + 1).to_le_bytes());
+    fake_array.extend_from_slice(&[0; 16]);
+    let mut ctx = crate::wire::unmarshal::UnmarshalContext {
+        fds: &[],
+        buf: &fake_array,
+        byteorder: crate::ByteOrder::LittleEndian,
+        offset: 0,
+    };
+    use crate::Unmarshal;
+    assert_eq!(
+        Err(UnmarshalError::ArrayTooBig(crate::wire::MAX_ARRAY_SIZE + 1)),
+        Vec::<u8>::unmarshal(&mut ctx).map(|_| ())
+    );
 }
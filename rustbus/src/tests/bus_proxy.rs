@@ -0,0 +1,154 @@
+use super::fake_bus;
+use crate::connection::bus_proxy::{BusProxy, ConnectionCredentials, StartServiceReply};
+use crate::connection::ll_conn::DuplexConn;
+use crate::connection::rpc_conn::{CallError, RpcConn};
+use crate::connection::{get_system_bus_path, Timeout};
+use crate::standard_messages::{WellKnownError, DBUS_START_REPLY_ALREADY_RUNNING};
+use crate::wire::errors::UnmarshalError;
+
+use std::collections::HashMap;
+
+crate::dbus_variant_sig!(TestValue, U32 => u32; Bytes => Vec<u8>; Str => String; StrList => Vec<String>);
+
+#[test]
+fn test_bus_proxy_system_bus() {
+    let mut conn = DuplexConn::connect_to_bus(get_system_bus_path().unwrap(), true).unwrap();
+    let unique_name = conn.send_hello(Timeout::Infinite).unwrap();
+    let mut con = RpcConn::new(conn);
+    let mut bus = BusProxy::new(&mut con, Timeout::Infinite);
+
+    assert!(!bus.get_id().unwrap().is_empty());
+    let names = bus.list_names().unwrap();
+    assert!(names.contains(&"org.freedesktop.DBus".to_owned()));
+    assert!(names.contains(&unique_name));
+    assert!(bus.name_has_owner(&unique_name).unwrap());
+    assert!(!bus.name_has_owner("io.killing.spark.NotThere").unwrap());
+    assert_eq!(bus.get_name_owner(&unique_name).unwrap(), unique_name);
+    assert_eq!(
+        bus.get_name_owner("io.killing.spark.NotThere")
+            .err()
+            .unwrap()
+            .well_known(),
+        Some(WellKnownError::NameHasNoOwner)
+    );
+    assert_eq!(
+        bus.list_queued_owners(&unique_name).unwrap(),
+        vec![unique_name.clone()]
+    );
+    bus.list_activatable_names().unwrap();
+
+    let uid = nix::unistd::getuid().as_raw();
+    let pid = std::process::id();
+    assert_eq!(bus.get_connection_unix_user(&unique_name).unwrap(), uid);
+    assert_eq!(
+        bus.get_connection_unix_process_id(&unique_name).unwrap(),
+        pid
+    );
+    let creds = bus.get_connection_credentials(&unique_name).unwrap();
+    assert_eq!(creds.unix_user_id, Some(uid));
+    assert_eq!(creds.process_id, Some(pid));
+
+    // the properties are only there on newer buses
+    match bus.features() {
+        Ok(_) => {
+            bus.interfaces().unwrap();
+        }
+        Err(err) => assert!(matches!(err, CallError::Remote(_))),
+    }
+}
+
+#[test]
+fn test_bus_proxy_decoding() {
+    let (mut bus, mut con) = fake_bus::connect();
+    let script = std::thread::spawn(move || {
+        let call = bus.expect_call("GetConnectionCredentials");
+        assert_eq!(call.body.parser().get::<&str>().unwrap(), ":1.42");
+        let mut reply = call.dynheader.make_response();
+        let mut creds: HashMap<&str, TestValue> = HashMap::new();
+        creds.insert("UnixUserID", TestValue::U32(1000));
+        creds.insert("ProcessID", TestValue::U32(4242));
+        // unknown credentials are skipped
+        creds.insert("SomethingNew", TestValue::U32(4242));
+        reply.body.push_param(&creds).unwrap();
+        let mut label: HashMap<&str, TestValue> = HashMap::new();
+        label.insert(
+            "LinuxSecurityLabel",
+            TestValue::Bytes(b"unconfined\0".to_vec()),
+        );
+        bus.send(reply);
+
+        let call = bus.expect_call("GetConnectionCredentials");
+        let mut reply = call.dynheader.make_response();
+        reply.body.push_param(&label).unwrap();
+        bus.send(reply);
+
+        // known credentials with the wrong type are errors
+        let call = bus.expect_call("GetConnectionCredentials");
+        let mut reply = call.dynheader.make_response();
+        let mut wrong: HashMap<&str, TestValue> = HashMap::new();
+        wrong.insert("ProcessID", TestValue::Str("4242".to_owned()));
+        reply.body.push_param(&wrong).unwrap();
+        bus.send(reply);
+
+        let call = bus.expect_call("StartServiceByName");
+        assert_eq!(
+            call.body.parser().get2::<&str, u32>().unwrap(),
+            ("io.killing.spark", 0)
+        );
+        bus.reply(&call, DBUS_START_REPLY_ALREADY_RUNNING);
+
+        let call = bus.expect_call("UpdateActivationEnvironment");
+        let env: HashMap<String, String> = call.body.parser().get().unwrap();
+        assert_eq!(env.get("LANG").map(String::as_str), Some("C"));
+        bus.reply_empty(&call);
+
+        let call = bus.expect_call("Get");
+        assert_eq!(
+            call.dynheader.interface.as_deref(),
+            Some("org.freedesktop.DBus.Properties")
+        );
+        assert_eq!(
+            call.body.parser().get2::<&str, &str>().unwrap(),
+            ("org.freedesktop.DBus", "Features")
+        );
+        let mut reply = call.dynheader.make_response();
+        reply
+            .body
+            .push_param(TestValue::StrList(vec!["SystemdActivation".to_owned()]))
+            .unwrap();
+        bus.send(reply);
+    });
+
+    let mut proxy = BusProxy::new(&mut con, Timeout::Infinite);
+    assert_eq!(
+        proxy.get_connection_credentials(":1.42").unwrap(),
+        ConnectionCredentials {
+            unix_user_id: Some(1000),
+            process_id: Some(4242),
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        proxy
+            .get_connection_credentials(":1.42")
+            .unwrap()
+            .linux_security_label,
+        Some(b"unconfined\0".to_vec())
+    );
+    assert!(matches!(
+        proxy.get_connection_credentials(":1.42"),
+        Err(CallError::Unmarshal(UnmarshalError::WrongSignature))
+    ));
+    assert_eq!(
+        proxy.start_service_by_name("io.killing.spark").unwrap(),
+        StartServiceReply::AlreadyRunning
+    );
+    let mut env = HashMap::new();
+    env.insert("LANG".to_owned(), "C".to_owned());
+    proxy.update_activation_environment(&env).unwrap();
+    assert_eq!(
+        proxy.features().unwrap(),
+        vec!["SystemdActivation".to_owned()]
+    );
+    script.join().unwrap();
+}
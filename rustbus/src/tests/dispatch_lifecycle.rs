@@ -0,0 +1,124 @@
+use crate::connection::dispatch_conn::{
+    DispatchConn, HandleEnvironment, Matches, StopHandle, StopReason,
+};
+use crate::connection::ll_conn::{DuplexConn, SendConn};
+use crate::connection::rpc_conn::RpcConn;
+use crate::connection::threaded_dispatch_conn::{SharedHandleEnvironment, ThreadedDispatchConn};
+use crate::connection::Timeout;
+use crate::message_builder::{MarshalledMessage, MessageBuilder, MessageType};
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+fn call(con: &mut RpcConn) -> u32 {
+    let mut msg = MessageBuilder::new()
+        .call("Method")
+        .on("/io/killing/spark")
+        .with_interface("io.killing.spark")
+        .build();
+    con.send_message(&mut msg).unwrap().write_all().unwrap()
+}
+
+fn new_dispatch_conn<T>(conn: DuplexConn, ctx: T) -> DispatchConn<T, ()> {
+    DispatchConn::new(
+        conn,
+        ctx,
+        Box::new(
+            |_: &mut T, _: Matches, _: &MarshalledMessage, _: &mut HandleEnvironment<T, ()>| {
+                Ok(None)
+            },
+        ),
+    )
+}
+
+#[test]
+fn test_run_until() {
+    let (conn, peer) = DuplexConn::pair();
+    let stop = StopHandle::new().unwrap();
+    let stop2 = stop.clone();
+    let dispatcher = std::thread::spawn(move || {
+        let mut dpcon = new_dispatch_conn(conn, ());
+        dpcon.run_until(&stop2).unwrap()
+    });
+
+    let mut con = RpcConn::new(peer);
+    let serial = call(&mut con);
+    let resp = con.wait_response(serial, Timeout::Infinite).unwrap();
+    assert_eq!(resp.typ, MessageType::Reply);
+
+    stop.stop();
+    assert_eq!(dispatcher.join().unwrap(), StopReason::Stopped);
+    assert!(stop.is_stopped());
+}
+
+#[test]
+fn test_run_once() {
+    let (conn, peer) = DuplexConn::pair();
+    let mut dpcon = new_dispatch_conn(conn, ());
+    let mut con = RpcConn::new(peer);
+
+    assert!(!dpcon
+        .run_once(Timeout::Duration(Duration::from_millis(10)))
+        .unwrap());
+    let serial = call(&mut con);
+    assert!(dpcon.run_once(Timeout::Infinite).unwrap());
+    let resp = con.wait_response(serial, Timeout::Infinite).unwrap();
+    assert_eq!(resp.typ, MessageType::Reply);
+}
+
+#[test]
+fn test_exit_on_idle() {
+    let (conn, peer) = DuplexConn::pair();
+    let mut idle_calls = 0;
+    let mut dpcon = new_dispatch_conn(conn, &mut idle_calls);
+    dpcon.set_idle_handler(
+        Duration::from_millis(20),
+        Box::new(|idle_calls: &mut &mut i32, _: &Arc<Mutex<SendConn>>| {
+            **idle_calls += 1;
+        }),
+    );
+    dpcon.set_exit_on_idle(Some(Duration::from_millis(200)));
+
+    // keep the dispatcher busy for a while, calls reset the idle time
+    let client = std::thread::spawn(move || {
+        let mut con = RpcConn::new(peer);
+        for _ in 0..3 {
+            std::thread::sleep(Duration::from_millis(100));
+            let serial = call(&mut con);
+            con.wait_response(serial, Timeout::Infinite).unwrap();
+        }
+        con
+    });
+
+    let start = Instant::now();
+    dpcon.run().unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(500));
+    drop(dpcon);
+    let _con = client.join().unwrap();
+    assert!(idle_calls >= 5);
+}
+
+#[test]
+fn test_threaded_run_until() {
+    type Env = SharedHandleEnvironment<(), ()>;
+
+    let (conn, peer) = DuplexConn::pair();
+    let stop = StopHandle::new().unwrap();
+    let stop2 = stop.clone();
+    let dispatcher = std::thread::spawn(move || {
+        let mut dpcon = ThreadedDispatchConn::new(
+            conn,
+            (),
+            Box::new(|_: &(), _: Matches, _: &MarshalledMessage, _: &mut Env| Ok(None)),
+        );
+        dpcon.run_until(&stop2)
+    });
+
+    let mut con = RpcConn::new(peer);
+    let serial = call(&mut con);
+    let resp = con.wait_response(serial, Timeout::Infinite).unwrap();
+    assert_eq!(resp.typ, MessageType::Reply);
+
+    stop.stop();
+    dispatcher.join().unwrap().unwrap();
+}
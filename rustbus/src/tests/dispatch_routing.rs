@@ -0,0 +1,296 @@
+use crate::connection::dispatch_conn::{DispatchConn, HandleEnvironment, HandleResult, Matches};
+use crate::connection::ll_conn::DuplexConn;
+use crate::connection::rpc_conn::{CallError, RpcConn};
+use crate::connection::Timeout;
+use crate::message_builder::MarshalledMessage;
+use crate::standard_messages::WellKnownError;
+
+type Env = HandleEnvironment<(), ()>;
+
+fn reply_with(msg: &MarshalledMessage, text: &str) -> HandleResult<()> {
+    let mut resp = msg.dynheader.make_response();
+    resp.body.push_param(text).unwrap();
+    Ok(Some(resp))
+}
+
+#[test]
+fn test_method_routing() {
+    // the handlers are not Send, so the DispatchConn has to live in its own thread from the start
+    let (conn, peer) = DuplexConn::pair();
+    std::thread::spawn(move || {
+        let mut dpcon = DispatchConn::new(
+            conn,
+            (),
+            Box::new(
+                |_: &mut (), _: Matches, msg: &MarshalledMessage, _: &mut Env| {
+                    reply_with(msg, "default")
+                },
+            ),
+        );
+        dpcon.add_method_handler(
+            "/io/killing/spark/:id",
+            "io.killing.spark.A",
+            "Echo",
+            Some("s"),
+            Box::new(
+                |_: &mut (), matches: Matches, msg: &MarshalledMessage, _: &mut Env| {
+                    let arg: &str = msg.body.parser().get().unwrap();
+                    reply_with(msg, &format!("A {} {}", matches.matches[":id"], arg))
+                },
+            ),
+        );
+        dpcon.add_method_handler(
+            "/io/killing/spark/:id",
+            "io.killing.spark.A",
+            "Ping",
+            None,
+            Box::new(
+                |_: &mut (), _: Matches, msg: &MarshalledMessage, _: &mut Env| {
+                    reply_with(msg, "A Ping")
+                },
+            ),
+        );
+        dpcon.add_method_handler(
+            "/io/killing/spark/:id",
+            "io.killing.spark.B",
+            "Ping",
+            None,
+            Box::new(
+                |_: &mut (), _: Matches, msg: &MarshalledMessage, _: &mut Env| {
+                    reply_with(msg, "B Ping")
+                },
+            ),
+        );
+        dpcon.add_method_handler(
+            "/io/killing/spark/:id",
+            "io.killing.spark.B",
+            "Unique",
+            None,
+            Box::new(
+                |_: &mut (), _: Matches, msg: &MarshalledMessage, _: &mut Env| {
+                    reply_with(msg, "B Unique")
+                },
+            ),
+        );
+        dpcon.add_handler(
+            "/io/killing/fallback",
+            Box::new(
+                |_: &mut (), _: Matches, msg: &MarshalledMessage, _: &mut Env| {
+                    reply_with(msg, "fallback")
+                },
+            ),
+        );
+        dpcon.add_method_handler(
+            "/io/killing/fallback",
+            "io.killing.spark.A",
+            "Ping",
+            None,
+            Box::new(
+                |_: &mut (), _: Matches, msg: &MarshalledMessage, _: &mut Env| {
+                    reply_with(msg, "fallback A Ping")
+                },
+            ),
+        );
+        // runs until the peer hangs up
+        let _ = dpcon.run();
+    });
+
+    let name = "io.killing.spark.Server";
+    let mut con = RpcConn::new(peer);
+    let mut call = |path: &str, interface: Option<&str>, member: &str, arg: Option<&str>| {
+        let mut msg = crate::message_builder::MessageBuilder::new()
+            .call(member)
+            .on(path)
+            .at(name)
+            .build();
+        msg.dynheader.interface = interface.map(str::to_owned);
+        if let Some(arg) = arg {
+            msg.body.push_param(arg).unwrap();
+        }
+        let serial = con.send_message(&mut msg).unwrap().write_all().unwrap();
+        let resp = con.wait_response(serial, Timeout::Infinite).unwrap();
+        match resp.typ {
+            crate::message_builder::MessageType::Reply => {
+                Ok(resp.body.parser().get::<String>().unwrap())
+            }
+            _ => Err(WellKnownError::from_name(
+                resp.dynheader.error_name.as_deref().unwrap(),
+            )),
+        }
+    };
+    let a = Some("io.killing.spark.A");
+    let b = Some("io.killing.spark.B");
+
+    assert_eq!(
+        call("/io/killing/spark/1", a, "Echo", Some("hi")),
+        Ok("A 1 hi".to_owned())
+    );
+    assert_eq!(
+        call("/io/killing/spark/2", a, "Ping", None),
+        Ok("A Ping".to_owned())
+    );
+    assert_eq!(
+        call("/io/killing/spark/2", b, "Ping", None),
+        Ok("B Ping".to_owned())
+    );
+    assert_eq!(
+        call("/io/killing/spark/1", a, "Echo", None),
+        Err(Some(WellKnownError::InvalidArgs))
+    );
+    assert_eq!(
+        call("/io/killing/spark/1", a, "Nope", None),
+        Err(Some(WellKnownError::UnknownMethod))
+    );
+    assert_eq!(
+        call(
+            "/io/killing/spark/1",
+            Some("io.killing.spark.C"),
+            "Ping",
+            None
+        ),
+        Err(Some(WellKnownError::UnknownInterface))
+    );
+
+    // without an interface only unique members can be routed
+    assert_eq!(
+        call("/io/killing/spark/1", None, "Unique", None),
+        Ok("B Unique".to_owned())
+    );
+    assert_eq!(
+        call("/io/killing/spark/1", None, "Ping", None),
+        Err(Some(WellKnownError::UnknownMethod))
+    );
+    assert_eq!(
+        call("/io/killing/spark/1", None, "Nope", None),
+        Err(Some(WellKnownError::UnknownMethod))
+    );
+
+    // calls no method fits go to the fallback of the object, other objects to the default handler
+    assert_eq!(
+        call("/io/killing/fallback", a, "Ping", None),
+        Ok("fallback A Ping".to_owned())
+    );
+    assert_eq!(
+        call("/io/killing/fallback", b, "Ping", None),
+        Ok("fallback".to_owned())
+    );
+    assert_eq!(
+        call("/io/killing/other", a, "Ping", None),
+        Ok("default".to_owned())
+    );
+
+    // the typed call api sees the same errors
+    let err = con
+        .call::<_, (String,)>(
+            name,
+            "/io/killing/spark/1",
+            "io.killing.spark.A",
+            "Echo",
+            (1u32,),
+            Timeout::Infinite,
+        )
+        .unwrap_err();
+    assert!(matches!(err, CallError::Remote(_)));
+    assert_eq!(err.well_known(), Some(WellKnownError::InvalidArgs));
+}
+
+#[derive(Debug)]
+struct NotFound(String);
+
+impl crate::DBusError for NotFound {
+    fn error_name(&self) -> &str {
+        "io.killing.spark.Error.NotFound"
+    }
+    fn error_message(&self) -> Option<String> {
+        Some(format!("{} was not found", self.0))
+    }
+    fn marshal_extra(
+        &self,
+        body: &mut crate::message_builder::MarshalledMessageBody,
+    ) -> Result<(), crate::wire::errors::MarshalError> {
+        body.push_param(404u32)
+    }
+}
+
+#[test]
+fn test_handler_errors() {
+    use crate::connection::dispatch_conn::HandleError;
+    type Env = HandleEnvironment<(), NotFound>;
+
+    let (conn, peer) = DuplexConn::pair();
+    std::thread::spawn(move || {
+        let mut dpcon = DispatchConn::new(
+            conn,
+            (),
+            Box::new(
+                |_: &mut (), _: Matches, msg: &MarshalledMessage, _: &mut Env| {
+                    let name: &str = msg.body.parser().get()?;
+                    Err(HandleError::User(NotFound(name.to_owned())))
+                },
+            ),
+        );
+        // errors are answered, only connection errors end the loop
+        let err = dpcon.run().unwrap_err();
+        assert!(matches!(err.1, HandleError::Connection(_)));
+    });
+
+    let mut con = RpcConn::new(peer);
+    let mut call = |arg: Option<&str>| {
+        let mut msg = crate::message_builder::MessageBuilder::new()
+            .call("Find")
+            .on("/io/killing/spark")
+            .with_interface("io.killing.spark")
+            .build();
+        if let Some(arg) = arg {
+            msg.body.push_param(arg).unwrap();
+        }
+        let serial = con.send_message(&mut msg).unwrap().write_all().unwrap();
+        con.wait_response(serial, Timeout::Infinite).unwrap()
+    };
+
+    let resp = call(Some("thing"));
+    assert_eq!(
+        resp.dynheader.error_name.as_deref(),
+        Some("io.killing.spark.Error.NotFound")
+    );
+    let (text, code): (&str, u32) = resp.body.parser().get2().unwrap();
+    assert_eq!(text, "thing was not found");
+    assert_eq!(code, 404);
+
+    // a handler failing to unmarshal its arguments means the arguments were wrong
+    let resp = call(None);
+    assert_eq!(
+        resp.dynheader.error_name.as_deref(),
+        Some(WellKnownError::InvalidArgs.name())
+    );
+
+    let resp = call(Some("other thing"));
+    assert_eq!(
+        resp.body.parser().get::<&str>().unwrap(),
+        "other thing was not found"
+    );
+}
+
+#[test]
+fn test_setup_without_dbus_error() {
+    // only running the dispatcher needs to know how to answer errors
+    #[derive(Debug)]
+    struct Plain;
+    type Env = HandleEnvironment<(), Plain>;
+
+    let (conn, _peer) = DuplexConn::pair();
+    let mut dpcon = DispatchConn::new(
+        conn,
+        (),
+        Box::new(|_: &mut (), _: Matches, _: &MarshalledMessage, _: &mut Env| Ok(None)),
+    );
+    dpcon.add_handler(
+        "/io/killing/spark",
+        Box::new(
+            |_: &mut (), _: Matches, _: &MarshalledMessage, _: &mut Env| {
+                Err(crate::connection::dispatch_conn::HandleError::User(Plain))
+            },
+        ),
+    );
+    dpcon.set_exit_on_idle(Some(std::time::Duration::from_millis(1)));
+}
@@ -0,0 +1,95 @@
+use crate::connection::dispatch_conn::{DispatchConn, HandleEnvironment, Matches};
+use crate::connection::ll_conn::DuplexConn;
+use crate::connection::rpc_conn::RpcConn;
+use crate::connection::{get_system_bus_path, Timeout};
+use crate::message_builder::{MarshalledMessage, MessageBuilder, MessageType};
+use crate::MatchRule;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+type Events = Arc<Mutex<Vec<String>>>;
+type Env = HandleEnvironment<Events, ()>;
+
+#[test]
+fn test_signal_and_reply_handlers() {
+    let mut conn = DuplexConn::connect_to_bus(get_system_bus_path().unwrap(), true).unwrap();
+    conn.send_hello(Timeout::Infinite).unwrap();
+
+    let events: Events = Arc::new(Mutex::new(Vec::new()));
+    let mut dpcon = DispatchConn::new(
+        conn,
+        events.clone(),
+        Box::new(
+            |events: &mut Events, _: Matches, msg: &MarshalledMessage, _: &mut Env| {
+                events
+                    .lock()
+                    .unwrap()
+                    .push(format!("default {:?}", msg.dynheader.member));
+                Ok(None)
+            },
+        ),
+    );
+    dpcon
+        .add_signal_handler(
+            MatchRule::new()
+                .message_type(MessageType::Signal)
+                .interface("io.killing.spark.DispatchTest")
+                .member("Ping"),
+            Box::new(
+                |events: &mut Events, _: Matches, msg: &MarshalledMessage, _: &mut Env| {
+                    let text: &str = msg.body.parser().get()?;
+                    events.lock().unwrap().push(format!("signal {}", text));
+                    Ok(None)
+                },
+            ),
+            Timeout::Infinite,
+        )
+        .unwrap();
+    dpcon
+        .call(
+            &crate::standard_messages::list_names(),
+            Box::new(
+                |events: &mut Events, _: Matches, msg: &MarshalledMessage, _: &mut Env| {
+                    let names: Vec<&str> = msg.body.parser().get()?;
+                    if names.contains(&"org.freedesktop.DBus") {
+                        events.lock().unwrap().push("reply".to_owned());
+                    }
+                    Ok(None)
+                },
+            ),
+        )
+        .unwrap();
+
+    let mut con = RpcConn::system_conn(Timeout::Infinite).unwrap();
+    let mut sig = MessageBuilder::new()
+        .signal("io.killing.spark.DispatchTest", "Ping", "/io/killing/spark")
+        .build();
+    sig.body.push_param("hello").unwrap();
+    let sig_serial = con.send_message(&mut sig).unwrap().write_all().unwrap();
+
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        dpcon
+            .run_once(Timeout::Duration(Duration::from_millis(50)))
+            .unwrap();
+        let events = events.lock().unwrap();
+        if events.contains(&"signal hello".to_owned()) && events.contains(&"reply".to_owned()) {
+            break;
+        }
+    }
+    let events = events.lock().unwrap().clone();
+    assert!(events.contains(&"signal hello".to_owned()), "{:?}", events);
+    assert!(events.contains(&"reply".to_owned()), "{:?}", events);
+    // the signal the bus sends after the hello is not matched by the rule
+    assert!(
+        events.contains(&"default Some(\"NameAcquired\")".to_owned()),
+        "{:?}",
+        events
+    );
+
+    // signals do not get answered
+    assert!(con
+        .wait_response(sig_serial, Timeout::Duration(Duration::from_millis(200)))
+        .is_err());
+}
@@ -0,0 +1,65 @@
+//! A minimal stand-in for the bus daemon, for tests that need to control what the bus answers
+
+use crate::connection::ll_conn::DuplexConn;
+use crate::connection::rpc_conn::RpcConn;
+use crate::connection::Timeout;
+use crate::message_builder::{HeaderFlags, MarshalledMessage, MessageBuilder, MessageType};
+use crate::Marshal;
+use crate::Signature;
+
+pub struct FakeBus {
+    conn: DuplexConn,
+}
+
+/// A connection to a fake bus. The bus side is meant to be moved into a thread that plays a script.
+pub fn connect() -> (FakeBus, RpcConn) {
+    let (bus, client) = DuplexConn::pair();
+    (FakeBus { conn: bus }, RpcConn::new(client))
+}
+
+impl FakeBus {
+    /// Wait for the next message and check that it is a call to the bus with this member
+    pub fn expect_call(&mut self, member: &str) -> MarshalledMessage {
+        let call = self.conn.recv.get_next_message(Timeout::Infinite).unwrap();
+        assert_eq!(call.typ, MessageType::Call);
+        assert_eq!(
+            call.dynheader.destination.as_deref(),
+            Some("org.freedesktop.DBus")
+        );
+        assert_eq!(call.dynheader.member.as_deref(), Some(member));
+        call
+    }
+
+    pub fn reply_empty(&mut self, call: &MarshalledMessage) {
+        if call.has_flag(HeaderFlags::NoReplyExpected) {
+            return;
+        }
+        self.send(call.dynheader.make_response());
+    }
+
+    pub fn reply<P: Marshal + Signature>(&mut self, call: &MarshalledMessage, value: P) {
+        let mut reply = call.dynheader.make_response();
+        reply.body.push_param(value).unwrap();
+        self.send(reply);
+    }
+
+    pub fn reply_error(&mut self, call: &MarshalledMessage, error_name: &str) {
+        self.send(call.dynheader.make_error_response(error_name, None));
+    }
+
+    /// Send a signal of the org.freedesktop.DBus interface
+    pub fn signal<P: Marshal + Signature>(&mut self, member: &str, args: &[P]) {
+        let mut signal = MessageBuilder::new()
+            .signal("org.freedesktop.DBus", member, "/org/freedesktop/DBus")
+            .build();
+        for arg in args {
+            signal.body.push_param(arg).unwrap();
+        }
+        self.send(signal);
+    }
+
+    pub fn send(&mut self, mut msg: MarshalledMessage) {
+        msg.dynheader.sender = Some("org.freedesktop.DBus".to_owned());
+        self.conn.send.send_message_write_all(&msg).unwrap();
+    }
+}
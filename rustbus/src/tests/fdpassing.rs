@@ -20,12 +20,10 @@ fn test_fd_passing() {
         .unwrap()
         .write_all()
         .unwrap();
-    con2.send_message(&mut crate::standard_messages::add_match(
-        "type='signal'".into(),
-    ))
-    .unwrap()
-    .write_all()
-    .unwrap();
+    con2.send_message(&mut crate::standard_messages::add_match("type='signal'"))
+        .unwrap()
+        .write_all()
+        .unwrap();
 
     std::thread::sleep(std::time::Duration::from_secs(1));
 
@@ -39,10 +37,9 @@ fn test_fd_passing() {
             .dynheader
             .interface
             .eq(&Some("io.killing.spark".to_owned()))
+            && signal.dynheader.member.eq(&Some("TestSignal".to_owned()))
         {
-            if signal.dynheader.member.eq(&Some("TestSignal".to_owned())) {
-                break signal;
-            }
+            break signal;
         }
     };
 
@@ -118,12 +115,10 @@ fn test_fd_marshalling() {
     let mut parser = sig.body.parser();
     let _fd1: crate::wire::UnixFd = parser.get().unwrap();
     // get _fd2
-    assert!(match parser.get_param().unwrap() {
-        crate::params::Param::Base(crate::params::Base::UnixFd(_fd)) => {
-            true
-        }
-        _ => false,
-    });
+    assert!(matches!(
+        parser.get_param().unwrap(),
+        crate::params::Param::Base(crate::params::Base::UnixFd(_fd))
+    ));
     let _fd3: crate::wire::UnixFd = parser.get().unwrap();
 
     // Take all fds back to prevent accidental closing of actual FDs
@@ -0,0 +1,176 @@
+use crate::format::{busctl_json, dbus_monitor};
+use crate::message_builder::{MarshalledMessage, MessageBuilder};
+use crate::params::{Base, Container, Param};
+use crate::wire::{ObjectPath, SignatureWrapper, UnixFd};
+use crate::{ByteOrder, Marshal};
+
+use std::collections::HashMap;
+use std::os::unix::io::IntoRawFd;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Compare the output with the snapshot in src/tests/snapshots. Run the tests with RUSTBUS_UPDATE_SNAPSHOTS set to
+/// write the current output as the new snapshots.
+fn check_snapshot(name: &str, output: &str) {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src/tests/snapshots")
+        .join(name);
+    if std::env::var_os("RUSTBUS_UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, output).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(expected, output, "snapshot {} differs", name);
+}
+
+fn check_both_styles(name: &str, msg: &MarshalledMessage) {
+    check_snapshot(
+        &format!("{}.monitor.txt", name),
+        &dbus_monitor(msg, None).unwrap(),
+    );
+    check_snapshot(&format!("{}.json", name), &busctl_json(msg, None).unwrap());
+}
+
+// The same messages bin/create_corpus.rs generates
+fn corpus_message<P1: Marshal, P2: Marshal>(p1: P1, p2: P2) -> MarshalledMessage {
+    let mut msg = MessageBuilder::new()
+        .call("ABCD")
+        .on("/A/B/C")
+        .with_interface("ABCD.ABCD")
+        .at("ABCD.ABCD")
+        .build();
+    msg.dynheader.serial = Some(1);
+    msg.body.push_param2(p1, p2).unwrap();
+    msg
+}
+
+#[test]
+fn test_format_corpus() {
+    let bytes = vec![
+        0u8, 1, 2, 3, 4, 5, 6, 7, 8, 9, 100, 255, 123, 123, 123, 123, 123, 123,
+    ];
+    let mut map = HashMap::new();
+    map.insert("ABCD", (0u8, 100u32));
+    map.insert("ABCDEFG", (1u8, 200u32));
+    map.insert("X", (2u8, 300u32));
+    map.insert("Y", (3u8, 400u32));
+
+    check_both_styles("corpus1", &corpus_message("ABCD", "asdöflasdölgkjsdfökl"));
+    check_both_styles(
+        "corpus2",
+        &corpus_message(vec!["ABCD", "EFGHI", "JKLMNOP"], &bytes),
+    );
+    check_both_styles("corpus3", &corpus_message(vec![&map, &map, &map], &bytes));
+    check_both_styles("corpus4", &corpus_message(&map, &bytes));
+}
+
+fn all_types_message(byteorder: ByteOrder) -> MarshalledMessage {
+    let mut msg = MessageBuilder::with_byteorder(byteorder)
+        .signal("io.killing.spark", "AllTypes", "/io/killing/spark")
+        .build();
+    msg.dynheader.serial = Some(7);
+    msg.dynheader.sender = Some(":1.42".to_owned());
+    msg.body.push_param4(true, 0xFFu8, -16i16, 16u16).unwrap();
+    msg.body.push_param4(-32i32, 32u32, -64i64, 64u64).unwrap();
+    msg.body.push_param(1.5f64).unwrap();
+    msg.body
+        .push_param3(
+            "quote \" and tab \t",
+            ObjectPath::new("/io/killing/spark").unwrap(),
+            SignatureWrapper::new("a{sv}").unwrap(),
+        )
+        .unwrap();
+    msg.body.push_param((-1i32, "in a struct", (2u8,))).unwrap();
+    msg.body.push_param(b"printable bytes".to_vec()).unwrap();
+    msg.body.push_param(Vec::<u8>::new()).unwrap();
+    msg.body.push_param(Vec::<String>::new()).unwrap();
+
+    let mut int_keys = HashMap::new();
+    int_keys.insert(3u32, "three");
+    int_keys.insert(1u32, "one");
+    msg.body.push_param(int_keys).unwrap();
+
+    let variant =
+        Container::make_variant(Param::Container(Container::make_variant(Base::Int32(5))));
+    msg.body.push_old_param(&Param::Container(variant)).unwrap();
+    let mut props = HashMap::new();
+    props.insert(
+        Base::String("list".to_owned()),
+        Param::Container(Container::make_variant(Param::Container(
+            Container::make_array("s", vec![Base::String("a".to_owned())].into_iter()).unwrap(),
+        ))),
+    );
+    props.insert(
+        Base::String("number".to_owned()),
+        Param::Container(Container::make_variant(Base::Uint64(7))),
+    );
+    msg.body
+        .push_old_param(&Param::Container(
+            Container::make_dict_with_sig(
+                crate::signature::Base::String,
+                crate::signature::Type::Container(crate::signature::Container::Variant),
+                props.into_iter(),
+            )
+            .unwrap(),
+        ))
+        .unwrap();
+    msg
+}
+
+#[test]
+fn test_format_all_types() {
+    let little = all_types_message(ByteOrder::LittleEndian);
+    let big = all_types_message(ByteOrder::BigEndian);
+    check_both_styles("all_types", &little);
+
+    // the byte order only shows in the endian field of the json
+    assert_eq!(
+        dbus_monitor(&little, None).unwrap(),
+        dbus_monitor(&big, None).unwrap()
+    );
+    assert_eq!(
+        busctl_json(&little, None)
+            .unwrap()
+            .replace("\"endian\" : \"l\"", "\"endian\" : \"B\""),
+        busctl_json(&big, None).unwrap()
+    );
+}
+
+#[test]
+fn test_format_headers() {
+    let mut call = MessageBuilder::new()
+        .call("Frobnicate")
+        .on("/io/killing/spark")
+        .at("io.killing.spark")
+        .build();
+    call.dynheader.serial = Some(3);
+    call.dynheader.sender = Some(":1.1".to_owned());
+
+    let mut reply = call.dynheader.make_response();
+    reply.dynheader.serial = Some(4);
+    let devnull = std::fs::File::open("/dev/null").unwrap();
+    let devzero = std::fs::File::open("/dev/zero").unwrap();
+    reply
+        .body
+        .push_param2(
+            UnixFd::new(devnull.into_raw_fd()),
+            UnixFd::new(devzero.into_raw_fd()),
+        )
+        .unwrap();
+
+    let mut error = call
+        .dynheader
+        .make_error_response("io.killing.spark.Error.Broken", Some("it broke".to_owned()));
+    error.dynheader.serial = Some(5);
+
+    let received = UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+    let output = [&call, &reply, &error]
+        .iter()
+        .map(|msg| dbus_monitor(msg, Some(received)).unwrap())
+        .collect::<String>();
+    check_snapshot("headers.monitor.txt", &output);
+    let output = [&call, &reply, &error]
+        .iter()
+        .map(|msg| busctl_json(msg, Some(received)).unwrap())
+        .collect::<String>();
+    check_snapshot("headers.json", &output);
+}
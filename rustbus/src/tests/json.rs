@@ -0,0 +1,260 @@
+use crate::message_builder::{MessageBuilder, MessageType};
+use crate::params::json::{
+    message_from_json, message_to_json, param_from_json, param_to_json, Json, JsonError, Number,
+};
+use crate::params::{Base, Container, Dict, DictMap, Param, Variant};
+use crate::signature;
+use crate::wire::UnixFd;
+use crate::ByteOrder;
+
+use std::os::unix::io::IntoRawFd;
+
+fn parse_sig(sig: &str) -> signature::Type {
+    signature::Type::parse_description(sig).unwrap().remove(0)
+}
+
+fn test_fds() -> Vec<UnixFd> {
+    (0..2)
+        .map(|_| UnixFd::new(std::fs::File::open("/dev/null").unwrap().into_raw_fd()))
+        .collect()
+}
+
+/// Convert to JSON, through the text and back
+fn roundtrip(param: &Param, fds: &[UnixFd]) -> Param<'static, 'static> {
+    let json = param_to_json(param, fds);
+    let text = json.to_string();
+    let reparsed = Json::parse(&text).unwrap();
+    // integral doubles are read back as integers, so only the text is the same
+    assert_eq!(reparsed.to_string(), text);
+    param_from_json(&reparsed, &param.sig(), fds).unwrap()
+}
+
+#[test]
+fn test_json_base_roundtrip() {
+    let fds = test_fds();
+    let params: Vec<Param> = vec![
+        Base::Byte(255).into(),
+        Base::Int16(i16::MIN).into(),
+        Base::Uint16(u16::MAX).into(),
+        Base::Int32(i32::MIN).into(),
+        Base::Uint32(u32::MAX).into(),
+        Base::Int64(i64::MIN).into(),
+        Base::Uint64(u64::MAX).into(),
+        Base::Double(0.1f64.to_bits()).into(),
+        Base::Double((-2.0f64).to_bits()).into(),
+        Base::Double(1e300f64.to_bits()).into(),
+        Base::Boolean(true).into(),
+        Base::String("quote \" backslash \\ newline \n tab \t bell \u{7} ünïcödé 🦀".into()).into(),
+        Base::ObjectPath("/io/killing/spark".into()).into(),
+        Base::Signature("a{sv}(iu)".into()).into(),
+        Base::UnixFd(fds[0].clone()).into(),
+        Base::UnixFd(fds[1].clone()).into(),
+    ];
+    for param in &params {
+        assert_eq!(&roundtrip(param, &fds), param);
+    }
+
+    // fds are written as their index
+    assert_eq!(
+        param_to_json(&params[15], &fds),
+        Json::Number(Number::Unsigned(1))
+    );
+    // doubles that JSON can not represent
+    let nan = param_to_json(&Base::Double(f64::INFINITY.to_bits()).into(), &[]);
+    assert_eq!(nan, Json::Null);
+    match param_from_json(&nan, &parse_sig("d"), &[]).unwrap() {
+        Param::Base(Base::Double(bits)) => assert!(f64::from_bits(bits).is_nan()),
+        other => panic!("Expected a double, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_json_container_roundtrip() {
+    let fds = test_fds();
+    let mut by_fd = DictMap::new();
+    by_fd.insert(
+        Base::UnixFd(fds[1].clone()),
+        Param::from(Base::Boolean(false)),
+    );
+    let mut by_number = DictMap::new();
+    by_number.insert(
+        Base::Int32(-7),
+        Param::from(Base::String("minus seven".into())),
+    );
+    by_number.insert(Base::Int32(12), Param::from(Base::String("twelve".into())));
+    let mut by_path = DictMap::new();
+    by_path.insert(
+        Base::ObjectPath("/a".into()),
+        Param::Container(Container::Variant(Box::new(Variant {
+            sig: parse_sig("(ag)"),
+            value: Param::Container(Container::Struct(vec![Param::Container(
+                Container::make_array(
+                    "g",
+                    vec![Param::from(Base::Signature("v".into()))].into_iter(),
+                )
+                .unwrap(),
+            )])),
+        }))),
+    );
+
+    let params = vec![
+        Param::Container(Container::Dict(Dict {
+            key_sig: signature::Base::UnixFd,
+            value_sig: parse_sig("b"),
+            map: by_fd,
+        })),
+        Param::Container(Container::Dict(Dict {
+            key_sig: signature::Base::Int32,
+            value_sig: parse_sig("s"),
+            map: by_number,
+        })),
+        Param::Container(Container::Dict(Dict {
+            key_sig: signature::Base::ObjectPath,
+            value_sig: parse_sig("v"),
+            map: by_path,
+        })),
+        Param::Container(Container::make_array("s", Vec::<Param>::new().into_iter()).unwrap()),
+    ];
+    for param in &params {
+        assert_eq!(&roundtrip(param, &fds), param);
+    }
+
+    assert_eq!(
+        param_to_json(&params[1], &fds).to_string(),
+        r#"{"-7":"minus seven","12":"twelve"}"#
+    );
+    assert_eq!(
+        param_to_json(&params[2], &fds).to_string(),
+        r#"{"/a":{"type":"(ag)","data":[["v"]]}}"#
+    );
+}
+
+#[test]
+fn test_json_message_roundtrip() {
+    let fds = test_fds();
+    let mut msg = MessageBuilder::with_byteorder(ByteOrder::BigEndian)
+        .call("Frobnicate")
+        .on("/io/killing/spark")
+        .with_interface("io.killing.spark")
+        .at("io.killing.spark")
+        .build();
+    msg.dynheader.serial = Some(17);
+    msg.flags = 1;
+    msg.body.push_param("text").unwrap();
+    msg.body.push_param(&fds[1]).unwrap();
+    msg.body.push_variant(42u64).unwrap();
+
+    let json = message_to_json(&msg).unwrap();
+    assert_eq!(
+        json.to_string(),
+        "{\"type\":\"method_call\",\"endian\":\"B\",\"flags\":1,\"version\":1,\"cookie\":17,\
+         \"destination\":\"io.killing.spark\",\"path\":\"/io/killing/spark\",\
+         \"interface\":\"io.killing.spark\",\"member\":\"Frobnicate\",\
+         \"payload\":{\"type\":\"shv\",\"data\":[\"text\",0,{\"type\":\"t\",\"data\":42}]}}"
+    );
+
+    let rebuilt =
+        message_from_json(&Json::parse(&json.to_string()).unwrap(), &msg.body.raw_fds).unwrap();
+    assert_eq!(rebuilt.typ, MessageType::Call);
+    assert_eq!(rebuilt.flags, 1);
+    assert_eq!(rebuilt.body.byteorder, ByteOrder::BigEndian);
+    assert_eq!(rebuilt.dynheader.serial, Some(17));
+    assert_eq!(rebuilt.dynheader.member.as_deref(), Some("Frobnicate"));
+    assert_eq!(rebuilt.get_sig(), "shv");
+    assert_eq!(rebuilt.get_buf(), msg.get_buf());
+    assert_eq!(rebuilt.body.raw_fds.len(), 1);
+    assert_eq!(message_to_json(&rebuilt).unwrap(), json);
+}
+
+#[test]
+fn test_json_errors() {
+    assert!(matches!(
+        Json::parse("[1, 2"),
+        Err(JsonError::Parse { offset: 5, .. })
+    ));
+    assert!(matches!(Json::parse("01"), Err(JsonError::Parse { .. })));
+    assert!(matches!(
+        Json::parse(r#""\u+041""#),
+        Err(JsonError::Parse { .. })
+    ));
+    assert_eq!(
+        Json::parse(r#""\u0041\u00e9""#).unwrap(),
+        Json::String("Aé".into())
+    );
+    assert!(matches!(Json::parse("{} {}"), Err(JsonError::Parse { .. })));
+    assert!(matches!(
+        Json::parse(&"[".repeat(1000)),
+        Err(JsonError::Parse { .. })
+    ));
+    assert_eq!(
+        Json::parse(r#"["🦀\/", -0.5e1, 18446744073709551616]"#).unwrap(),
+        Json::Array(vec![
+            Json::String("🦀/".into()),
+            Json::Number(Number::Float(-5.0)),
+            Json::Number(Number::Float(18446744073709551616.0)),
+        ])
+    );
+
+    let convert =
+        |json: &str, sig: &str| param_from_json(&Json::parse(json).unwrap(), &parse_sig(sig), &[]);
+    assert!(matches!(
+        convert("256", "y"),
+        Err(JsonError::OutOfRange { .. })
+    ));
+    assert!(matches!(
+        convert("-1", "u"),
+        Err(JsonError::OutOfRange { .. })
+    ));
+    assert!(matches!(
+        convert("1.5", "i"),
+        Err(JsonError::OutOfRange { .. })
+    ));
+    assert!(matches!(
+        convert("\"5\"", "i"),
+        Err(JsonError::WrongType {
+            found: "a string",
+            ..
+        })
+    ));
+    assert!(matches!(
+        convert("[1]", "(ii)"),
+        Err(JsonError::WrongLength {
+            expected: 2,
+            found: 1,
+            ..
+        })
+    ));
+    assert!(matches!(
+        convert("\"no/path\"", "o"),
+        Err(JsonError::Validation(_))
+    ));
+    assert!(matches!(
+        convert("\"a{\"", "g"),
+        Err(JsonError::Validation(_))
+    ));
+    assert!(matches!(convert("0", "h"), Err(JsonError::BadFdIndex(0))));
+    assert!(matches!(
+        convert("{\"x\": true}", "a{ib}"),
+        Err(JsonError::WrongType { .. })
+    ));
+    assert!(matches!(
+        convert("{\"data\": 1}", "v"),
+        Err(JsonError::MissingMember("type"))
+    ));
+    assert!(matches!(
+        convert("{\"type\": \"ii\", \"data\": 1}", "v"),
+        Err(JsonError::Validation(_))
+    ));
+
+    assert!(matches!(
+        message_from_json(&Json::parse(r#"{"type": "signal"}"#).unwrap(), &[]),
+        Err(JsonError::MissingMember("payload"))
+    ));
+    assert!(matches!(
+        message_from_json(
+            &Json::parse(r#"{"type": "shout", "payload": {"type": "", "data": []}}"#).unwrap(),
+            &[]
+        ),
+        Err(JsonError::InvalidMember { member: "type", .. })
+    ));
+}
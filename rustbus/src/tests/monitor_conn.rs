@@ -0,0 +1,56 @@
+use crate::connection::ll_conn::DuplexConn;
+use crate::connection::monitor_conn::MonitorConn;
+use crate::connection::{get_system_bus_path, Timeout};
+use crate::message_builder::{MessageBuilder, MessageType};
+use crate::params::{Base, Param};
+use crate::MatchRule;
+
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn test_monitor_conn() {
+    let rules = [MatchRule::new()
+        .message_type(MessageType::Signal)
+        .interface("io.killing.spark.MonitorTest")];
+    let before = SystemTime::now();
+    let mut monitor = MonitorConn::system_conn(&rules, Timeout::Infinite).unwrap();
+    assert!(monitor.unique_name().starts_with(':'));
+
+    let mut conn = DuplexConn::connect_to_bus(get_system_bus_path().unwrap(), true).unwrap();
+    let sender = conn.send_hello(Timeout::Infinite).unwrap();
+    for (member, arg) in [("Ignored", 0u32), ("Ping", 42u32)] {
+        let mut sig = MessageBuilder::new()
+            .signal("io.killing.spark.MonitorTest", member, "/io/killing/spark")
+            .build();
+        sig.body.push_param2(arg, "hello").unwrap();
+        conn.send.send_message_write_all(&sig).unwrap();
+    }
+    // not matched by the rule
+    let sig = MessageBuilder::new()
+        .signal("io.killing.spark.Other", "Ping", "/io/killing/spark")
+        .build();
+    conn.send.send_message_write_all(&sig).unwrap();
+
+    let timeout = Timeout::Duration(Duration::from_secs(5));
+    let first = monitor.next_marshalled(timeout).unwrap();
+    assert!(first.0 >= before);
+    assert_eq!(first.1.dynheader.member.as_deref(), Some("Ignored"));
+    assert_eq!(first.1.dynheader.sender.as_deref(), Some(sender.as_str()));
+
+    let observed = monitor.next_message(timeout).unwrap();
+    assert!(observed.received >= first.0);
+    assert_eq!(observed.msg.typ, MessageType::Signal);
+    assert_eq!(observed.msg.dynheader.member.as_deref(), Some("Ping"));
+    assert_eq!(
+        observed.msg.params,
+        vec![
+            Param::Base(Base::Uint32(42)),
+            Param::Base(Base::String("hello".to_owned()))
+        ]
+    );
+
+    assert!(matches!(
+        monitor.next_message(Timeout::Duration(Duration::from_millis(100))),
+        Err(crate::connection::Error::TimedOut)
+    ));
+}
@@ -0,0 +1,128 @@
+use super::fake_bus;
+use crate::connection::name_owner::{
+    NameEvent, NameOwner, NameState, ReleaseNameReply, RequestNameReply,
+};
+use crate::connection::rpc_conn::CallError;
+use crate::connection::{Error, Timeout};
+use crate::message_builder::HeaderFlags;
+use crate::standard_messages::{
+    DBUS_NAME_FLAG_ALLOW_REPLACEMENT, DBUS_NAME_FLAG_DO_NOT_QUEUE,
+    DBUS_RELEASE_NAME_REPLY_RELEASED, DBUS_REQUEST_NAME_REPLY_EXISTS,
+    DBUS_REQUEST_NAME_REPLY_IN_QUEUE, DBUS_REQUEST_NAME_REPLY_PRIMARY_OWNER,
+};
+
+const NAME: &str = "io.killing.spark";
+
+#[test]
+fn test_name_owner_events() {
+    let (mut bus, mut con) = fake_bus::connect();
+    let script = std::thread::spawn(move || {
+        let call = bus.expect_call("AddMatch");
+        bus.reply_empty(&call);
+        let call = bus.expect_call("RequestName");
+        assert_eq!(
+            call.body.parser().get2::<&str, u32>().unwrap(),
+            (NAME, DBUS_NAME_FLAG_ALLOW_REPLACEMENT)
+        );
+        // like the real bus the signal comes before the reply
+        bus.signal("NameAcquired", &[NAME]);
+        bus.reply(&call, DBUS_REQUEST_NAME_REPLY_PRIMARY_OWNER);
+
+        // somebody else replaces us and releases the name later
+        bus.signal("NameAcquired", &["some.other.name"]);
+        bus.signal("NameLost", &[NAME]);
+        bus.signal("NameAcquired", &[NAME]);
+
+        let call = bus.expect_call("ReleaseName");
+        assert!(call.has_flag(HeaderFlags::NoReplyExpected));
+        assert_eq!(call.body.parser().get::<&str>().unwrap(), NAME);
+    });
+
+    let mut owner = NameOwner::request(
+        &mut con,
+        NAME,
+        DBUS_NAME_FLAG_ALLOW_REPLACEMENT,
+        Timeout::Infinite,
+    )
+    .unwrap();
+    assert_eq!(owner.name(), NAME);
+    assert_eq!(owner.reply(), RequestNameReply::PrimaryOwner);
+    assert!(owner.is_owner());
+
+    // the NameAcquired before the reply changes nothing
+    assert_eq!(
+        owner.wait_event(Timeout::Infinite).unwrap(),
+        NameEvent::Lost
+    );
+    assert_eq!(owner.state(), NameState::Queued);
+    assert_eq!(
+        owner.wait_event(Timeout::Infinite).unwrap(),
+        NameEvent::Acquired
+    );
+    assert_eq!(owner.state(), NameState::Owned);
+    assert_eq!(owner.try_get_event().unwrap(), None);
+
+    drop(owner);
+    script.join().unwrap();
+}
+
+#[test]
+fn test_name_owner_taken() {
+    let (mut bus, mut con) = fake_bus::connect();
+    let script = std::thread::spawn(move || {
+        let call = bus.expect_call("AddMatch");
+        bus.reply_empty(&call);
+        let call = bus.expect_call("RequestName");
+        bus.reply(&call, DBUS_REQUEST_NAME_REPLY_EXISTS);
+        let call = bus.expect_call("RemoveMatch");
+        bus.reply_empty(&call);
+    });
+
+    let err = NameOwner::request(
+        &mut con,
+        NAME,
+        DBUS_NAME_FLAG_DO_NOT_QUEUE,
+        Timeout::Infinite,
+    )
+    .err()
+    .unwrap();
+    assert!(matches!(err, CallError::Connection(Error::NameTaken)));
+    script.join().unwrap();
+}
+
+#[test]
+fn test_name_owner_queued() {
+    let (mut bus, mut con) = fake_bus::connect();
+    let script = std::thread::spawn(move || {
+        let call = bus.expect_call("AddMatch");
+        bus.reply_empty(&call);
+        let call = bus.expect_call("RequestName");
+        bus.reply(&call, DBUS_REQUEST_NAME_REPLY_IN_QUEUE);
+        let call = bus.expect_call("RemoveMatch");
+        bus.reply_empty(&call);
+        let call = bus.expect_call("ReleaseName");
+        bus.reply(&call, DBUS_RELEASE_NAME_REPLY_RELEASED);
+    });
+
+    let owner = NameOwner::request(&mut con, NAME, 0, Timeout::Infinite).unwrap();
+    assert_eq!(owner.reply(), RequestNameReply::InQueue);
+    assert_eq!(owner.state(), NameState::Queued);
+    assert!(!owner.is_owner());
+    assert_eq!(
+        owner.release(Timeout::Infinite).unwrap(),
+        ReleaseNameReply::Released
+    );
+    script.join().unwrap();
+
+    // errors of the bus are passed on
+    let (mut bus, mut con) = fake_bus::connect();
+    let script = std::thread::spawn(move || {
+        let call = bus.expect_call("AddMatch");
+        bus.reply_error(&call, "org.freedesktop.DBus.Error.AccessDenied");
+    });
+    assert!(matches!(
+        NameOwner::request(&mut con, NAME, 0, Timeout::Infinite),
+        Err(CallError::Connection(Error::MatchRuleRejected(_)))
+    ));
+    script.join().unwrap();
+}
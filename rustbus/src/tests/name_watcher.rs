@@ -0,0 +1,137 @@
+use super::fake_bus;
+use crate::connection::name_watcher::{NameOwnerChange, NameWatcher};
+use crate::connection::Timeout;
+use std::sync::{Arc, Mutex};
+
+const NAME: &str = "io.killing.spark";
+const OTHER_NAME: &str = "io.killing.other";
+
+fn change(name: &str, old_owner: Option<&str>, new_owner: Option<&str>) -> NameOwnerChange {
+    NameOwnerChange {
+        name: name.to_owned(),
+        old_owner: old_owner.map(str::to_owned),
+        new_owner: new_owner.map(str::to_owned),
+    }
+}
+
+#[test]
+fn test_name_watcher_changes() {
+    let (mut bus, mut con) = fake_bus::connect();
+    let script = std::thread::spawn(move || {
+        let call = bus.expect_call("AddMatch");
+        bus.reply_empty(&call);
+        let call = bus.expect_call("GetNameOwner");
+        assert_eq!(call.body.parser().get::<&str>().unwrap(), NAME);
+        // a change from before the reply is already contained in it
+        bus.signal("NameOwnerChanged", &[NAME, "", ":1.1"]);
+        bus.reply(&call, ":1.1");
+
+        let call = bus.expect_call("AddMatch");
+        bus.reply_empty(&call);
+        let call = bus.expect_call("GetNameOwner");
+        assert_eq!(call.body.parser().get::<&str>().unwrap(), OTHER_NAME);
+        bus.reply_error(&call, "org.freedesktop.DBus.Error.NameHasNoOwner");
+
+        bus.signal("NameOwnerChanged", &[NAME, ":1.1", ""]);
+        bus.signal("NameOwnerChanged", &[OTHER_NAME, "", ":1.3"]);
+        bus.signal("NameOwnerChanged", &[NAME, "", ":1.2"]);
+        bus.signal("NameOwnerChanged", &[NAME, ":1.2", ":1.4"]);
+
+        let call = bus.expect_call("RemoveMatch");
+        bus.reply_empty(&call);
+        bus.signal("NameOwnerChanged", &[NAME, ":1.4", ""]);
+        bus.signal("NameOwnerChanged", &[OTHER_NAME, ":1.3", ""]);
+    });
+
+    let watcher = NameWatcher::new();
+    assert_eq!(
+        watcher.watch(&mut con, NAME, Timeout::Infinite).unwrap(),
+        Some(":1.1".to_owned())
+    );
+    assert_eq!(
+        watcher
+            .watch(&mut con, OTHER_NAME, Timeout::Infinite)
+            .unwrap(),
+        None
+    );
+    // watching a name twice does not subscribe again
+    assert_eq!(
+        watcher.watch(&mut con, NAME, Timeout::Infinite).unwrap(),
+        Some(":1.1".to_owned())
+    );
+    let mut names = watcher.names();
+    names.sort();
+    assert_eq!(names, vec![OTHER_NAME.to_owned(), NAME.to_owned()]);
+
+    // only the owner found initially is reported for the watch, not the signal before the reply
+    let first = watcher.try_get_change().unwrap();
+    assert_eq!(first, change(NAME, None, Some(":1.1")));
+    assert!(first.appeared());
+    assert_eq!(watcher.try_get_change(), None);
+
+    let expected = [
+        change(NAME, Some(":1.1"), None),
+        change(OTHER_NAME, None, Some(":1.3")),
+        change(NAME, None, Some(":1.2")),
+        change(NAME, Some(":1.2"), Some(":1.4")),
+    ];
+    for expected in expected {
+        assert_eq!(
+            watcher.wait_change(&mut con, Timeout::Infinite).unwrap(),
+            expected
+        );
+    }
+    assert_eq!(watcher.owner(NAME), Some(":1.4".to_owned()));
+    assert_eq!(watcher.owner(OTHER_NAME), Some(":1.3".to_owned()));
+
+    watcher.unwatch(&mut con, OTHER_NAME).unwrap();
+    assert_eq!(watcher.owner(OTHER_NAME), None);
+    let vanished = watcher.wait_change(&mut con, Timeout::Infinite).unwrap();
+    assert_eq!(vanished, change(NAME, Some(":1.4"), None));
+    assert!(vanished.vanished());
+    assert_eq!(watcher.owner(NAME), None);
+
+    script.join().unwrap();
+    // the signal for the name that is not watched anymore is dropped
+    let _ = con.try_refill_once(Timeout::Nonblock);
+    assert_eq!(watcher.try_get_change(), None);
+}
+
+#[test]
+fn test_name_watcher_callback() {
+    let (mut bus, mut con) = fake_bus::connect();
+    let script = std::thread::spawn(move || {
+        let call = bus.expect_call("AddMatch");
+        bus.reply_empty(&call);
+        let call = bus.expect_call("GetNameOwner");
+        bus.reply_error(&call, "org.freedesktop.DBus.Error.NameHasNoOwner");
+        bus.signal("NameOwnerChanged", &[NAME, "", ":1.1"]);
+        // duplicates do not change anything
+        bus.signal("NameOwnerChanged", &[NAME, "", ":1.1"]);
+        bus.signal("NameOwnerChanged", &[NAME, ":1.1", ""]);
+    });
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen_cb = seen.clone();
+    let watcher = NameWatcher::with_callback(Box::new(move |change| {
+        seen_cb.lock().unwrap().push(change.clone());
+    }));
+    assert_eq!(
+        watcher.watch(&mut con, NAME, Timeout::Infinite).unwrap(),
+        None
+    );
+    script.join().unwrap();
+    while seen.lock().unwrap().len() < 2 {
+        con.refill_once(Timeout::Infinite).unwrap();
+    }
+    let _ = con.try_refill_once(Timeout::Nonblock);
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            change(NAME, None, Some(":1.1")),
+            change(NAME, Some(":1.1"), None)
+        ]
+    );
+    // the changes are not queued when a callback is used
+    assert_eq!(watcher.try_get_change(), None);
+}
@@ -0,0 +1,99 @@
+use crate::connection::dispatch_conn::{DispatchConn, HandleEnvironment, Matches};
+use crate::connection::ll_conn::DuplexConn;
+use crate::connection::rpc_conn::RpcConn;
+use crate::connection::{Error, Timeout};
+use crate::message_builder::{MarshalledMessage, MessageBuilder, MessageType};
+use crate::peer::handle_peer_message;
+
+use std::time::Duration;
+
+fn ping(no_reply: bool) -> MarshalledMessage {
+    let call = MessageBuilder::new()
+        .call("Ping")
+        .with_interface("org.freedesktop.DBus.Peer")
+        .on("/io/killing/spark");
+    if no_reply {
+        call.no_reply_expected().build()
+    } else {
+        call.build()
+    }
+}
+
+/// The next message has to be the reply to the serial, and nothing may follow it
+fn assert_only_reply_to(conn: &mut DuplexConn, serial: u32) {
+    let reply = conn.recv.get_next_message(Timeout::Infinite).unwrap();
+    assert_eq!(reply.typ, MessageType::Reply);
+    assert_eq!(reply.dynheader.response_serial, Some(serial));
+    assert!(matches!(
+        conn.recv
+            .get_next_message(Timeout::Duration(Duration::from_millis(50))),
+        Err(Error::TimedOut)
+    ));
+}
+
+#[test]
+fn test_dispatch_no_reply_expected() {
+    type Env = HandleEnvironment<(), ()>;
+
+    let (conn, mut peer) = DuplexConn::pair();
+    std::thread::spawn(move || {
+        let mut dpcon = DispatchConn::new(
+            conn,
+            (),
+            Box::new(|_: &mut (), _: Matches, _: &MarshalledMessage, _: &mut Env| Ok(None)),
+        );
+        let _ = dpcon.run();
+    });
+
+    peer.send.send_message_write_all(&ping(true)).unwrap();
+    let serial = peer.send.send_message_write_all(&ping(false)).unwrap();
+    // the calls are handled in order, so the first one would have been answered first
+    assert_only_reply_to(&mut peer, serial);
+}
+
+#[test]
+fn test_peer_no_reply_expected() {
+    let (mut conn, mut peer) = DuplexConn::pair();
+
+    peer.send.send_message_write_all(&ping(true)).unwrap();
+    let serial = peer.send.send_message_write_all(&ping(false)).unwrap();
+    for _ in 0..2 {
+        let msg = conn.recv.get_next_message(Timeout::Infinite).unwrap();
+        assert!(handle_peer_message(&msg, &mut conn).unwrap());
+    }
+    assert_only_reply_to(&mut peer, serial);
+}
+
+#[test]
+fn test_rpc_drops_unexpected_replies() {
+    let (conn, mut peer) = DuplexConn::pair();
+    let mut con = RpcConn::new(conn);
+
+    let no_reply = con
+        .send_message(&mut ping(true))
+        .unwrap()
+        .write_all()
+        .unwrap();
+    let serial = con
+        .send_message(&mut ping(false))
+        .unwrap()
+        .write_all()
+        .unwrap();
+
+    // a misbehaving peer that answers both calls
+    for _ in 0..2 {
+        let call = peer.recv.get_next_message(Timeout::Infinite).unwrap();
+        peer.send
+            .send_message_write_all(&call.dynheader.make_response())
+            .unwrap();
+    }
+
+    let reply = con.wait_response(serial, Timeout::Infinite).unwrap();
+    assert_eq!(reply.dynheader.response_serial, Some(serial));
+    // both replies were read, but the one nobody asked for was dropped
+    assert!(con.try_get_response(no_reply).is_none());
+    assert!(matches!(
+        con.wait_response(no_reply, Timeout::Duration(Duration::from_millis(50))),
+        Err(Error::TimedOut)
+    ));
+}
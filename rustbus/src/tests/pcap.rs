@@ -0,0 +1,164 @@
+use crate::message_builder::{MarshalledMessage, MessageBuilder};
+use crate::wire::pcap::{PcapError, PcapReader, PcapWriter, LINKTYPE_DBUS};
+use crate::ByteOrder;
+
+use std::convert::TryInto;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn messages() -> Vec<MarshalledMessage> {
+    let mut call = MessageBuilder::new()
+        .call("Frobnicate")
+        .with_interface("io.killing.spark")
+        .on("/io/killing/spark")
+        .at("io.killing.spark")
+        .build();
+    call.dynheader.serial = Some(1);
+    call.body.push_param2("hello", vec![1u32, 2, 3]).unwrap();
+
+    let mut reply = call.dynheader.make_response();
+    reply.dynheader.serial = Some(2);
+    reply.body.push_param((true, 1.5f64)).unwrap();
+
+    let mut signal = MessageBuilder::with_byteorder(ByteOrder::BigEndian)
+        .signal("io.killing.spark", "Ping", "/io/killing/spark")
+        .build();
+    signal.dynheader.serial = Some(3);
+    signal.dynheader.sender = Some(":1.42".to_owned());
+    signal.body.push_param(0xDEADBEEFu64).unwrap();
+    vec![call, reply, signal]
+}
+
+fn assert_same(written: &MarshalledMessage, read: &MarshalledMessage) {
+    assert_eq!(written.typ, read.typ);
+    assert_eq!(written.flags, read.flags);
+    // the signature header field is only filled in when the message is marshalled
+    let mut expected = written.dynheader.clone();
+    expected.signature = Some(written.get_sig().to_owned()).filter(|sig| !sig.is_empty());
+    assert_eq!(format!("{:?}", expected), format!("{:?}", read.dynheader));
+    assert_eq!(written.get_sig(), read.get_sig());
+    assert_eq!(written.get_buf(), read.get_buf());
+}
+
+#[test]
+fn test_pcap_roundtrip() {
+    let messages = messages();
+    let time = UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+    let mut writer = PcapWriter::new(Vec::new()).unwrap();
+    for (idx, msg) in messages.iter().enumerate() {
+        writer
+            .write_message(msg, time + Duration::from_secs(idx as u64))
+            .unwrap();
+    }
+    let capture = writer.into_inner();
+    assert_eq!(
+        &capture[20..24],
+        &LINKTYPE_DBUS.to_ne_bytes(),
+        "the link type is DBUS"
+    );
+
+    let reader = PcapReader::new(capture.as_slice()).unwrap();
+    let read: Vec<_> = reader.map(Result::unwrap).collect();
+    assert_eq!(read.len(), messages.len());
+    for (idx, (written, (read_time, read))) in messages.iter().zip(read.iter()).enumerate() {
+        assert_eq!(*read_time, time + Duration::from_secs(idx as u64));
+        assert_same(written, read);
+    }
+    let params = read[2].1.body.parser().get::<u64>().unwrap();
+    assert_eq!(params, 0xDEADBEEF);
+
+    // messages need a serial to be marshalled
+    let mut writer = PcapWriter::new(Vec::new()).unwrap();
+    let mut no_serial = messages[0].clone();
+    no_serial.dynheader.serial = None;
+    assert!(matches!(
+        writer.write_message(&no_serial, time),
+        Err(PcapError::MissingSerial)
+    ));
+    // nothing but the file header was written
+    assert_eq!(writer.into_inner().len(), 24);
+}
+
+#[test]
+fn test_pcap_foreign_byteorder() {
+    let msg = &messages()[0];
+    let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let mut writer = PcapWriter::new(Vec::new()).unwrap();
+    writer.write_message(msg, time).unwrap();
+    let mut capture = writer.into_inner();
+
+    // rewrite all header fields as if the capture was written on a machine with the other byte order
+    let swap_u32 = |buf: &mut [u8], offsets: &[usize]| {
+        for &off in offsets {
+            buf[off..off + 4].reverse();
+        }
+    };
+    swap_u32(&mut capture, &[0, 8, 12, 16, 20]);
+    capture[4..6].reverse();
+    capture[6..8].reverse();
+    swap_u32(&mut capture, &[24, 28, 32, 36]);
+
+    let mut reader = PcapReader::new(capture.as_slice()).unwrap();
+    let (read_time, read) = reader.next_message().unwrap().unwrap();
+    assert_eq!(read_time, time);
+    assert_same(msg, &read);
+    assert!(reader.next_message().unwrap().is_none());
+}
+
+#[test]
+fn test_pcap_errors() {
+    let msg = &messages()[0];
+    let mut writer = PcapWriter::new(Vec::new()).unwrap();
+    writer.write_message(msg, UNIX_EPOCH).unwrap();
+    let capture = writer.into_inner();
+
+    let mut bad_magic = capture.clone();
+    bad_magic[0..4].copy_from_slice(&[0, 0, 0, 0]);
+    assert!(matches!(
+        PcapReader::new(bad_magic.as_slice()),
+        Err(PcapError::InvalidMagic(0))
+    ));
+
+    let mut ethernet = capture.clone();
+    ethernet[20..24].copy_from_slice(&1u32.to_ne_bytes());
+    assert!(matches!(
+        PcapReader::new(ethernet.as_slice()),
+        Err(PcapError::WrongLinkType(1))
+    ));
+
+    let cut = &capture[..capture.len() - 1];
+    let mut reader = PcapReader::new(cut).unwrap();
+    assert!(matches!(
+        reader.next_message(),
+        Err(PcapError::UnexpectedEof)
+    ));
+
+    let mut truncated = capture.clone();
+    let orig_len = u32::from_ne_bytes(truncated[36..40].try_into().unwrap());
+    truncated[36..40].copy_from_slice(&(orig_len + 10).to_ne_bytes());
+    let mut reader = PcapReader::new(truncated.as_slice()).unwrap();
+    assert!(matches!(
+        reader.next_message(),
+        Err(PcapError::TruncatedPacket(_, _))
+    ));
+
+    // a length this big is rejected before the packet is read
+    let mut huge = capture.clone();
+    huge[32..40].copy_from_slice(&[0xff; 8]);
+    let mut reader = PcapReader::new(huge.as_slice()).unwrap();
+    assert!(matches!(
+        reader.next_message(),
+        Err(PcapError::PacketTooBig(u32::MAX))
+    ));
+
+    // a huge snaplen does not allow huge packets
+    let mut huge_snaplen = capture.clone();
+    huge_snaplen[16..20].copy_from_slice(&0xffff_fffeu32.to_ne_bytes());
+    huge_snaplen[32..36].copy_from_slice(&0xffff_fffdu32.to_ne_bytes());
+    huge_snaplen[36..40].copy_from_slice(&0xffff_fffdu32.to_ne_bytes());
+    let mut reader = PcapReader::new(huge_snaplen.as_slice()).unwrap();
+    assert_eq!(reader.snaplen(), 0xffff_fffe);
+    assert!(matches!(
+        reader.next_message(),
+        Err(PcapError::PacketTooBig(0xffff_fffd))
+    ));
+}
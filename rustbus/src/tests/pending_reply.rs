@@ -0,0 +1,153 @@
+use crate::connection::dispatch_conn::{DispatchConn, HandleEnvironment, Matches, PendingReply};
+use crate::connection::ll_conn::DuplexConn;
+use crate::connection::rpc_conn::RpcConn;
+use crate::connection::Timeout;
+use crate::message_builder::{MarshalledMessage, MessageBuilder, MessageType};
+
+type Env = HandleEnvironment<Vec<PendingReply>, ()>;
+
+fn call(con: &mut RpcConn, member: &str) -> u32 {
+    let mut msg = MessageBuilder::new()
+        .call(member)
+        .on("/io/killing/spark")
+        .with_interface("io.killing.spark")
+        .at("io.killing.spark.Server")
+        .build();
+    con.send_message(&mut msg).unwrap().write_all().unwrap()
+}
+
+#[test]
+fn test_pending_reply() {
+    let (conn, peer) = DuplexConn::pair();
+    std::thread::spawn(move || {
+        let mut dpcon = DispatchConn::new(
+            conn,
+            Vec::new(),
+            Box::new(
+                |_: &mut Vec<PendingReply>, _: Matches, _: &MarshalledMessage, _: &mut Env| {
+                    Ok(None)
+                },
+            ),
+        );
+        dpcon.add_method_handler(
+            "/io/killing/spark",
+            "io.killing.spark",
+            "Later",
+            None,
+            Box::new(
+                |_: &mut Vec<PendingReply>, _: Matches, msg, env: &mut Env| {
+                    let pending = env.defer_reply(msg);
+                    std::thread::spawn(move || {
+                        std::thread::sleep(std::time::Duration::from_millis(50));
+                        let mut resp = pending.make_response();
+                        resp.body.push_param("done").unwrap();
+                        pending.complete(resp).unwrap();
+                    });
+                    Ok(None)
+                },
+            ),
+        );
+        dpcon.add_method_handler(
+            "/io/killing/spark",
+            "io.killing.spark",
+            "Store",
+            None,
+            Box::new(
+                |stored: &mut Vec<PendingReply>, _: Matches, msg, env: &mut Env| {
+                    let mut pending = env.defer_reply(msg);
+                    pending.set_drop_error("io.killing.spark.Error.Dropped", None);
+                    stored.push(pending);
+                    Ok(None)
+                },
+            ),
+        );
+        dpcon.add_method_handler(
+            "/io/killing/spark",
+            "io.killing.spark",
+            "Drop",
+            None,
+            Box::new(
+                |stored: &mut Vec<PendingReply>, _: Matches, msg, env: &mut Env| {
+                    stored.clear();
+                    drop(env.defer_reply(msg));
+                    Ok(None)
+                },
+            ),
+        );
+        let _ = dpcon.run();
+    });
+
+    let mut con = RpcConn::new(peer);
+
+    let later = call(&mut con, "Later");
+    let resp = con.wait_response(later, Timeout::Infinite).unwrap();
+    assert_eq!(resp.typ, MessageType::Reply);
+    assert_eq!(resp.body.parser().get::<&str>().unwrap(), "done");
+
+    // the stored reply is only answered when it gets dropped by the next call
+    let stored = call(&mut con, "Store");
+    let dropped = call(&mut con, "Drop");
+    let resp = con.wait_response(stored, Timeout::Infinite).unwrap();
+    assert_eq!(resp.typ, MessageType::Error);
+    assert_eq!(
+        resp.dynheader.error_name.as_deref(),
+        Some("io.killing.spark.Error.Dropped")
+    );
+    let resp = con.wait_response(dropped, Timeout::Infinite).unwrap();
+    assert_eq!(resp.typ, MessageType::Error);
+    assert_eq!(
+        resp.dynheader.error_name.as_deref(),
+        Some("org.freedesktop.DBus.Error.NoReply")
+    );
+
+    // exactly one reply per call
+    assert!(con
+        .wait_response(
+            later,
+            Timeout::Duration(std::time::Duration::from_millis(100))
+        )
+        .is_err());
+}
+
+#[test]
+fn test_pending_reply_poisoned() {
+    use crate::connection::dispatch_conn::PENDING_REPLY_DROP_ERROR;
+    use crate::connection::Error;
+    use std::sync::{Arc, Mutex};
+
+    let (conn, mut peer) = DuplexConn::pair();
+    let send = Arc::new(Mutex::new(conn.send));
+    let poison = send.clone();
+    std::thread::spawn(move || {
+        let _conn = poison.lock().unwrap();
+        panic!("poisoning the connection on purpose");
+    })
+    .join()
+    .unwrap_err();
+
+    let mut msg = MessageBuilder::new()
+        .call("Later")
+        .on("/io/killing/spark")
+        .build();
+    msg.dynheader.serial = Some(1);
+
+    let pending = PendingReply::new(send.clone(), &msg);
+    let reply = pending.make_response();
+    assert!(matches!(pending.complete(reply), Err(Error::Poisoned)));
+
+    // dropped while unwinding, which must not panic a second time
+    let pending = PendingReply::new(send, &msg);
+    std::thread::spawn(move || {
+        let _pending = pending;
+        panic!("dropping the pending reply on purpose");
+    })
+    .join()
+    .unwrap_err();
+
+    let resp = peer.recv.get_next_message(Timeout::Infinite).unwrap();
+    assert_eq!(resp.typ, MessageType::Error);
+    assert_eq!(
+        resp.dynheader.error_name.as_deref(),
+        Some(PENDING_REPLY_DROP_ERROR)
+    );
+}
@@ -0,0 +1,107 @@
+use crate::connection::rpc_conn::{CallError, RpcConn};
+use crate::connection::Timeout;
+use crate::standard_messages::WellKnownError;
+
+#[test]
+fn test_rpc_call() {
+    let mut con = RpcConn::system_conn(Timeout::Infinite).unwrap();
+
+    let (owner,): (String,) = con
+        .call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "GetNameOwner",
+            ("org.freedesktop.DBus",),
+            Timeout::Infinite,
+        )
+        .unwrap();
+    assert_eq!(owner, "org.freedesktop.DBus");
+
+    let (names,): (Vec<String>,) = con
+        .call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "ListNames",
+            (),
+            Timeout::Infinite,
+        )
+        .unwrap();
+    assert!(names.iter().any(|name| name == "org.freedesktop.DBus"));
+
+    let err = con
+        .call::<_, (String,)>(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "GetNameOwner",
+            ("io.killing.spark.DoesNotExist",),
+            Timeout::Infinite,
+        )
+        .unwrap_err();
+    assert_eq!(err.well_known(), Some(WellKnownError::NameHasNoOwner));
+    match err {
+        CallError::Remote(err) => {
+            assert_eq!(err.name, "org.freedesktop.DBus.Error.NameHasNoOwner");
+            assert!(err.message.is_some());
+        }
+        other => panic!("Unexpected error: {:?}", other),
+    }
+
+    let err = con
+        .call::<_, ()>(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "NoSuchMethod",
+            (),
+            Timeout::Infinite,
+        )
+        .unwrap_err();
+    assert_eq!(err.well_known(), Some(WellKnownError::UnknownMethod));
+
+    let err = con
+        .call::<_, ()>(
+            "io.killing.spark.DoesNotExist",
+            "/",
+            "io.killing.spark",
+            "Method",
+            (),
+            Timeout::Infinite,
+        )
+        .unwrap_err();
+    assert_eq!(err.well_known(), Some(WellKnownError::ServiceUnknown));
+
+    // wrong return type
+    let err = con
+        .call::<_, (u32,)>(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "GetNameOwner",
+            ("org.freedesktop.DBus",),
+            Timeout::Infinite,
+        )
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        CallError::Unmarshal(crate::wire::errors::UnmarshalError::WrongSignature)
+    ));
+
+    // the reply has more values than expected
+    let err = con
+        .call::<_, ()>(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "GetNameOwner",
+            ("org.freedesktop.DBus",),
+            Timeout::Infinite,
+        )
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        CallError::Unmarshal(crate::wire::errors::UnmarshalError::WrongSignature)
+    ));
+}
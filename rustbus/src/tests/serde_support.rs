@@ -0,0 +1,387 @@
+use crate::signature::Type;
+use crate::wire::errors::{MarshalError, UnmarshalError};
+use crate::wire::marshal::MarshalContext;
+use crate::wire::serde::{
+    from_context, signature_of_type, signature_of_value, to_context, SerdeError, SerdeParam,
+};
+use crate::wire::unmarshal::traits::Variant;
+use crate::wire::unmarshal::UnmarshalContext;
+use crate::{ByteOrder, MessageBuilder};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Inner {
+    flag: bool,
+    small: i8,
+    ratio: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Shape {
+    Empty,
+    Circle(f64),
+    Rect(u32, u32),
+    Named { name: String, corners: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Outer {
+    id: u64,
+    name: String,
+    inner: Inner,
+    pairs: Vec<(u16, String)>,
+    table: HashMap<String, Vec<i32>>,
+    shape: Shape,
+    wrapped: Wrapper,
+    maybe: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Wrapper(u8);
+
+fn sig_str(typ: &Type) -> String {
+    let mut sig = String::new();
+    typ.to_str(&mut sig);
+    sig
+}
+
+fn parse_sig(sig: &str) -> Type {
+    Type::parse_description(sig).unwrap().remove(0)
+}
+
+fn roundtrip<T>(value: &T, sig: &str, byteorder: ByteOrder) -> T
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    let sig = parse_sig(sig);
+    let mut fds = Vec::new();
+    let mut buf = Vec::new();
+    let mut ctx = MarshalContext {
+        fds: &mut fds,
+        buf: &mut buf,
+        byteorder,
+    };
+    to_context(value, &sig, &mut ctx).unwrap();
+
+    let mut ctx = UnmarshalContext {
+        fds: &[],
+        buf: &buf,
+        byteorder,
+        offset: 0,
+    };
+    let read = from_context(&sig, &mut ctx).unwrap();
+    assert_eq!(ctx.offset, buf.len());
+    read
+}
+
+fn example() -> Outer {
+    let mut table = HashMap::new();
+    table.insert("primes".to_owned(), vec![2, 3, 5]);
+    table.insert("none".to_owned(), vec![]);
+    Outer {
+        id: 42,
+        name: "outer".to_owned(),
+        inner: Inner {
+            flag: true,
+            small: -3,
+            ratio: 0.25,
+        },
+        pairs: vec![(1, "one".to_owned()), (2, "two".to_owned())],
+        table,
+        shape: Shape::Rect(3, 4),
+        wrapped: Wrapper(7),
+        maybe: Some(-1),
+    }
+}
+
+#[test]
+fn test_serde_signature_inference() {
+    let sig = signature_of_type::<Outer>().unwrap();
+    assert_eq!(sig_str(&sig), "(ts(bnd)a(qs)a{sai}vyx)");
+    // the empty array in the table hides its signature
+    assert!(signature_of_value(&example()).is_err());
+    let mut value = example();
+    value.table.remove("none");
+    assert_eq!(sig_str(&signature_of_value(&value).unwrap()), sig_str(&sig));
+
+    assert_eq!(sig_str(&signature_of_type::<Vec<String>>().unwrap()), "as");
+    assert_eq!(
+        sig_str(&signature_of_type::<HashMap<u32, (bool, char)>>().unwrap()),
+        "a{u(bs)}"
+    );
+    assert_eq!(sig_str(&signature_of_type::<Shape>().unwrap()), "v");
+
+    assert!(matches!(
+        signature_of_value(&Vec::<u32>::new()),
+        Err(SerdeError::CannotInferSignature(_))
+    ));
+    assert!(matches!(
+        signature_of_value(&Option::<u32>::None),
+        Err(SerdeError::Unsupported(_))
+    ));
+    assert!(matches!(
+        signature_of_type::<HashMap<Vec<u8>, u8>>(),
+        Err(SerdeError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn test_serde_roundtrip() {
+    let value = example();
+    for byteorder in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+        let sig = "(ts(bnd)a(qs)a{sai}vyx)";
+        assert_eq!(roundtrip(&value, sig, byteorder), value);
+
+        for shape in [
+            Shape::Empty,
+            Shape::Circle(1.5),
+            Shape::Rect(1, 2),
+            Shape::Named {
+                name: "tri".to_owned(),
+                corners: vec![1, 2, 3],
+            },
+        ] {
+            assert_eq!(roundtrip(&shape, "v", byteorder), shape);
+        }
+    }
+
+    // strings can be marshalled as object paths and signatures
+    let path = roundtrip(
+        &"/io/killing/spark".to_owned(),
+        "o",
+        ByteOrder::LittleEndian,
+    );
+    assert_eq!(path, "/io/killing/spark");
+    let sig = roundtrip(&"a{sv}".to_owned(), "g", ByteOrder::LittleEndian);
+    assert_eq!(sig, "a{sv}");
+}
+
+#[test]
+fn test_serde_params() {
+    let value = example();
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Serde", "/io/killing/spark")
+        .build();
+    msg.body.push_param(SerdeParam(value.clone())).unwrap();
+    msg.body.push_param(SerdeParam(Shape::Circle(2.0))).unwrap();
+    assert_eq!(msg.get_sig(), "(ts(bnd)a(qs)a{sai}vyx)v");
+
+    let mut parser = msg.body.parser();
+    let SerdeParam(read) = parser.get::<SerdeParam<Outer>>().unwrap();
+    assert_eq!(read, value);
+
+    // the enum is a normal variant for the rest of rustbus
+    let variant: Variant = parser.get().unwrap();
+    assert_eq!(
+        variant.get::<(String, f64)>().unwrap(),
+        ("Circle".to_owned(), 2.0)
+    );
+
+    // and the other way around
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Serde", "/io/killing/spark")
+        .build();
+    msg.body.push_param((7u32, vec!["a", "b"])).unwrap();
+    let SerdeParam((num, list)) = msg
+        .body
+        .parser()
+        .get::<SerdeParam<(u32, Vec<String>)>>()
+        .unwrap();
+    assert_eq!(num, 7);
+    assert_eq!(list, vec!["a".to_owned(), "b".to_owned()]);
+}
+
+#[test]
+fn test_serde_errors() {
+    let mut fds = Vec::new();
+    let mut buf = Vec::new();
+    let mut ctx = MarshalContext {
+        fds: &mut fds,
+        buf: &mut buf,
+        byteorder: ByteOrder::LittleEndian,
+    };
+    assert!(matches!(
+        to_context(&5u32, &parse_sig("s"), &mut ctx),
+        Err(SerdeError::WrongSignature(sig)) if sig == "s"
+    ));
+    assert!(matches!(
+        to_context(&(1u8, 2u8), &parse_sig("(yyy)"), &mut ctx),
+        Err(SerdeError::WrongSignature(_))
+    ));
+    assert!(matches!(
+        to_context("no/path", &parse_sig("o"), &mut ctx),
+        Err(SerdeError::Marshal(_))
+    ));
+
+    // a string read as a number
+    let mut buf = Vec::new();
+    let mut ctx = MarshalContext {
+        fds: &mut fds,
+        buf: &mut buf,
+        byteorder: ByteOrder::LittleEndian,
+    };
+    to_context("text", &parse_sig("s"), &mut ctx).unwrap();
+    let mut ctx = UnmarshalContext {
+        fds: &[],
+        buf: &buf,
+        byteorder: ByteOrder::LittleEndian,
+        offset: 0,
+    };
+    assert!(from_context::<u32>(&parse_sig("s"), &mut ctx).is_err());
+
+    // an array whose length points past the end
+    let short = [8, 0, 0, 0, 1, 0, 0, 0];
+    let mut ctx = UnmarshalContext {
+        fds: &[],
+        buf: &short,
+        byteorder: ByteOrder::LittleEndian,
+        offset: 0,
+    };
+    assert!(matches!(
+        from_context::<Vec<u32>>(&parse_sig("au"), &mut ctx),
+        Err(SerdeError::Unmarshal(
+            UnmarshalError::NotEnoughBytesForCollection
+        ))
+    ));
+
+    // the wrong variant content for an enum
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Serde", "/io/killing/spark")
+        .build();
+    msg.body
+        .push_variant((String::from("Rect"), "four"))
+        .unwrap();
+    assert!(msg.body.parser().get::<SerdeParam<Shape>>().is_err());
+}
+
+/// Only serializable, and borrowing
+#[derive(Serialize)]
+struct Borrowed<'a> {
+    name: &'a str,
+    tags: Vec<&'a str>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct BorrowedBack<'a> {
+    name: &'a str,
+    tags: Vec<&'a str>,
+}
+
+#[test]
+fn test_serde_explicit_signature() {
+    let name = String::from("borrowed");
+    let value = Borrowed {
+        name: &name,
+        tags: vec![],
+    };
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Serde", "/io/killing/spark")
+        .build();
+    // the empty list hides its type
+    assert!(signature_of_value(&value).is_err());
+    msg.body.push_serde(&value, &parse_sig("(sas)")).unwrap();
+    msg.body
+        .push_serde(&Option::<u32>::None, &parse_sig("u"))
+        .unwrap_err();
+    msg.body.push_serde(&5u8, &parse_sig("(yy)")).unwrap_err();
+    // failed pushes leave the body unchanged
+    assert_eq!(msg.get_sig(), "(sas)");
+
+    let mut parser = msg.body.parser();
+    let read: BorrowedBack = parser.get_serde().unwrap();
+    assert_eq!(
+        read,
+        BorrowedBack {
+            name: "borrowed",
+            tags: vec![]
+        }
+    );
+    assert!(matches!(
+        parser.get_serde::<u32>(),
+        Err(UnmarshalError::EndOfMessage)
+    ));
+}
+
+#[test]
+fn test_serde_param_without_signature() {
+    type Unsupported = HashMap<Vec<u8>, u8>;
+    assert!(SerdeParam::<Unsupported>::try_signature().is_err());
+
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Serde", "/io/killing/spark")
+        .build();
+    assert!(matches!(
+        msg.body.push_param(SerdeParam(Unsupported::new())),
+        Err(MarshalError::Serde(_))
+    ));
+    assert_eq!(msg.get_sig(), "");
+
+    msg.body.push_param(vec![1u8]).unwrap();
+    assert!(matches!(
+        msg.body.parser().get::<SerdeParam<Unsupported>>(),
+        Err(UnmarshalError::WrongSignature)
+    ));
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Event {
+    Added(Vec<u32>),
+    Changed {
+        id: u32,
+        props: HashMap<String, Vec<u32>>,
+    },
+}
+
+#[test]
+fn test_serde_enum_with_empty_collections() {
+    let mut props = HashMap::new();
+    props.insert("some".to_owned(), vec![1, 2]);
+    props.insert("none".to_owned(), vec![]);
+    let events = [
+        Event::Added(vec![]),
+        Event::Changed {
+            id: 1,
+            props: HashMap::new(),
+        },
+        // the empty list is merged with the full one
+        Event::Changed { id: 2, props },
+    ];
+
+    for event in &events {
+        assert_eq!(&roundtrip(event, "v", ByteOrder::LittleEndian), event);
+
+        let mut msg = MessageBuilder::new()
+            .signal("io.killing.spark", "Serde", "/io/killing/spark")
+            .build();
+        msg.body.push_param(SerdeParam(event.clone())).unwrap();
+        msg.body.push_serde(event, &parse_sig("v")).unwrap();
+        let mut parser = msg.body.parser();
+        let SerdeParam(read) = parser.get::<SerdeParam<Event>>().unwrap();
+        assert_eq!(&read, event);
+        assert_eq!(&parser.get_serde::<Event>().unwrap(), event);
+    }
+
+    // the placeholder signatures of the empty collections
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Serde", "/io/killing/spark")
+        .build();
+    msg.body.push_param(SerdeParam(events[0].clone())).unwrap();
+    msg.body.push_param(SerdeParam(events[1].clone())).unwrap();
+    let mut parser = msg.body.parser();
+    assert_eq!(parser.get::<Variant>().unwrap().sig, parse_sig("(sav)"));
+    assert_eq!(parser.get::<Variant>().unwrap().sig, parse_sig("(sua{sv})"));
+}
+
+#[test]
+#[should_panic(expected = "can not be inferred")]
+fn test_serde_param_without_signature_in_container() {
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "Serde", "/io/killing/spark")
+        .build();
+    // the vec asks for the signature of its elements, which can not fail gracefully
+    let _ = msg
+        .body
+        .push_param(Vec::<SerdeParam<HashMap<Vec<u8>, u8>>>::new());
+}
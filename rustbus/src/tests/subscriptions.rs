@@ -0,0 +1,109 @@
+use crate::connection::rpc_conn::RpcConn;
+use crate::connection::Timeout;
+use crate::match_rule::MatchRule;
+use crate::message_builder::MessageBuilder;
+use crate::MessageType;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+fn emit(con: &mut RpcConn, member: &str) {
+    let mut sig = MessageBuilder::new()
+        .signal("io.killing.spark", member, "/io/killing/spark")
+        .build();
+    con.send_message(&mut sig)
+        .unwrap()
+        .write_all()
+        .map_err(crate::connection::ll_conn::force_finish_on_error)
+        .unwrap();
+}
+
+/// A call to the bus that returns after the bus processed all messages sent before it on this connection,
+/// and all messages the bus sent to this connection before the reply have been read.
+fn roundtrip(con: &mut RpcConn) {
+    let mut call = crate::standard_messages::list_names();
+    let serial = con
+        .send_message(&mut call)
+        .unwrap()
+        .write_all()
+        .map_err(crate::connection::ll_conn::force_finish_on_error)
+        .unwrap();
+    con.wait_response(serial, Timeout::Infinite).unwrap();
+}
+
+#[test]
+fn test_subscriptions() {
+    let mut con1 = RpcConn::system_conn(Timeout::Infinite).unwrap();
+    let mut con2 = RpcConn::system_conn(Timeout::Infinite).unwrap();
+    // get rid of signals like NameAcquired the bus sends on its own
+    roundtrip(&mut con2);
+    while con2.try_get_signal().is_some() {}
+
+    let rule = MatchRule::new()
+        .message_type(MessageType::Signal)
+        .interface("io.killing.spark")
+        .member("SubscriptionSignal");
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let counter_cb = counter.clone();
+    let cb_token = con2
+        .subscribe(
+            rule.clone(),
+            Box::new(move |_| {
+                counter_cb.fetch_add(1, Ordering::SeqCst);
+            }),
+            Timeout::Infinite,
+        )
+        .unwrap();
+    // same rule, the match is only added once, so the signal is only delivered once
+    let queue_token = con2
+        .subscribe_queued(rule.clone(), Timeout::Infinite)
+        .unwrap();
+
+    emit(&mut con1, "SubscriptionSignal");
+    let sig = con2
+        .wait_subscribed_signal(&queue_token, Timeout::Infinite)
+        .unwrap();
+    assert_eq!(sig.dynheader.member.as_deref(), Some("SubscriptionSignal"));
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+    assert!(con2.try_get_subscribed_signal(&queue_token).is_none());
+    assert!(con2.try_get_signal().is_none());
+
+    // the rule stays active while one subscription uses it
+    drop(queue_token);
+    roundtrip(&mut con2);
+    emit(&mut con1, "SubscriptionSignal");
+    roundtrip(&mut con1);
+    roundtrip(&mut con2);
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+    assert!(con2.try_get_signal().is_none());
+
+    // after the last subscription is gone the bus does not send the signal anymore
+    con2.unsubscribe(cb_token).unwrap();
+    emit(&mut con1, "SubscriptionSignal");
+    roundtrip(&mut con1);
+    roundtrip(&mut con2);
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+    assert!(con2.try_get_signal().is_none());
+}
+
+#[test]
+fn test_flush_subscriptions() {
+    let mut con1 = RpcConn::system_conn(Timeout::Infinite).unwrap();
+    let mut con2 = RpcConn::system_conn(Timeout::Infinite).unwrap();
+    roundtrip(&mut con2);
+    while con2.try_get_signal().is_some() {}
+
+    let rule = MatchRule::new()
+        .message_type(MessageType::Signal)
+        .interface("io.killing.spark")
+        .member("FlushedSignal");
+    let token = con2.subscribe_queued(rule, Timeout::Infinite).unwrap();
+
+    // without the flush the bus would still route the signal to con2 because RemoveMatch was not sent yet
+    drop(token);
+    con2.flush_subscriptions().unwrap();
+    emit(&mut con1, "FlushedSignal");
+    roundtrip(&mut con1);
+    roundtrip(&mut con2);
+    assert!(con2.try_get_signal().is_none());
+}
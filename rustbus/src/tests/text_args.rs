@@ -0,0 +1,170 @@
+use crate::params::text::{parse_args, TextError};
+use crate::params::{Base, Container, Param};
+use crate::signature;
+use crate::MessageBuilder;
+
+use std::collections::HashMap;
+
+fn tokens(text: &str) -> Vec<&str> {
+    text.split(' ').filter(|token| !token.is_empty()).collect()
+}
+
+#[test]
+fn test_text_args_busctl_example() {
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "TextArgs", "/io/killing/spark")
+        .build();
+    msg.body
+        .push_text_args("a{sv}(ii)as", tokens("2 key1 s hello key2 i 5 3 4 2 a b"))
+        .unwrap();
+    assert_eq!(msg.get_sig(), "a{sv}(ii)as");
+
+    let mut parser = msg.body.parser();
+    let dict: HashMap<String, crate::wire::unmarshal::traits::Variant> = parser.get().unwrap();
+    assert_eq!(dict["key1"].get::<String>().unwrap(), "hello");
+    assert_eq!(dict["key2"].get::<i32>().unwrap(), 5);
+    let pair: (i32, i32) = parser.get().unwrap();
+    assert_eq!(pair, (3, 4));
+    let list: Vec<String> = parser.get().unwrap();
+    assert_eq!(list, vec!["a".to_owned(), "b".to_owned()]);
+}
+
+#[test]
+fn test_text_args_base_types() {
+    let params = parse_args(
+        "ybnqiuxtdsogb",
+        tokens("255 true -5 5 -100000 100000 -1 18446744073709551615 1.5 text /a/b a(su) off"),
+    )
+    .unwrap();
+    assert_eq!(
+        params,
+        vec![
+            Param::Base(Base::Byte(255)),
+            Param::Base(Base::Boolean(true)),
+            Param::Base(Base::Int16(-5)),
+            Param::Base(Base::Uint16(5)),
+            Param::Base(Base::Int32(-100000)),
+            Param::Base(Base::Uint32(100000)),
+            Param::Base(Base::Int64(-1)),
+            Param::Base(Base::Uint64(u64::MAX)),
+            Param::Base(Base::Double(1.5f64.to_bits())),
+            Param::Base(Base::String("text".to_owned())),
+            Param::Base(Base::ObjectPath("/a/b".to_owned())),
+            Param::Base(Base::Signature("a(su)".to_owned())),
+            Param::Base(Base::Boolean(false)),
+        ]
+    );
+}
+
+#[test]
+fn test_text_args_containers() {
+    let params = parse_args("asa{ya{sb}}v", tokens("0 1 7 1 x yes av 2 u 1 s two")).unwrap();
+    let string_sig = signature::Type::Base(signature::Base::String);
+    match &params[0] {
+        Param::Container(Container::Array(array)) => {
+            assert_eq!(array.element_sig, string_sig);
+            assert!(array.values.is_empty());
+        }
+        other => panic!("Expected an array, got {:?}", other),
+    }
+    match &params[1] {
+        Param::Container(Container::Dict(dict)) => {
+            assert_eq!(dict.key_sig, signature::Base::Byte);
+            assert_eq!(dict.map.len(), 1);
+            assert!(dict.map.contains_key(&Base::Byte(7)));
+        }
+        other => panic!("Expected a dict, got {:?}", other),
+    }
+    match &params[2] {
+        Param::Container(Container::Variant(variant)) => {
+            let mut sig = String::new();
+            variant.sig.to_str(&mut sig);
+            assert_eq!(sig, "av");
+        }
+        other => panic!("Expected a variant, got {:?}", other),
+    }
+
+    // the params must be accepted by the marshalling
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "TextArgs", "/io/killing/spark")
+        .build();
+    msg.body.push_old_params(&params).unwrap();
+    assert_eq!(msg.get_sig(), "asa{ya{sb}}v");
+    msg.unmarshall_all().unwrap();
+}
+
+#[test]
+fn test_text_args_errors() {
+    match parse_args("si", tokens("text five")) {
+        Err(TextError::InvalidToken {
+            index,
+            token,
+            expected,
+        }) => {
+            assert_eq!(index, 1);
+            assert_eq!(token, "five");
+            assert_eq!(expected, "int32 (i)");
+        }
+        other => panic!("Expected an invalid token, got {:?}", other),
+    }
+    match parse_args("as", tokens("3 a b")) {
+        Err(TextError::MissingToken { index, expected }) => {
+            assert_eq!(index, 3);
+            assert_eq!(expected, "string (s)");
+        }
+        other => panic!("Expected a missing token, got {:?}", other),
+    }
+    match parse_args("as", tokens("many a")) {
+        Err(TextError::InvalidToken {
+            index, expected, ..
+        }) => {
+            assert_eq!(index, 0);
+            assert_eq!(expected, "element count for as");
+        }
+        other => panic!("Expected an invalid token, got {:?}", other),
+    }
+    match parse_args("u", tokens("1 2")) {
+        Err(TextError::TooManyTokens { index, token }) => {
+            assert_eq!(index, 1);
+            assert_eq!(token, "2");
+        }
+        other => panic!("Expected too many tokens, got {:?}", other),
+    }
+    assert!(matches!(
+        parse_args("v", tokens("ii 1 2")),
+        Err(TextError::InvalidToken { index: 0, .. })
+    ));
+    assert!(matches!(
+        parse_args("o", tokens("no/path")),
+        Err(TextError::InvalidToken { index: 0, .. })
+    ));
+    assert!(matches!(
+        parse_args("b", tokens("maybe")),
+        Err(TextError::InvalidToken { index: 0, .. })
+    ));
+    assert!(matches!(
+        parse_args("a{", tokens("")),
+        Err(TextError::InvalidSignature(_))
+    ));
+
+    // every v token adds a variant to the one in the signature, the error comes before the stack overflows
+    let nested = |inner: usize| format!("{} i 5", vec!["v"; inner].join(" "));
+    assert!(parse_args("v", tokens(&nested(63))).is_ok());
+    assert!(matches!(
+        parse_args("v", tokens(&nested(64))),
+        Err(TextError::NestedTooDeeply { index: 64 })
+    ));
+    assert!(matches!(
+        parse_args("v", tokens(&nested(1_000_000))),
+        Err(TextError::NestedTooDeeply { index: 64 })
+    ));
+
+    // a failed push leaves the body as it was
+    let mut msg = MessageBuilder::new()
+        .signal("io.killing.spark", "TextArgs", "/io/killing/spark")
+        .build();
+    msg.body.push_param(1u32).unwrap();
+    assert!(msg.body.push_text_args("su", tokens("text x")).is_err());
+    assert_eq!(msg.get_sig(), "u");
+    assert_eq!(msg.get_buf().len(), 4);
+}
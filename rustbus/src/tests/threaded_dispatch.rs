@@ -0,0 +1,203 @@
+use crate::connection::dispatch_conn::{HandleResult, Matches};
+use crate::connection::ll_conn::DuplexConn;
+use crate::connection::rpc_conn::RpcConn;
+use crate::connection::threaded_dispatch_conn::{SharedHandleEnvironment, ThreadedDispatchConn};
+use crate::connection::Timeout;
+use crate::message_builder::{MarshalledMessage, MessageBuilder, MessageType};
+use crate::standard_messages::WellKnownError;
+
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+type Env = SharedHandleEnvironment<Ctx, ()>;
+
+struct Ctx {
+    unblock_tx: Mutex<mpsc::Sender<()>>,
+    unblock_rx: Mutex<mpsc::Receiver<()>>,
+    seen: Mutex<Vec<u32>>,
+}
+
+fn new_ctx() -> Ctx {
+    let (unblock_tx, unblock_rx) = mpsc::channel();
+    Ctx {
+        unblock_tx: Mutex::new(unblock_tx),
+        unblock_rx: Mutex::new(unblock_rx),
+        seen: Mutex::new(Vec::new()),
+    }
+}
+
+fn call(con: &mut RpcConn, path: &str, member: &str, arg: u32) -> u32 {
+    let mut msg = MessageBuilder::new()
+        .call(member)
+        .on(path)
+        .with_interface("io.killing.spark")
+        .at("io.killing.spark.Server")
+        .build();
+    msg.body.push_param(arg).unwrap();
+    con.send_message(&mut msg).unwrap().write_all().unwrap()
+}
+
+#[test]
+fn test_slow_handler_does_not_block() {
+    let (conn, peer) = DuplexConn::pair();
+    std::thread::spawn(move || {
+        let mut dpcon = ThreadedDispatchConn::new(
+            conn,
+            new_ctx(),
+            Box::new(|_: &Ctx, _: Matches, _: &MarshalledMessage, _: &mut Env| Ok(None)),
+        );
+        dpcon.set_worker_threads(2);
+        dpcon.add_method_handler(
+            "/slow",
+            "io.killing.spark",
+            "Wait",
+            None,
+            Box::new(
+                |ctx: &Ctx, _: Matches, _: &MarshalledMessage, _: &mut Env| -> HandleResult<()> {
+                    // only returns in time if the other call gets handled while this one blocks
+                    let unblock = ctx.unblock_rx.lock().unwrap();
+                    unblock.recv_timeout(Duration::from_secs(5)).unwrap();
+                    Ok(None)
+                },
+            ),
+        );
+        dpcon.add_method_handler(
+            "/fast",
+            "io.killing.spark",
+            "Unblock",
+            None,
+            Box::new(
+                |ctx: &Ctx, _: Matches, _: &MarshalledMessage, _: &mut Env| {
+                    ctx.unblock_tx.lock().unwrap().send(()).unwrap();
+                    Ok(None)
+                },
+            ),
+        );
+        let _ = dpcon.run();
+    });
+
+    let mut con = RpcConn::new(peer);
+    let slow = call(&mut con, "/slow", "Wait", 0);
+    let fast = call(&mut con, "/fast", "Unblock", 0);
+    let fast_resp = con.wait_response(fast, Timeout::Infinite).unwrap();
+    assert_eq!(fast_resp.typ, MessageType::Reply);
+    let slow_resp = con.wait_response(slow, Timeout::Infinite).unwrap();
+    assert_eq!(slow_resp.typ, MessageType::Reply);
+}
+
+#[test]
+fn test_per_object_ordering() {
+    let (conn, peer) = DuplexConn::pair();
+    std::thread::spawn(move || {
+        let mut dpcon = ThreadedDispatchConn::new(
+            conn,
+            new_ctx(),
+            Box::new(|_: &Ctx, _: Matches, _: &MarshalledMessage, _: &mut Env| Ok(None)),
+        );
+        dpcon.set_worker_threads(4);
+        dpcon.set_per_object_ordering(true);
+        dpcon.add_handler(
+            "/ordered",
+            Box::new(
+                |ctx: &Ctx, _: Matches, msg: &MarshalledMessage, _: &mut Env| {
+                    let num: u32 = msg.body.parser().get()?;
+                    if num.is_multiple_of(2) {
+                        // give the other workers a chance to overtake this one
+                        std::thread::sleep(Duration::from_millis(5));
+                    }
+                    ctx.seen.lock().unwrap().push(num);
+                    let mut resp = msg.dynheader.make_response();
+                    resp.body
+                        .push_param(ctx.seen.lock().unwrap().len() as u32)?;
+                    Ok(Some(resp))
+                },
+            ),
+        );
+        let _ = dpcon.run();
+    });
+
+    let mut con = RpcConn::new(peer);
+    let serials = (0..20)
+        .map(|num| call(&mut con, "/ordered", "Count", num))
+        .collect::<Vec<_>>();
+    let mut counts = Vec::new();
+    for serial in serials {
+        let resp = con.wait_response(serial, Timeout::Infinite).unwrap();
+        counts.push(resp.body.parser().get::<u32>().unwrap());
+    }
+    // every call saw all the calls that were sent before it
+    assert_eq!(counts, (1..=20).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_panicking_handler() {
+    let (conn, peer) = DuplexConn::pair();
+    std::thread::spawn(move || {
+        let mut dpcon = ThreadedDispatchConn::new(
+            conn,
+            new_ctx(),
+            Box::new(|_: &Ctx, _: Matches, _: &MarshalledMessage, _: &mut Env| Ok(None)),
+        );
+        // with only one worker the next call is only answered if the worker survived
+        dpcon.set_worker_threads(1);
+        dpcon.add_handler(
+            "/panic",
+            Box::new(
+                |_: &Ctx, _: Matches, _: &MarshalledMessage, _: &mut Env| -> HandleResult<()> {
+                    panic!("the handler panicked on purpose")
+                },
+            ),
+        );
+        let _ = dpcon.run();
+    });
+
+    let mut con = RpcConn::new(peer);
+    let serial = call(&mut con, "/panic", "Panic", 0);
+    let resp = con.wait_response(serial, Timeout::Infinite).unwrap();
+    assert_eq!(resp.typ, MessageType::Error);
+    assert_eq!(
+        resp.dynheader.error_name.as_deref(),
+        Some(WellKnownError::Failed.name())
+    );
+
+    let serial = call(&mut con, "/other", "Ping", 0);
+    let resp = con.wait_response(serial, Timeout::Infinite).unwrap();
+    assert_eq!(resp.typ, MessageType::Reply);
+}
+
+#[test]
+fn test_panic_while_holding_conn() {
+    let (conn, peer) = DuplexConn::pair();
+    std::thread::spawn(move || {
+        let mut dpcon = ThreadedDispatchConn::new(
+            conn,
+            new_ctx(),
+            Box::new(|_: &Ctx, _: Matches, _: &MarshalledMessage, _: &mut Env| Ok(None)),
+        );
+        dpcon.set_worker_threads(2);
+        dpcon.set_per_object_ordering(true);
+        dpcon.add_handler(
+            "/panic",
+            Box::new(
+                |_: &Ctx, _: Matches, _: &MarshalledMessage, env: &mut Env| -> HandleResult<()> {
+                    // poisons the lock on the connection all workers send through
+                    let _conn = env.conn.lock().unwrap();
+                    panic!("the handler panicked on purpose")
+                },
+            ),
+        );
+        let _ = dpcon.run();
+    });
+
+    let mut con = RpcConn::new(peer);
+    let serial = call(&mut con, "/panic", "Panic", 0);
+    let resp = con.wait_response(serial, Timeout::Infinite).unwrap();
+    assert_eq!(resp.typ, MessageType::Error);
+
+    // all queues are still served
+    for num in 0..10 {
+        let serial = call(&mut con, &format!("/object{}", num), "Ping", num);
+        let resp = con.wait_response(serial, Timeout::Infinite).unwrap();
+        assert_eq!(resp.typ, MessageType::Reply);
+    }
+}
@@ -2,6 +2,9 @@
 
 pub mod errors;
 pub mod marshal;
+pub mod pcap;
+#[cfg(feature = "serde")]
+pub mod serde;
 pub mod unmarshal;
 pub mod util;
 pub mod validate_raw;
@@ -12,6 +15,56 @@ pub use wrapper_types::unixfd::UnixFd;
 pub use wrapper_types::ObjectPath;
 pub use wrapper_types::SignatureWrapper;
 
+use crate::message_builder::DynamicHeader;
+use crate::message_builder::MessageType;
+use errors::HeaderError;
+
+/// The maximum length of a message in bytes, including the header and padding (128 MiB)
+pub const MAX_MESSAGE_SIZE: usize = 1 << 27;
+/// The maximum length of the content of an array in bytes (64 MiB)
+pub const MAX_ARRAY_SIZE: usize = 1 << 26;
+
+/// Checks that the header of a message has all the fields the spec requires for its message type and a serial
+/// that is not zero. This is done for every message that is marshalled or unmarshalled.
+pub fn validate_header(
+    typ: MessageType,
+    serial: u32,
+    dynheader: &DynamicHeader,
+) -> Result<(), HeaderError> {
+    if serial == 0 {
+        return Err(HeaderError::ZeroSerial);
+    }
+    match typ {
+        MessageType::Call | MessageType::Signal => {
+            if dynheader.object.is_none() {
+                return Err(HeaderError::MissingPath);
+            }
+            if dynheader.member.is_none() {
+                return Err(HeaderError::MissingMember);
+            }
+            if typ == MessageType::Signal && dynheader.interface.is_none() {
+                return Err(HeaderError::MissingInterface);
+            }
+        }
+        MessageType::Error => {
+            if dynheader.error_name.is_none() {
+                return Err(HeaderError::MissingErrorName);
+            }
+            if dynheader.response_serial.is_none() {
+                return Err(HeaderError::MissingReplySerial);
+            }
+        }
+        MessageType::Reply => {
+            if dynheader.response_serial.is_none() {
+                return Err(HeaderError::MissingReplySerial);
+            }
+        }
+        // The byte values are checked while (un-)marshalling the fixed header
+        MessageType::Invalid => {}
+    }
+    Ok(())
+}
+
 /// The different header fields a message may or maynot have
 #[derive(Debug)]
 pub enum HeaderField {
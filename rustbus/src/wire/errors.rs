@@ -15,12 +15,54 @@ pub enum MarshalError {
     /// Errors occuring while validating the input
     #[error("Errors occured while validating: {0}")]
     Validation(crate::params::validation::Error),
+    /// The header of the message does not conform to the spec
+    #[error("The message header is invalid: {0}")]
+    InvalidHeader(HeaderError),
+    /// The message would be bigger than `MAX_MESSAGE_SIZE`
+    #[error("The message would be {0} bytes long which is more than the maximum message size")]
+    MessageTooBig(usize),
+    /// An array would be bigger than `MAX_ARRAY_SIZE`
+    #[error("An array would be {0} bytes long which is more than the maximum array size")]
+    ArrayTooBig(usize),
+    /// A serde type could not be marshalled, see `wire::serde`
+    #[cfg(feature = "serde")]
+    #[error("A serde type could not be marshalled: {0}")]
+    Serde(String),
+}
+
+/// Ways in which a message header can violate the spec
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Error)]
+pub enum HeaderError {
+    /// The serial of a message must not be zero
+    #[error("The serial is zero")]
+    ZeroSerial,
+    /// Calls and signals need a PATH header field
+    #[error("The PATH header field is missing")]
+    MissingPath,
+    /// Calls and signals need a MEMBER header field
+    #[error("The MEMBER header field is missing")]
+    MissingMember,
+    /// Signals need an INTERFACE header field
+    #[error("The INTERFACE header field is missing")]
+    MissingInterface,
+    /// Errors need an ERROR_NAME header field
+    #[error("The ERROR_NAME header field is missing")]
+    MissingErrorName,
+    /// Replies and errors need a REPLY_SERIAL header field
+    #[error("The REPLY_SERIAL header field is missing")]
+    MissingReplySerial,
 }
 
 //--------
 // Conversion to MarshalError
 //--------
 
+impl From<HeaderError> for MarshalError {
+    fn from(e: HeaderError) -> Self {
+        MarshalError::InvalidHeader(e)
+    }
+}
+
 impl From<crate::params::validation::Error> for MarshalError {
     fn from(e: crate::params::validation::Error) -> Self {
         MarshalError::Validation(e)
@@ -85,7 +127,38 @@ pub enum UnmarshalError {
     /// A unix fd member had an index that is bigger than the size of the list of unix fds passed along with the message
     #[error("A unix fd member had an index that is bigger than the size of the list of unix fds passed along with the message")]
     BadFdIndex(usize),
+    /// An array could not be borrowed from the buffer because the byteorder or the memory alignment does not match.
+    /// Use `Cow<[T]>` or `Vec<T>` to fall back to copying in that case.
+    #[error("An array could not be borrowed from the buffer because the byteorder or the memory alignment does not match")]
+    CannotBorrowSlice,
+    /// The UnixFd in the message has already been taken by somebody else
+    #[error("The UnixFd in the message has already been taken by somebody else")]
+    EmptyUnixFd,
+    /// Error while trying to dup a UnixFd from the message
+    #[error("Error while trying to dup a UnixFd from the message")]
+    DupUnixFd(nix::Error),
     /// When unmarshalling a Variant and there is not matching variant in the enum that had the unmarshal impl derived
     #[error("When unmarshalling a Variant and there is not matching variant in the enum that had the unmarshal impl derived")]
     NoMatchingVariantFound,
+    /// The header of the message does not conform to the spec
+    #[error("The message header is invalid: {0}")]
+    InvalidHeader(HeaderError),
+    /// A message announced a length bigger than `MAX_MESSAGE_SIZE`
+    #[error(
+        "A message announced a length of {0} bytes which is more than the maximum message size"
+    )]
+    MessageTooBig(usize),
+    /// An array announced a length bigger than `MAX_ARRAY_SIZE`
+    #[error("An array announced a length of {0} bytes which is more than the maximum array size")]
+    ArrayTooBig(usize),
+    /// A serde type could not be unmarshalled, see `wire::serde`
+    #[cfg(feature = "serde")]
+    #[error("A serde type could not be unmarshalled: {0}")]
+    Serde(String),
+}
+
+impl From<HeaderError> for UnmarshalError {
+    fn from(e: HeaderError) -> Self {
+        UnmarshalError::InvalidHeader(e)
+    }
 }
@@ -37,9 +37,15 @@ pub fn marshal(
     chosen_serial: u32,
     buf: &mut Vec<u8>,
 ) -> MarshalResult<()> {
+    crate::wire::validate_header(msg.typ, chosen_serial, &msg.dynheader)?;
     marshal_header(msg, chosen_serial, buf)?;
     pad_to_align(8, buf);
 
+    let msg_size = buf.len() + msg.get_buf().len();
+    if msg_size > crate::wire::MAX_MESSAGE_SIZE {
+        return Err(crate::wire::errors::MarshalError::MessageTooBig(msg_size));
+    }
+
     // set the correct message length
     insert_u32(
         msg.body.byteorder,
@@ -93,6 +99,9 @@ fn marshal_header(
     if let Some(serial) = &msg.dynheader.response_serial {
         marshal_header_field(byteorder, &HeaderField::ReplySerial(*serial), buf)?;
     }
+    if let Some(name) = &msg.dynheader.error_name {
+        marshal_header_field(byteorder, &HeaderField::ErrorName(name.clone()), buf)?;
+    }
     if let Some(int) = &msg.dynheader.interface {
         marshal_header_field(byteorder, &HeaderField::Interface(int.clone()), buf)?;
     }
@@ -105,6 +114,9 @@ fn marshal_header(
     if let Some(obj) = &msg.dynheader.object {
         marshal_header_field(byteorder, &HeaderField::Path(obj.clone()), buf)?;
     }
+    if let Some(snd) = &msg.dynheader.sender {
+        marshal_header_field(byteorder, &HeaderField::Sender(snd.clone()), buf)?;
+    }
     if !msg.body.raw_fds.is_empty() {
         marshal_header_field(
             byteorder,
@@ -32,11 +32,7 @@ fn marshal_array(
         marshal_param(p, ctx)?;
     }
     let len = ctx.buf.len() - content_pos;
-    insert_u32(
-        ctx.byteorder,
-        len as u32,
-        &mut ctx.buf[len_pos..len_pos + 4],
-    );
+    insert_array_len(ctx.byteorder, len, ctx.buf, len_pos)?;
     Ok(())
 }
 
@@ -73,11 +69,7 @@ fn marshal_dict(dict: &params::DictMap, ctx: &mut MarshalContext) -> Result<(),
         marshal_param(value, ctx)?;
     }
     let len = ctx.buf.len() - content_pos;
-    insert_u32(
-        ctx.byteorder,
-        len as u32,
-        &mut ctx.buf[len_pos..len_pos + 4],
-    );
+    insert_array_len(ctx.byteorder, len, ctx.buf, len_pos)?;
     Ok(())
 }
 
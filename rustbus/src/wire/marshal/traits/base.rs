@@ -8,6 +8,7 @@ use crate::wire::ObjectPath;
 use crate::wire::SignatureWrapper;
 use crate::Marshal;
 use crate::Signature;
+use std::borrow::Cow;
 
 impl Signature for u64 {
     #[inline]
@@ -66,6 +67,35 @@ impl Marshal for i64 {
     }
 }
 
+impl Signature for f64 {
+    #[inline]
+    fn signature() -> crate::signature::Type {
+        crate::signature::Type::Base(crate::signature::Base::Double)
+    }
+    #[inline]
+    fn alignment() -> usize {
+        8
+    }
+    #[inline]
+    unsafe fn valid_slice(bo: crate::ByteOrder) -> bool {
+        bo == crate::ByteOrder::NATIVE
+    }
+    fn sig_str(sig: &mut SignatureBuffer) {
+        sig.push_static("d");
+    }
+    fn has_sig(sig: &str) -> bool {
+        sig.starts_with('d')
+    }
+}
+impl Marshal for f64 {
+    fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+        ctx.align_to(Self::alignment());
+        // dbus uses IEEE 754 doubles, which is what rust uses too
+        util::write_u64(self.to_bits(), ctx.byteorder, ctx.buf);
+        Ok(())
+    }
+}
+
 impl Signature for u32 {
     #[inline]
     fn signature() -> crate::signature::Type {
@@ -280,6 +310,30 @@ impl Marshal for &str {
     }
 }
 
+impl Signature for Cow<'_, str> {
+    #[inline]
+    fn signature() -> crate::signature::Type {
+        String::signature()
+    }
+    #[inline]
+    fn alignment() -> usize {
+        String::alignment()
+    }
+    #[inline]
+    fn sig_str(sig: &mut SignatureBuffer) {
+        String::sig_str(sig);
+    }
+    #[inline]
+    fn has_sig(sig: &str) -> bool {
+        String::has_sig(sig)
+    }
+}
+impl Marshal for Cow<'_, str> {
+    fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+        self.as_ref().marshal(ctx)
+    }
+}
+
 impl<S: AsRef<str>> Signature for ObjectPath<S> {
     #[inline]
     fn signature() -> crate::signature::Type {
@@ -6,6 +6,10 @@ use crate::wire::marshal::traits::SignatureBuffer;
 use crate::wire::marshal::MarshalContext;
 use crate::Marshal;
 use crate::Signature;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use std::sync::Arc;
 
 impl<E: Signature> Signature for (E,) {
     fn signature() -> crate::signature::Type {
@@ -271,45 +275,151 @@ impl<E: Marshal> Marshal for &[E] {
     fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
         // always align to 4
         ctx.align_to(4);
+        if marshal_fixed_size_array(&[self], ctx)? {
+            return Ok(());
+        }
+        marshal_array_elements(self.iter(), ctx)
+    }
+}
+
+/// Bulk copy the elements of the parts into the buffer as one dbus array, if the memory layout of `E` matches
+/// the dbus array format (see `Signature::valid_slice`). Returns false and does nothing if that is not the case.
+/// Fails if the array would be bigger than `MAX_ARRAY_SIZE`.
+/// The caller is responsible to align to 4 beforehand.
+fn marshal_fixed_size_array<E: Signature>(
+    parts: &[&[E]],
+    ctx: &mut MarshalContext,
+) -> Result<bool, MarshalError> {
+    // SAFETY: valid_slice guarantees that the bytes of the elements are valid dbus array content
+    unsafe {
+        if !E::valid_slice(ctx.byteorder) {
+            return Ok(false);
+        }
         let alignment = E::alignment();
-        unsafe {
-            if E::valid_slice(ctx.byteorder) {
-                debug_assert_eq!(alignment, std::mem::size_of::<E>());
-                let len = alignment * self.len();
-                assert!(len <= u32::MAX as usize);
-                write_u32(len as u32, ctx.byteorder, ctx.buf);
-                ctx.align_to(alignment);
-                let ptr = *self as *const [E] as *const u8;
-                let slice = std::slice::from_raw_parts(ptr, len);
-                ctx.buf.extend_from_slice(slice);
-                return Ok(());
-            }
+        debug_assert_eq!(alignment, std::mem::size_of::<E>());
+        let len = alignment * parts.iter().map(|part| part.len()).sum::<usize>();
+        if len > crate::wire::MAX_ARRAY_SIZE {
+            return Err(MarshalError::ArrayTooBig(len));
+        }
+        write_u32(len as u32, ctx.byteorder, ctx.buf);
+        ctx.align_to(alignment);
+        ctx.buf.reserve(len);
+        for part in parts {
+            let ptr = *part as *const [E] as *const u8;
+            let slice = std::slice::from_raw_parts(ptr, alignment * part.len());
+            ctx.buf.extend_from_slice(slice);
         }
+    }
+    Ok(true)
+}
 
-        let size_pos = ctx.buf.len();
-        ctx.buf.extend_from_slice(&[0; 4]);
+/// Marshal the elements of any collection as a dbus array. The caller is responsible to align to 4 beforehand.
+fn marshal_array_elements<'a, E: Marshal + 'a>(
+    elements: impl ExactSizeIterator<Item = &'a E>,
+    ctx: &mut MarshalContext,
+) -> Result<(), MarshalError> {
+    let alignment = E::alignment();
+    let size_pos = ctx.buf.len();
+    ctx.buf.extend_from_slice(&[0; 4]);
 
-        ctx.align_to(alignment);
+    ctx.align_to(alignment);
+
+    if elements.len() == 0 {
+        return Ok(());
+    }
+
+    // In an array each entry, except the last  will take up at least its alignment in space.
+    // The last may take less (like type '(yy)') but this is small and worth it.
+    ctx.buf.reserve(elements.len() * alignment);
+    let size_before = ctx.buf.len();
+    for p in elements {
+        p.marshal(ctx)?;
+    }
+    let size_of_content = ctx.buf.len() - size_before;
+    crate::wire::util::insert_array_len(ctx.byteorder, size_of_content, ctx.buf, size_pos)?;
+
+    Ok(())
+}
 
-        if self.is_empty() {
+impl<E: Marshal + Clone> Marshal for Cow<'_, [E]> {
+    fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+        self.as_ref().marshal(ctx)
+    }
+}
+
+impl<E: Signature> Signature for VecDeque<E> {
+    fn signature() -> crate::signature::Type {
+        <[E]>::signature()
+    }
+    #[inline]
+    fn alignment() -> usize {
+        <[E]>::alignment()
+    }
+    #[inline]
+    fn sig_str(s_buf: &mut SignatureBuffer) {
+        <[E]>::sig_str(s_buf)
+    }
+    fn has_sig(sig: &str) -> bool {
+        <[E]>::has_sig(sig)
+    }
+}
+impl<E: Marshal> Marshal for VecDeque<E> {
+    fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+        // always align to 4
+        ctx.align_to(4);
+        let (front, back) = self.as_slices();
+        if marshal_fixed_size_array(&[front, back], ctx)? {
             return Ok(());
         }
+        marshal_array_elements(self.iter(), ctx)
+    }
+}
 
-        // In an array each entry, except the last  will take up at least its alignment in space.
-        // The last may take less (like type '(yy)') but this is small and worth it.
-        ctx.buf.reserve(self.len() * alignment);
-        let size_before = ctx.buf.len();
-        for p in self.iter() {
-            p.marshal(ctx)?;
-        }
-        let size_of_content = ctx.buf.len() - size_before;
-        crate::wire::util::insert_u32(
-            ctx.byteorder,
-            size_of_content as u32,
-            &mut ctx.buf[size_pos..size_pos + 4],
-        );
+impl<E: Signature> Signature for HashSet<E> {
+    fn signature() -> crate::signature::Type {
+        <[E]>::signature()
+    }
+    #[inline]
+    fn alignment() -> usize {
+        <[E]>::alignment()
+    }
+    #[inline]
+    fn sig_str(s_buf: &mut SignatureBuffer) {
+        <[E]>::sig_str(s_buf)
+    }
+    fn has_sig(sig: &str) -> bool {
+        <[E]>::has_sig(sig)
+    }
+}
+impl<E: Marshal> Marshal for HashSet<E> {
+    fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+        // always align to 4
+        ctx.align_to(4);
+        marshal_array_elements(self.iter(), ctx)
+    }
+}
 
-        Ok(())
+impl<E: Signature> Signature for BTreeSet<E> {
+    fn signature() -> crate::signature::Type {
+        <[E]>::signature()
+    }
+    #[inline]
+    fn alignment() -> usize {
+        <[E]>::alignment()
+    }
+    #[inline]
+    fn sig_str(s_buf: &mut SignatureBuffer) {
+        <[E]>::sig_str(s_buf)
+    }
+    fn has_sig(sig: &str) -> bool {
+        <[E]>::has_sig(sig)
+    }
+}
+impl<E: Marshal> Marshal for BTreeSet<E> {
+    fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+        // always align to 4
+        ctx.align_to(4);
+        marshal_array_elements(self.iter(), ctx)
     }
 }
 
@@ -339,68 +449,132 @@ impl<T: Marshal + Signature> Marshal for Variant<T> {
     }
 }
 
-impl<K: Signature, V: Signature> Signature for std::collections::HashMap<K, V> {
+fn dict_signature<K: Signature, V: Signature>() -> crate::signature::Type {
+    let ks = K::signature();
+    let vs = V::signature();
+    if let crate::signature::Type::Base(ks) = ks {
+        crate::signature::Type::Container(crate::signature::Container::Dict(ks, Box::new(vs)))
+    } else {
+        panic!("Ivalid key sig")
+    }
+}
+
+fn dict_sig_str<K: Signature, V: Signature>(s_buf: &mut SignatureBuffer) {
+    s_buf.push_str("a{");
+    K::sig_str(s_buf);
+    V::sig_str(s_buf);
+    s_buf.push_str("}");
+}
+
+fn dict_has_sig<K: Signature, V: Signature>(sig: &str) -> bool {
+    if sig.starts_with("a{") {
+        let mut iter = SignatureIter::new(&sig[2..sig.len() - 1]);
+        K::has_sig(iter.next().unwrap()) && V::has_sig(iter.next().unwrap())
+    } else {
+        false
+    }
+}
+
+/// Marshal key-value pairs as a dbus dict. The caller does not need to align beforehand.
+fn marshal_dict_entries<'a, K: Marshal + 'a, V: Marshal + 'a>(
+    entries: impl ExactSizeIterator<Item = (&'a K, &'a V)>,
+    ctx: &mut MarshalContext,
+) -> Result<(), MarshalError> {
+    // always align to 4
+    ctx.align_to(4);
+
+    let size_pos = ctx.buf.len();
+    ctx.buf.push(0);
+    ctx.buf.push(0);
+    ctx.buf.push(0);
+    ctx.buf.push(0);
+
+    // always align to 8
+    ctx.align_to(8);
+
+    if entries.len() == 0 {
+        return Ok(());
+    }
+
+    let size_before = ctx.buf.len();
+    for p in entries {
+        // always align to 8
+        ctx.align_to(8);
+        p.0.marshal(ctx)?;
+        p.1.marshal(ctx)?;
+    }
+    let size_of_content = ctx.buf.len() - size_before;
+    crate::wire::util::insert_array_len(ctx.byteorder, size_of_content, ctx.buf, size_pos)?;
+
+    Ok(())
+}
+
+impl<K: Signature, V: Signature> Signature for HashMap<K, V> {
     fn signature() -> crate::signature::Type {
-        let ks = K::signature();
-        let vs = V::signature();
-        if let crate::signature::Type::Base(ks) = ks {
-            crate::signature::Type::Container(crate::signature::Container::Dict(ks, Box::new(vs)))
-        } else {
-            panic!("Ivalid key sig")
-        }
+        dict_signature::<K, V>()
     }
 
     fn alignment() -> usize {
         4
     }
     fn sig_str(s_buf: &mut SignatureBuffer) {
-        s_buf.push_str("a{");
-        K::sig_str(s_buf);
-        V::sig_str(s_buf);
-        s_buf.push_str("}");
+        dict_sig_str::<K, V>(s_buf)
     }
     fn has_sig(sig: &str) -> bool {
-        if sig.starts_with("a{") {
-            let mut iter = SignatureIter::new(&sig[2..sig.len() - 1]);
-            K::has_sig(iter.next().unwrap()) && V::has_sig(iter.next().unwrap())
-        } else {
-            false
-        }
+        dict_has_sig::<K, V>(sig)
     }
 }
 
-impl<K: Marshal, V: Marshal> Marshal for std::collections::HashMap<K, V> {
+impl<K: Marshal, V: Marshal> Marshal for HashMap<K, V> {
     fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
-        // always align to 4
-        ctx.align_to(4);
+        marshal_dict_entries(self.iter(), ctx)
+    }
+}
 
-        let size_pos = ctx.buf.len();
-        ctx.buf.push(0);
-        ctx.buf.push(0);
-        ctx.buf.push(0);
-        ctx.buf.push(0);
+impl<K: Signature, V: Signature> Signature for BTreeMap<K, V> {
+    fn signature() -> crate::signature::Type {
+        dict_signature::<K, V>()
+    }
 
-        // always align to 8
-        ctx.align_to(8);
+    fn alignment() -> usize {
+        4
+    }
+    fn sig_str(s_buf: &mut SignatureBuffer) {
+        dict_sig_str::<K, V>(s_buf)
+    }
+    fn has_sig(sig: &str) -> bool {
+        dict_has_sig::<K, V>(sig)
+    }
+}
 
-        if self.is_empty() {
-            return Ok(());
-        }
+/// Entries are marshalled in key order, so the same map always results in the same bytes.
+impl<K: Marshal, V: Marshal> Marshal for BTreeMap<K, V> {
+    fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+        marshal_dict_entries(self.iter(), ctx)
+    }
+}
 
-        let size_before = ctx.buf.len();
-        for p in self.iter() {
-            // always align to 8
-            ctx.align_to(8);
-            p.0.marshal(ctx)?;
-            p.1.marshal(ctx)?;
+macro_rules! smart_pointer_impls {
+    ($($ptr:ident),*) => {$(
+        impl<S: Signature> Signature for $ptr<S> {
+            fn signature() -> crate::signature::Type {
+                S::signature()
+            }
+            fn alignment() -> usize {
+                S::alignment()
+            }
+            fn sig_str(s_buf: &mut SignatureBuffer) {
+                S::sig_str(s_buf)
+            }
+            fn has_sig(sig: &str) -> bool {
+                S::has_sig(sig)
+            }
         }
-        let size_of_content = ctx.buf.len() - size_before;
-        crate::wire::util::insert_u32(
-            ctx.byteorder,
-            size_of_content as u32,
-            &mut ctx.buf[size_pos..size_pos + 4],
-        );
-
-        Ok(())
-    }
+        impl<P: Marshal> Marshal for $ptr<P> {
+            fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+                self.as_ref().marshal(ctx)
+            }
+        }
+    )*};
 }
+smart_pointer_impls!(Box, Rc, Arc);
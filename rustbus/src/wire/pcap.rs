@@ -0,0 +1,270 @@
+//! Reading and writing captures of dbus traffic in the pcap format
+//!
+//! `busctl capture` and `dbus-monitor --pcap` write pcap files with the link type DBUS (231), where every packet is one
+//! whole marshalled message. Wireshark can dissect these. The `PcapWriter` writes such files, the `PcapReader` reads
+//! them back into `MarshalledMessage`s.
+//!
+//! Unix fds can not be captured, messages read from a capture never carry any.
+//!
+//! ```rust
+//! use rustbus::wire::pcap::{PcapReader, PcapWriter};
+//! use rustbus::MessageBuilder;
+//! use std::time::SystemTime;
+//!
+//! let mut msg = MessageBuilder::new()
+//!     .signal("io.killing.spark", "Ping", "/io/killing/spark")
+//!     .build();
+//! msg.dynheader.serial = Some(1);
+//!
+//! let mut writer = PcapWriter::new(Vec::new()).unwrap();
+//! writer.write_message(&msg, SystemTime::now()).unwrap();
+//! let capture = writer.into_inner();
+//!
+//! let mut reader = PcapReader::new(capture.as_slice()).unwrap();
+//! let (_time, read) = reader.next_message().unwrap().unwrap();
+//! assert_eq!(read.dynheader.member.as_deref(), Some("Ping"));
+//! ```
+
+use crate::message_builder::MarshalledMessage;
+use crate::wire::errors::{MarshalError, UnmarshalError};
+use crate::wire::unmarshal;
+
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+
+/// The link type for dbus messages
+pub const LINKTYPE_DBUS: u32 = 231;
+/// Packets may be as big as the biggest message
+pub const DEFAULT_SNAPLEN: u32 = crate::wire::MAX_MESSAGE_SIZE as u32;
+
+const MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const VERSION_MAJOR: u16 = 2;
+const VERSION_MINOR: u16 = 4;
+const FILE_HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+/// Errors that can occur while reading or writing a capture
+#[derive(Debug, Error)]
+pub enum PcapError {
+    #[error("An io error occured: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("An error occured while marshalling: {0}")]
+    Marshal(#[from] MarshalError),
+    #[error("An error occured while unmarshalling: {0}")]
+    Unmarshal(#[from] UnmarshalError),
+    /// The file does not start with a pcap magic number
+    #[error("Not a pcap file, the magic number is {0:#x}")]
+    InvalidMagic(u32),
+    /// The capture does not contain dbus messages
+    #[error("The capture has the link type {0} instead of DBUS")]
+    WrongLinkType(u32),
+    /// A packet was cut off by the snaplen of the capture
+    #[error("The packet was truncated from {0} to {1} bytes")]
+    TruncatedPacket(u32, u32),
+    /// A packet is bigger than the snaplen of the capture or than any message could be
+    #[error("The packet claims to be {0} bytes big, which is more than any packet in this capture can be")]
+    PacketTooBig(u32),
+    /// Messages need a serial to be written
+    #[error("The message has no serial")]
+    MissingSerial,
+    /// The file ended in the middle of a packet
+    #[error("The capture ended in the middle of a packet")]
+    UnexpectedEof,
+}
+
+/// Writes messages into a pcap capture. See the module docs.
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Write the file header to the writer
+    pub fn new(writer: W) -> Result<Self, PcapError> {
+        Self::with_snaplen(writer, DEFAULT_SNAPLEN)
+    }
+
+    /// Like `new` but announces a different maximum packet size in the file header. Messages are never truncated
+    /// by the writer though.
+    pub fn with_snaplen(mut writer: W, snaplen: u32) -> Result<Self, PcapError> {
+        let mut header = Vec::with_capacity(FILE_HEADER_LEN);
+        header.extend_from_slice(&MAGIC_MICROS.to_ne_bytes());
+        header.extend_from_slice(&VERSION_MAJOR.to_ne_bytes());
+        header.extend_from_slice(&VERSION_MINOR.to_ne_bytes());
+        // timezone offset and accuracy of the timestamps, always 0
+        header.extend_from_slice(&0i32.to_ne_bytes());
+        header.extend_from_slice(&0u32.to_ne_bytes());
+        header.extend_from_slice(&snaplen.to_ne_bytes());
+        header.extend_from_slice(&LINKTYPE_DBUS.to_ne_bytes());
+        writer.write_all(&header)?;
+        Ok(Self {
+            writer,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Marshal the message and append it as a packet. The message needs a serial.
+    pub fn write_message(
+        &mut self,
+        msg: &MarshalledMessage,
+        time: SystemTime,
+    ) -> Result<(), PcapError> {
+        let serial = msg.dynheader.serial.ok_or(PcapError::MissingSerial)?;
+        self.buf.clear();
+        crate::wire::marshal::marshal(msg, serial, &mut self.buf)?;
+        self.buf.extend_from_slice(msg.get_buf());
+        write_record(&mut self.writer, &self.buf, time)
+    }
+
+    /// Append raw bytes as a packet, e.g. a message as it was read from a connection
+    pub fn write_packet(&mut self, data: &[u8], time: SystemTime) -> Result<(), PcapError> {
+        write_record(&mut self.writer, data, time)
+    }
+
+    pub fn flush(&mut self) -> Result<(), PcapError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn write_record<W: Write>(writer: &mut W, data: &[u8], time: SystemTime) -> Result<(), PcapError> {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut header = Vec::with_capacity(RECORD_HEADER_LEN);
+    // the format only has room for 32 bit seconds
+    header.extend_from_slice(&(since_epoch.as_secs() as u32).to_ne_bytes());
+    header.extend_from_slice(&since_epoch.subsec_micros().to_ne_bytes());
+    header.extend_from_slice(&(data.len() as u32).to_ne_bytes());
+    header.extend_from_slice(&(data.len() as u32).to_ne_bytes());
+    writer.write_all(&header)?;
+    writer.write_all(data)?;
+    Ok(())
+}
+
+/// Reads the messages from a pcap capture. See the module docs.
+///
+/// It can also be used as an iterator over the messages.
+pub struct PcapReader<R: Read> {
+    reader: R,
+    swapped: bool,
+    nanos: bool,
+    snaplen: u32,
+}
+
+impl<R: Read> PcapReader<R> {
+    /// Read the file header. Captures written on machines with either byte order are accepted.
+    pub fn new(mut reader: R) -> Result<Self, PcapError> {
+        let mut header = [0u8; FILE_HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        let magic = u32::from_ne_bytes([header[0], header[1], header[2], header[3]]);
+        let (swapped, nanos) = match magic {
+            MAGIC_MICROS => (false, false),
+            MAGIC_NANOS => (false, true),
+            _ if magic.swap_bytes() == MAGIC_MICROS => (true, false),
+            _ if magic.swap_bytes() == MAGIC_NANOS => (true, true),
+            _ => return Err(PcapError::InvalidMagic(magic)),
+        };
+        let mut pcap = Self {
+            reader,
+            swapped,
+            nanos,
+            snaplen: 0,
+        };
+        pcap.snaplen = pcap.read_u32(&header[16..20]);
+        let linktype = pcap.read_u32(&header[20..24]);
+        if linktype != LINKTYPE_DBUS {
+            return Err(PcapError::WrongLinkType(linktype));
+        }
+        Ok(pcap)
+    }
+
+    /// The maximum packet size the capture announced
+    pub fn snaplen(&self) -> u32 {
+        self.snaplen
+    }
+
+    fn read_u32(&self, bytes: &[u8]) -> u32 {
+        let val = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if self.swapped {
+            val.swap_bytes()
+        } else {
+            val
+        }
+    }
+
+    /// Read the next packet as raw bytes. Returns None at the end of the capture.
+    pub fn next_packet(&mut self) -> Result<Option<(SystemTime, Vec<u8>)>, PcapError> {
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        // a clean end of the file is only allowed between packets
+        let mut filled = 0;
+        while filled < RECORD_HEADER_LEN {
+            match self.reader.read(&mut header[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(PcapError::UnexpectedEof),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let secs = self.read_u32(&header[0..4]);
+        let fraction = self.read_u32(&header[4..8]);
+        let incl_len = self.read_u32(&header[8..12]);
+        let orig_len = self.read_u32(&header[12..16]);
+
+        // check before allocating, the length could be anything in a broken capture. So could the snaplen.
+        let max_len = self.snaplen.min(crate::wire::MAX_MESSAGE_SIZE as u32);
+        if incl_len > max_len {
+            return Err(PcapError::PacketTooBig(incl_len));
+        }
+        let mut data = vec![0u8; incl_len as usize];
+        self.reader.read_exact(&mut data).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                PcapError::UnexpectedEof
+            } else {
+                PcapError::Io(e)
+            }
+        })?;
+        if incl_len != orig_len {
+            return Err(PcapError::TruncatedPacket(orig_len, incl_len));
+        }
+
+        let fraction = if self.nanos {
+            Duration::from_nanos(fraction as u64)
+        } else {
+            Duration::from_micros(fraction as u64)
+        };
+        let time = UNIX_EPOCH + Duration::from_secs(secs as u64) + fraction;
+        Ok(Some((time, data)))
+    }
+
+    /// Read the next packet and unmarshal it. Returns None at the end of the capture.
+    pub fn next_message(&mut self) -> Result<Option<(SystemTime, MarshalledMessage)>, PcapError> {
+        let (time, data) = match self.next_packet()? {
+            Some(packet) => packet,
+            None => return Ok(None),
+        };
+        let (hdrbytes, header) = unmarshal::unmarshal_header(&data, 0)?;
+        let (dynhdrbytes, dynheader) =
+            unmarshal::unmarshal_dynamic_header(&header, &data, hdrbytes)?;
+        let (bytes_used, msg) =
+            unmarshal::unmarshal_next_message(&header, dynheader, &data, hdrbytes + dynhdrbytes)?;
+        if data.len() != hdrbytes + dynhdrbytes + bytes_used {
+            return Err(UnmarshalError::NotAllBytesUsed.into());
+        }
+        Ok(Some((time, msg)))
+    }
+}
+
+impl<R: Read> Iterator for PcapReader<R> {
+    type Item = Result<(SystemTime, MarshalledMessage), PcapError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_message().transpose()
+    }
+}
@@ -0,0 +1,1770 @@
+//! Marshalling types that implement serde's `Serialize` and `Deserialize`
+//!
+//! This module is only available with the `serde` feature. It maps serde's data model onto dbus like this:
+//! * bool, the integers, the floats and strings map to the base types. There is no 8 bit signed integer or 32 bit float
+//!   in dbus, `i8` is marshalled as `n` and `f32` as `d`. A `char` is marshalled as a string.
+//! * Structs and tuples map to structs `(..)`
+//! * Sequences map to arrays `a..`, serde's byte buffers map to `ay`
+//! * Maps map to dicts `a{..}`. The keys must be base types.
+//! * Enums map to variants. A unit variant contains the name of the enum variant as a string, the other variants contain
+//!   a struct of the name followed by the fields. So `Shape::Circle(2.0)` becomes `<("Circle", 2.0)>`. The signature of
+//!   the fields is inferred from their values, empty sequences and maps in them are marshalled as `av` and `a{sv}`.
+//! * `Some(x)` and newtype structs are marshalled as their content. `None` and unit values can not be represented.
+//! * Unix fds are not supported
+//!
+//! Values on the wire do not describe themselves, the signature has to be known to marshal and unmarshal them.
+//! `signature_of_type` infers it from the `Deserialize` impl of a type, `signature_of_value` from a value.
+//!
+//! The `SerdeParam` wrapper implements `Marshal` and `Unmarshal`, so serde types can be used with
+//! `MarshalledMessageBody::push_param` and the `MessageBodyParser`:
+//! ```rust
+//! use rustbus::wire::serde::SerdeParam;
+//! use rustbus::MessageBuilder;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Point {
+//!     x: i32,
+//!     y: i32,
+//!     labels: Vec<String>,
+//! }
+//!
+//! let point = Point { x: 1, y: 2, labels: vec!["origin".to_owned()] };
+//! let expected = Point { x: 1, y: 2, labels: vec!["origin".to_owned()] };
+//! let mut msg = MessageBuilder::new()
+//!     .signal("io.killing.spark", "Moved", "/io/killing/spark")
+//!     .build();
+//! msg.body.push_param(SerdeParam(point)).unwrap();
+//! assert_eq!(msg.get_sig(), "(iias)");
+//!
+//! let SerdeParam(read) = msg.body.parser().get::<SerdeParam<Point>>().unwrap();
+//! assert_eq!(read, expected);
+//! ```
+//!
+//! Types whose signature can not be inferred, that only implement `Serialize` or that borrow from the message can be
+//! pushed with `MarshalledMessageBody::push_serde` and read with `MessageBodyParser::get_serde` instead.
+
+use crate::params;
+use crate::signature::{Base, Container, StructTypes, Type};
+use crate::wire::errors::{MarshalError, UnmarshalError};
+use crate::wire::marshal::traits::{Marshal, Signature};
+use crate::wire::marshal::MarshalContext;
+use crate::wire::unmarshal::traits::Unmarshal;
+use crate::wire::unmarshal::{UnmarshalContext, UnmarshalResult};
+use crate::wire::util;
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use serde::Deserialize;
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::{Mutex, OnceLock};
+
+use thiserror::Error;
+
+/// Errors that can occur while marshalling serde types
+#[derive(Debug, Error)]
+pub enum SerdeError {
+    /// An error reported by a `Serialize` or `Deserialize` impl
+    #[error("{0}")]
+    Custom(String),
+    #[error("An error occured while marshalling: {0}")]
+    Marshal(#[from] MarshalError),
+    #[error("An error occured while unmarshalling: {0}")]
+    Unmarshal(#[from] UnmarshalError),
+    /// The value does not fit the signature it is marshalled or unmarshalled with
+    #[error("The value does not fit the signature {0}")]
+    WrongSignature(String),
+    /// The value has no representation in dbus
+    #[error("{0} can not be represented in dbus")]
+    Unsupported(&'static str),
+    #[error("The signature can not be inferred: {0}")]
+    CannotInferSignature(&'static str),
+}
+
+impl ser::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        SerdeError::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        SerdeError::Custom(msg.to_string())
+    }
+}
+
+impl From<SerdeError> for MarshalError {
+    fn from(e: SerdeError) -> Self {
+        match e {
+            SerdeError::Marshal(e) => e,
+            other => MarshalError::Serde(other.to_string()),
+        }
+    }
+}
+
+impl From<SerdeError> for UnmarshalError {
+    fn from(e: SerdeError) -> Self {
+        match e {
+            SerdeError::Unmarshal(e) => e,
+            other => UnmarshalError::Serde(other.to_string()),
+        }
+    }
+}
+
+fn sig_string(typ: &Type) -> String {
+    let mut sig = String::new();
+    typ.to_str(&mut sig);
+    sig
+}
+
+fn wrong_sig(typ: &Type) -> SerdeError {
+    SerdeError::WrongSignature(sig_string(typ))
+}
+
+fn string_type() -> Type {
+    Type::Base(Base::String)
+}
+
+/// Marshal the value with the signature
+pub fn to_context<T: Serialize + ?Sized>(
+    value: &T,
+    sig: &Type,
+    ctx: &mut MarshalContext,
+) -> Result<(), SerdeError> {
+    value.serialize(Serializer::new(ctx, sig))
+}
+
+/// Unmarshal a value with the signature
+pub fn from_context<'de, T: Deserialize<'de>>(
+    sig: &Type,
+    ctx: &mut UnmarshalContext<'_, 'de>,
+) -> Result<T, SerdeError> {
+    T::deserialize(Deserializer::new(ctx, sig))
+}
+
+/// Wraps a serde type so it can be used as a param. See the module docs.
+///
+/// The signature is inferred with `signature_of_type`, once per type. If it can not be inferred, marshalling the
+/// wrapper fails and the parser reports a wrong signature. `Signature::signature` has no way to report an error though
+/// and panics for these types. Containers use it for the signature of their elements, so e.g. pushing a
+/// `Vec<SerdeParam<T>>` panics too. Check `SerdeParam::try_signature` first if the type is not known to work.
+///
+/// See `MarshalledMessageBody::push_serde` and `MessageBodyParser::get_serde` for types that can not be wrapped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SerdeParam<T>(pub T);
+
+impl<T: DeserializeOwned + 'static> SerdeParam<T> {
+    /// The signature the wrapper uses, or why it can not be inferred
+    pub fn try_signature() -> Result<Type, SerdeError> {
+        // the marshalling traits ask for the signature a lot, only successful inferences are cached though
+        static CACHE: OnceLock<Mutex<HashMap<TypeId, Type>>> = OnceLock::new();
+        let cache = CACHE.get_or_init(Default::default);
+        if let Some(typ) = cache.lock().unwrap().get(&TypeId::of::<T>()) {
+            return Ok(typ.clone());
+        }
+        let typ = signature_of_type::<T>()?;
+        cache.lock().unwrap().insert(TypeId::of::<T>(), typ.clone());
+        Ok(typ)
+    }
+}
+
+impl<T: DeserializeOwned + 'static> Signature for SerdeParam<T> {
+    fn signature() -> Type {
+        Self::try_signature().unwrap_or_else(|e| {
+            panic!(
+                "The signature of {} can not be inferred: {}",
+                std::any::type_name::<T>(),
+                e
+            )
+        })
+    }
+    fn alignment() -> usize {
+        // marshalling the value reports the error
+        Self::try_signature()
+            .map(|typ| typ.get_alignment())
+            .unwrap_or(1)
+    }
+    fn has_sig(sig: &str) -> bool {
+        Self::try_signature()
+            .map(|typ| sig_string(&typ) == sig)
+            .unwrap_or(false)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + 'static> Marshal for SerdeParam<T> {
+    fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+        to_context(&self.0, &Self::try_signature()?, ctx)?;
+        Ok(())
+    }
+}
+
+impl<'buf, 'fds, T: DeserializeOwned + 'static> Unmarshal<'buf, 'fds> for SerdeParam<T> {
+    fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> UnmarshalResult<Self> {
+        let start_offset = ctx.offset;
+        let value = from_context(&Self::try_signature()?, ctx)?;
+        Ok((ctx.offset - start_offset, SerdeParam(value)))
+    }
+}
+
+//--------
+// Marshalling
+//--------
+
+/// Marshals a value into the context, checking it against the signature
+pub struct Serializer<'a, 'fds, 'buf> {
+    ctx: &'a mut MarshalContext<'fds, 'buf>,
+    sig: &'a Type,
+}
+
+impl<'a, 'fds, 'buf> Serializer<'a, 'fds, 'buf> {
+    pub fn new(ctx: &'a mut MarshalContext<'fds, 'buf>, sig: &'a Type) -> Self {
+        Self { ctx, sig }
+    }
+
+    fn marshal_base<T: Marshal>(self, base: Base, value: T) -> Result<(), SerdeError> {
+        if *self.sig != Type::Base(base) {
+            return Err(wrong_sig(self.sig));
+        }
+        value.marshal(self.ctx)?;
+        Ok(())
+    }
+
+    fn expect_variant(&self) -> Result<(), SerdeError> {
+        match self.sig {
+            Type::Container(Container::Variant) => Ok(()),
+            _ => Err(wrong_sig(self.sig)),
+        }
+    }
+
+    fn start_array(&mut self, alignment: usize) -> (usize, usize) {
+        self.ctx.align_to(4);
+        let len_pos = self.ctx.buf.len();
+        self.ctx.buf.extend_from_slice(&[0; 4]);
+        // align even if there are no elements
+        self.ctx.align_to(alignment);
+        (len_pos, self.ctx.buf.len())
+    }
+}
+
+fn write_variant_sig(ctx: &mut MarshalContext, content: &Type) -> Result<(), SerdeError> {
+    let sig = sig_string(content);
+    params::validate_signature(&sig).map_err(MarshalError::from)?;
+    util::write_signature(&sig, ctx.buf);
+    Ok(())
+}
+
+fn finish_array(ctx: &mut MarshalContext, len_pos: usize, start: usize) -> Result<(), SerdeError> {
+    let len = ctx.buf.len() - start;
+    util::insert_array_len(ctx.byteorder, len, ctx.buf, len_pos)?;
+    Ok(())
+}
+
+impl<'a, 'fds, 'buf> ser::Serializer for Serializer<'a, 'fds, 'buf> {
+    type Ok = ();
+    type Error = SerdeError;
+    type SerializeSeq = SeqSerializer<'a, 'fds, 'buf>;
+    type SerializeTuple = StructSerializer<'a, 'fds, 'buf>;
+    type SerializeTupleStruct = StructSerializer<'a, 'fds, 'buf>;
+    type SerializeTupleVariant = VariantSerializer<'a, 'fds, 'buf>;
+    type SerializeMap = MapSerializer<'a, 'fds, 'buf>;
+    type SerializeStruct = StructSerializer<'a, 'fds, 'buf>;
+    type SerializeStructVariant = VariantSerializer<'a, 'fds, 'buf>;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<(), SerdeError> {
+        self.marshal_base(Base::Boolean, v)
+    }
+    fn serialize_i8(self, v: i8) -> Result<(), SerdeError> {
+        self.marshal_base(Base::Int16, v as i16)
+    }
+    fn serialize_i16(self, v: i16) -> Result<(), SerdeError> {
+        self.marshal_base(Base::Int16, v)
+    }
+    fn serialize_i32(self, v: i32) -> Result<(), SerdeError> {
+        self.marshal_base(Base::Int32, v)
+    }
+    fn serialize_i64(self, v: i64) -> Result<(), SerdeError> {
+        self.marshal_base(Base::Int64, v)
+    }
+    fn serialize_u8(self, v: u8) -> Result<(), SerdeError> {
+        self.marshal_base(Base::Byte, v)
+    }
+    fn serialize_u16(self, v: u16) -> Result<(), SerdeError> {
+        self.marshal_base(Base::Uint16, v)
+    }
+    fn serialize_u32(self, v: u32) -> Result<(), SerdeError> {
+        self.marshal_base(Base::Uint32, v)
+    }
+    fn serialize_u64(self, v: u64) -> Result<(), SerdeError> {
+        self.marshal_base(Base::Uint64, v)
+    }
+    fn serialize_f32(self, v: f32) -> Result<(), SerdeError> {
+        self.marshal_base(Base::Double, v as f64)
+    }
+    fn serialize_f64(self, v: f64) -> Result<(), SerdeError> {
+        self.marshal_base(Base::Double, v)
+    }
+    fn serialize_char(self, v: char) -> Result<(), SerdeError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SerdeError> {
+        match self.sig {
+            Type::Base(Base::String) => v.marshal(self.ctx)?,
+            Type::Base(Base::ObjectPath) => {
+                params::validate_object_path(v).map_err(MarshalError::from)?;
+                v.marshal(self.ctx)?;
+            }
+            Type::Base(Base::Signature) => {
+                params::validate_signature(v).map_err(MarshalError::from)?;
+                util::write_signature(v, self.ctx.buf);
+            }
+            _ => return Err(wrong_sig(self.sig)),
+        }
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), SerdeError> {
+        match self.sig {
+            Type::Container(Container::Array(elem)) if **elem == Type::Base(Base::Byte) => {
+                v.marshal(self.ctx)?;
+                Ok(())
+            }
+            _ => Err(wrong_sig(self.sig)),
+        }
+    }
+
+    fn serialize_none(self) -> Result<(), SerdeError> {
+        Err(SerdeError::Unsupported("None"))
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), SerdeError> {
+        Err(SerdeError::Unsupported("A unit value"))
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerdeError> {
+        Err(SerdeError::Unsupported("A unit struct"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), SerdeError> {
+        self.expect_variant()?;
+        write_variant_sig(self.ctx, &string_type())?;
+        variant.marshal(self.ctx)?;
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.expect_variant()?;
+        let field = signature_of_variant_field(value)?;
+        let content = Type::Container(Container::Struct(
+            StructTypes::new(vec![string_type(), field.clone()]).map_err(MarshalError::from)?,
+        ));
+        write_variant_sig(self.ctx, &content)?;
+        self.ctx.align_to(8);
+        variant.marshal(self.ctx)?;
+        value.serialize(Serializer::new(self.ctx, &field))
+    }
+
+    fn serialize_seq(mut self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        match self.sig {
+            Type::Container(Container::Array(elem)) => {
+                let (len_pos, start) = self.start_array(elem.get_alignment());
+                Ok(SeqSerializer {
+                    ctx: self.ctx,
+                    elem,
+                    len_pos,
+                    start,
+                })
+            }
+            _ => Err(wrong_sig(self.sig)),
+        }
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        match self.sig {
+            Type::Container(Container::Struct(types)) => {
+                self.ctx.align_to(8);
+                Ok(StructSerializer {
+                    ctx: self.ctx,
+                    sig: self.sig,
+                    types: types.as_ref(),
+                    index: 0,
+                })
+            }
+            _ => Err(wrong_sig(self.sig)),
+        }
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        self.expect_variant()?;
+        VariantSerializer::new(self.ctx, variant)
+    }
+
+    fn serialize_map(mut self, _len: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        match self.sig {
+            Type::Container(Container::Dict(key, value)) => {
+                let (len_pos, start) = self.start_array(8);
+                Ok(MapSerializer {
+                    ctx: self.ctx,
+                    key: Type::Base(*key),
+                    value,
+                    len_pos,
+                    start,
+                })
+            }
+            _ => Err(wrong_sig(self.sig)),
+        }
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, SerdeError> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        self.expect_variant()?;
+        VariantSerializer::new(self.ctx, variant)
+    }
+}
+
+pub struct SeqSerializer<'a, 'fds, 'buf> {
+    ctx: &'a mut MarshalContext<'fds, 'buf>,
+    elem: &'a Type,
+    len_pos: usize,
+    start: usize,
+}
+
+impl ser::SerializeSeq for SeqSerializer<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(Serializer::new(self.ctx, self.elem))
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        finish_array(self.ctx, self.len_pos, self.start)
+    }
+}
+
+pub struct MapSerializer<'a, 'fds, 'buf> {
+    ctx: &'a mut MarshalContext<'fds, 'buf>,
+    key: Type,
+    value: &'a Type,
+    len_pos: usize,
+    start: usize,
+}
+
+impl ser::SerializeMap for MapSerializer<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        // every entry is aligned like a struct
+        self.ctx.align_to(8);
+        key.serialize(Serializer::new(self.ctx, &self.key))
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(Serializer::new(self.ctx, self.value))
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        finish_array(self.ctx, self.len_pos, self.start)
+    }
+}
+
+pub struct StructSerializer<'a, 'fds, 'buf> {
+    ctx: &'a mut MarshalContext<'fds, 'buf>,
+    sig: &'a Type,
+    types: &'a [Type],
+    index: usize,
+}
+
+impl StructSerializer<'_, '_, '_> {
+    fn field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let typ = self
+            .types
+            .get(self.index)
+            .ok_or_else(|| wrong_sig(self.sig))?;
+        self.index += 1;
+        value.serialize(Serializer::new(self.ctx, typ))
+    }
+    fn finish(self) -> Result<(), SerdeError> {
+        if self.index != self.types.len() {
+            return Err(wrong_sig(self.sig));
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for StructSerializer<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.field(value)
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for StructSerializer<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.field(value)
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for StructSerializer<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.field(value)
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+/// Marshals the fields of tuple and struct variants.
+///
+/// The signature of the variant is only known after all fields were seen, but it has to be written before them. So the
+/// content is collected in a separate buffer. The content is a struct, which always starts at an offset aligned to 8,
+/// so the padding in that buffer is the same as it will be in the message.
+pub struct VariantSerializer<'a, 'fds, 'buf> {
+    ctx: &'a mut MarshalContext<'fds, 'buf>,
+    fields: Vec<Type>,
+    content: Vec<u8>,
+}
+
+impl<'a, 'fds, 'buf> VariantSerializer<'a, 'fds, 'buf> {
+    fn new(ctx: &'a mut MarshalContext<'fds, 'buf>, variant: &str) -> Result<Self, SerdeError> {
+        let mut content = Vec::new();
+        util::write_string(variant, ctx.byteorder, &mut content);
+        Ok(Self {
+            ctx,
+            fields: vec![string_type()],
+            content,
+        })
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let typ = signature_of_variant_field(value)?;
+        let mut content_ctx = MarshalContext {
+            fds: self.ctx.fds,
+            buf: &mut self.content,
+            byteorder: self.ctx.byteorder,
+        };
+        value.serialize(Serializer::new(&mut content_ctx, &typ))?;
+        self.fields.push(typ);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), SerdeError> {
+        let content = Type::Container(Container::Struct(
+            StructTypes::new(self.fields).map_err(MarshalError::from)?,
+        ));
+        write_variant_sig(self.ctx, &content)?;
+        self.ctx.align_to(8);
+        self.ctx.buf.extend_from_slice(&self.content);
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.field(value)
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.field(value)
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+//--------
+// Unmarshalling
+//--------
+
+/// Unmarshals a value from the context following the signature
+pub struct Deserializer<'a, 'fds, 'de> {
+    ctx: &'a mut UnmarshalContext<'fds, 'de>,
+    sig: &'a Type,
+}
+
+impl<'a, 'fds, 'de> Deserializer<'a, 'fds, 'de> {
+    pub fn new(ctx: &'a mut UnmarshalContext<'fds, 'de>, sig: &'a Type) -> Self {
+        Self { ctx, sig }
+    }
+
+    fn unmarshal<T: Unmarshal<'de, 'fds>>(&mut self) -> Result<T, SerdeError> {
+        Ok(T::unmarshal(self.ctx)?.1)
+    }
+
+    fn unmarshal_signature(&mut self) -> Result<&'de str, SerdeError> {
+        let buf: &'de [u8] = self.ctx.buf;
+        let (bytes, sig) = util::unmarshal_signature(&buf[self.ctx.offset..])?;
+        params::validate_signature(sig).map_err(UnmarshalError::from)?;
+        self.ctx.offset += bytes;
+        Ok(sig)
+    }
+
+    /// Read the signature of a variant, which must be a single complete type
+    fn variant_sig(&mut self) -> Result<Type, SerdeError> {
+        let sig = self.unmarshal_signature()?;
+        let mut types = Type::parse_description(sig).map_err(UnmarshalError::from)?;
+        if types.len() != 1 {
+            return Err(UnmarshalError::WrongSignature.into());
+        }
+        Ok(types.remove(0))
+    }
+
+    /// Read the length of an array and return the offset where its content ends
+    fn start_array(&mut self, alignment: usize) -> Result<usize, SerdeError> {
+        self.ctx.align_to(4)?;
+        let (_, len) = util::parse_array_len(&self.ctx.buf[self.ctx.offset..], self.ctx.byteorder)?;
+        self.ctx.offset += 4;
+        self.ctx.align_to(alignment)?;
+        let end = self.ctx.offset + len;
+        if end > self.ctx.buf.len() {
+            return Err(UnmarshalError::NotEnoughBytesForCollection.into());
+        }
+        Ok(end)
+    }
+}
+
+fn check_array_end(ctx: &UnmarshalContext, end: usize) -> Result<(), SerdeError> {
+    if ctx.offset != end {
+        return Err(UnmarshalError::NotEnoughBytesForCollection.into());
+    }
+    Ok(())
+}
+
+impl<'a, 'fds, 'de> de::Deserializer<'de> for Deserializer<'a, 'fds, 'de> {
+    type Error = SerdeError;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.sig {
+            Type::Base(base) => match base {
+                Base::Byte => visitor.visit_u8(self.unmarshal()?),
+                Base::Int16 => visitor.visit_i16(self.unmarshal()?),
+                Base::Uint16 => visitor.visit_u16(self.unmarshal()?),
+                Base::Int32 => visitor.visit_i32(self.unmarshal()?),
+                Base::Uint32 => visitor.visit_u32(self.unmarshal()?),
+                Base::Int64 => visitor.visit_i64(self.unmarshal()?),
+                Base::Uint64 => visitor.visit_u64(self.unmarshal()?),
+                Base::Double => visitor.visit_f64(self.unmarshal()?),
+                Base::Boolean => visitor.visit_bool(self.unmarshal()?),
+                Base::String => visitor.visit_borrowed_str(self.unmarshal()?),
+                Base::ObjectPath => {
+                    let path: crate::wire::ObjectPath<&'de str> = self.unmarshal()?;
+                    visitor.visit_borrowed_str(path.into_inner())
+                }
+                Base::Signature => visitor.visit_borrowed_str(self.unmarshal_signature()?),
+                Base::UnixFd => Err(SerdeError::Unsupported("A unix fd")),
+            },
+            Type::Container(Container::Array(elem)) => {
+                let end = self.start_array(elem.get_alignment())?;
+                let value = visitor.visit_seq(SeqAccess {
+                    ctx: &mut *self.ctx,
+                    elem,
+                    end,
+                })?;
+                check_array_end(self.ctx, end)?;
+                Ok(value)
+            }
+            Type::Container(Container::Dict(key, value)) => {
+                let end = self.start_array(8)?;
+                let result = visitor.visit_map(MapAccess {
+                    ctx: &mut *self.ctx,
+                    key: Type::Base(*key),
+                    value,
+                    end,
+                })?;
+                check_array_end(self.ctx, end)?;
+                Ok(result)
+            }
+            Type::Container(Container::Struct(types)) => {
+                self.ctx.align_to(8)?;
+                let mut fields = StructAccess {
+                    ctx: &mut *self.ctx,
+                    types: types.as_ref(),
+                    index: 0,
+                };
+                let value = visitor.visit_seq(&mut fields)?;
+                if fields.index != types.as_ref().len() {
+                    return Err(wrong_sig(self.sig));
+                }
+                Ok(value)
+            }
+            // outside of enums variants are transparent
+            Type::Container(Container::Variant) => {
+                let content = self.variant_sig()?;
+                Deserializer::new(self.ctx, &content).deserialize_any(visitor)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.sig {
+            Type::Container(Container::Array(elem)) if **elem == Type::Base(Base::Byte) => {
+                visitor.visit_borrowed_bytes(self.unmarshal::<&'de [u8]>()?)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        if *self.sig != Type::Container(Container::Variant) {
+            return Err(wrong_sig(self.sig));
+        }
+        let content = self.variant_sig()?;
+        let fields = match &content {
+            Type::Base(Base::String) => &[][..],
+            Type::Container(Container::Struct(types)) if types.as_ref()[0] == string_type() => {
+                self.ctx.align_to(8)?;
+                &types.as_ref()[1..]
+            }
+            _ => return Err(wrong_sig(&content)),
+        };
+        let variant = self.unmarshal()?;
+        visitor.visit_enum(EnumAccess {
+            ctx: self.ctx,
+            variant,
+            content: &content,
+            fields,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct SeqAccess<'a, 'fds, 'de> {
+    ctx: &'a mut UnmarshalContext<'fds, 'de>,
+    elem: &'a Type,
+    end: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'_, '_, 'de> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        if self.ctx.offset >= self.end {
+            return Ok(None);
+        }
+        seed.deserialize(Deserializer::new(self.ctx, self.elem))
+            .map(Some)
+    }
+}
+
+struct MapAccess<'a, 'fds, 'de> {
+    ctx: &'a mut UnmarshalContext<'fds, 'de>,
+    key: Type,
+    value: &'a Type,
+    end: usize,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'_, '_, 'de> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        if self.ctx.offset >= self.end {
+            return Ok(None);
+        }
+        self.ctx.align_to(8)?;
+        seed.deserialize(Deserializer::new(self.ctx, &self.key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, SerdeError> {
+        seed.deserialize(Deserializer::new(self.ctx, self.value))
+    }
+}
+
+struct StructAccess<'a, 'fds, 'de> {
+    ctx: &'a mut UnmarshalContext<'fds, 'de>,
+    types: &'a [Type],
+    index: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for StructAccess<'_, '_, 'de> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        let typ = match self.types.get(self.index) {
+            Some(typ) => typ,
+            None => return Ok(None),
+        };
+        self.index += 1;
+        seed.deserialize(Deserializer::new(self.ctx, typ)).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.types.len() - self.index)
+    }
+}
+
+struct EnumAccess<'a, 'fds, 'de> {
+    ctx: &'a mut UnmarshalContext<'fds, 'de>,
+    variant: &'de str,
+    // the whole content of the variant, for errors
+    content: &'a Type,
+    fields: &'a [Type],
+}
+
+impl<'a, 'fds, 'de> de::EnumAccess<'de> for EnumAccess<'a, 'fds, 'de> {
+    type Error = SerdeError;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), SerdeError> {
+        let name = de::value::BorrowedStrDeserializer::<SerdeError>::new(self.variant);
+        Ok((seed.deserialize(name)?, self))
+    }
+}
+
+impl<'de> EnumAccess<'_, '_, 'de> {
+    fn visit_fields<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let mut fields = StructAccess {
+            ctx: self.ctx,
+            types: self.fields,
+            index: 0,
+        };
+        let value = visitor.visit_seq(&mut fields)?;
+        if fields.index != self.fields.len() {
+            return Err(wrong_sig(self.content));
+        }
+        Ok(value)
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for EnumAccess<'_, '_, 'de> {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        if !self.fields.is_empty() {
+            return Err(wrong_sig(self.content));
+        }
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        match self.fields {
+            [field] => seed.deserialize(Deserializer::new(self.ctx, field)),
+            _ => Err(wrong_sig(self.content)),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.visit_fields(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.visit_fields(visitor)
+    }
+}
+
+//--------
+// Signature inference
+//--------
+
+/// Infer the signature of a value from its `Serialize` impl.
+///
+/// This fails for empty sequences and maps, because they do not show the type of their elements. Enums always have
+/// the signature `v`.
+pub fn signature_of_value<T: Serialize + ?Sized>(value: &T) -> Result<Type, SerdeError> {
+    value.serialize(SignatureSerializer { lenient: false })
+}
+
+/// The signature of the content of an enum variant. Only the value is known here, so empty collections get the
+/// placeholder signatures `av` and `a{sv}` instead of failing. The receiver finds no elements in them anyway.
+fn signature_of_variant_field<T: Serialize + ?Sized>(value: &T) -> Result<Type, SerdeError> {
+    value.serialize(SignatureSerializer { lenient: true })
+}
+
+fn empty_array_sig() -> Type {
+    Type::Container(Container::Array(Box::new(Type::Container(
+        Container::Variant,
+    ))))
+}
+
+fn empty_dict_sig() -> Type {
+    Type::Container(Container::Dict(
+        Base::String,
+        Box::new(Type::Container(Container::Variant)),
+    ))
+}
+
+struct SignatureSerializer {
+    // fill in placeholders for empty collections, see signature_of_variant_field
+    lenient: bool,
+}
+
+fn base_sig(base: Base) -> Result<Type, SerdeError> {
+    Ok(Type::Base(base))
+}
+
+fn variant_sig() -> Result<Type, SerdeError> {
+    Ok(Type::Container(Container::Variant))
+}
+
+fn struct_sig(fields: Vec<Type>) -> Result<Type, SerdeError> {
+    StructTypes::new(fields)
+        .map(|types| Type::Container(Container::Struct(types)))
+        .map_err(|_| SerdeError::Unsupported("An empty struct"))
+}
+
+fn dict_sig(key: Type, value: Type) -> Result<Type, SerdeError> {
+    match key {
+        Type::Base(key) => Ok(Type::Container(Container::Dict(key, Box::new(value)))),
+        Type::Container(_) => Err(SerdeError::Unsupported("A map key that is not a base type")),
+    }
+}
+
+impl ser::Serializer for SignatureSerializer {
+    type Ok = Type;
+    type Error = SerdeError;
+    type SerializeSeq = SeqSignature;
+    type SerializeTuple = StructSignature;
+    type SerializeTupleStruct = StructSignature;
+    type SerializeTupleVariant = VariantSignature;
+    type SerializeMap = MapSignature;
+    type SerializeStruct = StructSignature;
+    type SerializeStructVariant = VariantSignature;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<Type, SerdeError> {
+        base_sig(Base::Boolean)
+    }
+    fn serialize_i8(self, _v: i8) -> Result<Type, SerdeError> {
+        base_sig(Base::Int16)
+    }
+    fn serialize_i16(self, _v: i16) -> Result<Type, SerdeError> {
+        base_sig(Base::Int16)
+    }
+    fn serialize_i32(self, _v: i32) -> Result<Type, SerdeError> {
+        base_sig(Base::Int32)
+    }
+    fn serialize_i64(self, _v: i64) -> Result<Type, SerdeError> {
+        base_sig(Base::Int64)
+    }
+    fn serialize_u8(self, _v: u8) -> Result<Type, SerdeError> {
+        base_sig(Base::Byte)
+    }
+    fn serialize_u16(self, _v: u16) -> Result<Type, SerdeError> {
+        base_sig(Base::Uint16)
+    }
+    fn serialize_u32(self, _v: u32) -> Result<Type, SerdeError> {
+        base_sig(Base::Uint32)
+    }
+    fn serialize_u64(self, _v: u64) -> Result<Type, SerdeError> {
+        base_sig(Base::Uint64)
+    }
+    fn serialize_f32(self, _v: f32) -> Result<Type, SerdeError> {
+        base_sig(Base::Double)
+    }
+    fn serialize_f64(self, _v: f64) -> Result<Type, SerdeError> {
+        base_sig(Base::Double)
+    }
+    fn serialize_char(self, _v: char) -> Result<Type, SerdeError> {
+        base_sig(Base::String)
+    }
+    fn serialize_str(self, _v: &str) -> Result<Type, SerdeError> {
+        base_sig(Base::String)
+    }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Type, SerdeError> {
+        Ok(Type::Container(Container::Array(Box::new(Type::Base(
+            Base::Byte,
+        )))))
+    }
+    fn serialize_none(self) -> Result<Type, SerdeError> {
+        Err(SerdeError::Unsupported("None"))
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Type, SerdeError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Type, SerdeError> {
+        Err(SerdeError::Unsupported("A unit value"))
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Type, SerdeError> {
+        Err(SerdeError::Unsupported("A unit struct"))
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<Type, SerdeError> {
+        variant_sig()
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Type, SerdeError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Type, SerdeError> {
+        variant_sig()
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<SeqSignature, SerdeError> {
+        Ok(SeqSignature {
+            elem: None,
+            lenient: self.lenient,
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<StructSignature, SerdeError> {
+        Ok(StructSignature {
+            fields: Vec::with_capacity(len),
+            lenient: self.lenient,
+        })
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<StructSignature, SerdeError> {
+        self.serialize_tuple(len)
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<VariantSignature, SerdeError> {
+        Ok(VariantSignature)
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSignature, SerdeError> {
+        Ok(MapSignature {
+            key: None,
+            value: None,
+            lenient: self.lenient,
+        })
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<StructSignature, SerdeError> {
+        self.serialize_tuple(len)
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<VariantSignature, SerdeError> {
+        Ok(VariantSignature)
+    }
+}
+
+/// All elements must have the same signature
+fn merge_sig(seen: &mut Option<Type>, typ: Type) -> Result<(), SerdeError> {
+    let merged = match seen.take() {
+        Some(seen) => unify_sig(seen, typ).ok_or(SerdeError::CannotInferSignature(
+            "the elements of a collection have different signatures",
+        ))?,
+        None => typ,
+    };
+    *seen = Some(merged);
+    Ok(())
+}
+
+/// The common signature of two elements of a collection. These can only differ where one of them contains an empty
+/// collection with a placeholder signature, the other one shows the real signature there.
+fn unify_sig(left: Type, right: Type) -> Option<Type> {
+    if left == right {
+        return Some(left);
+    }
+    if left == empty_array_sig() || left == empty_dict_sig() {
+        return Some(right);
+    }
+    if right == empty_array_sig() || right == empty_dict_sig() {
+        return Some(left);
+    }
+    match (left, right) {
+        (Type::Container(Container::Array(left)), Type::Container(Container::Array(right))) => {
+            let elem = unify_sig(*left, *right)?;
+            Some(Type::Container(Container::Array(Box::new(elem))))
+        }
+        (
+            Type::Container(Container::Dict(key, left)),
+            Type::Container(Container::Dict(other_key, right)),
+        ) if key == other_key => {
+            let value = unify_sig(*left, *right)?;
+            Some(Type::Container(Container::Dict(key, Box::new(value))))
+        }
+        (Type::Container(Container::Struct(left)), Type::Container(Container::Struct(right)))
+            if left.as_ref().len() == right.as_ref().len() =>
+        {
+            let fields = left
+                .as_ref()
+                .iter()
+                .zip(right.as_ref())
+                .map(|(left, right)| unify_sig(left.clone(), right.clone()))
+                .collect::<Option<Vec<_>>>()?;
+            Some(Type::Container(Container::Struct(
+                StructTypes::new(fields).ok()?,
+            )))
+        }
+        _ => None,
+    }
+}
+
+struct SeqSignature {
+    elem: Option<Type>,
+    lenient: bool,
+}
+
+impl ser::SerializeSeq for SeqSignature {
+    type Ok = Type;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let typ = value.serialize(SignatureSerializer {
+            lenient: self.lenient,
+        })?;
+        merge_sig(&mut self.elem, typ)
+    }
+    fn end(self) -> Result<Type, SerdeError> {
+        match self.elem {
+            Some(elem) => Ok(Type::Container(Container::Array(Box::new(elem)))),
+            None if self.lenient => Ok(empty_array_sig()),
+            None => Err(SerdeError::CannotInferSignature(
+                "an empty sequence does not show the signature of its elements",
+            )),
+        }
+    }
+}
+
+struct MapSignature {
+    key: Option<Type>,
+    value: Option<Type>,
+    lenient: bool,
+}
+
+impl ser::SerializeMap for MapSignature {
+    type Ok = Type;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        merge_sig(&mut self.key, signature_of_value(key)?)
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let typ = value.serialize(SignatureSerializer {
+            lenient: self.lenient,
+        })?;
+        merge_sig(&mut self.value, typ)
+    }
+    fn end(self) -> Result<Type, SerdeError> {
+        match (self.key, self.value) {
+            (Some(key), Some(value)) => dict_sig(key, value),
+            (None, None) if self.lenient => Ok(empty_dict_sig()),
+            _ => Err(SerdeError::CannotInferSignature(
+                "an empty map does not show the signature of its entries",
+            )),
+        }
+    }
+}
+
+struct StructSignature {
+    fields: Vec<Type>,
+    lenient: bool,
+}
+
+impl StructSignature {
+    fn field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.fields.push(value.serialize(SignatureSerializer {
+            lenient: self.lenient,
+        })?);
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for StructSignature {
+    type Ok = Type;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.field(value)
+    }
+    fn end(self) -> Result<Type, SerdeError> {
+        struct_sig(self.fields)
+    }
+}
+
+impl ser::SerializeTupleStruct for StructSignature {
+    type Ok = Type;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.field(value)
+    }
+    fn end(self) -> Result<Type, SerdeError> {
+        struct_sig(self.fields)
+    }
+}
+
+impl ser::SerializeStruct for StructSignature {
+    type Ok = Type;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.field(value)
+    }
+    fn end(self) -> Result<Type, SerdeError> {
+        struct_sig(self.fields)
+    }
+}
+
+// The fields of an enum variant do not change its signature
+struct VariantSignature;
+
+impl ser::SerializeTupleVariant for VariantSignature {
+    type Ok = Type;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _value: &T) -> Result<(), SerdeError> {
+        Ok(())
+    }
+    fn end(self) -> Result<Type, SerdeError> {
+        variant_sig()
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSignature {
+    type Ok = Type;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        _value: &T,
+    ) -> Result<(), SerdeError> {
+        Ok(())
+    }
+    fn end(self) -> Result<Type, SerdeError> {
+        variant_sig()
+    }
+}
+
+/// Infer the signature of a type from its `Deserialize` impl.
+///
+/// This works by letting the type deserialize itself from made up values and recording what it asks for. Types that
+/// deserialize differently depending on the data (e.g. untagged enums) and recursive types can not be inferred.
+/// The made up values are zeros and empty strings, types that reject those in their `Deserialize` impl can not be
+/// inferred either.
+pub fn signature_of_type<T: DeserializeOwned>() -> Result<Type, SerdeError> {
+    let mut typ = None;
+    T::deserialize(TypeTracer {
+        out: &mut typ,
+        depth: 0,
+    })?;
+    typ.ok_or(SerdeError::CannotInferSignature(
+        "the type did not ask for a value",
+    ))
+}
+
+// Dbus allows 32 nested arrays plus 32 nested structs. Anything deeper is most likely a recursive type.
+const MAX_TRACE_DEPTH: usize = 64;
+
+struct TypeTracer<'a> {
+    out: &'a mut Option<Type>,
+    depth: usize,
+}
+
+impl<'a> TypeTracer<'a> {
+    fn child<'b>(out: &'b mut Option<Type>, depth: usize) -> Result<TypeTracer<'b>, SerdeError> {
+        if depth > MAX_TRACE_DEPTH {
+            return Err(SerdeError::CannotInferSignature(
+                "the type is nested too deeply or recursive",
+            ));
+        }
+        Ok(TypeTracer { out, depth })
+    }
+
+    fn record(self, typ: Type) {
+        *self.out = Some(typ);
+    }
+
+    fn trace_fields<'de, V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let mut fields = Vec::with_capacity(len);
+        let value = visitor.visit_seq(TraceFields {
+            fields: &mut fields,
+            remaining: len,
+            depth: self.depth,
+        })?;
+        self.record(struct_sig(fields)?);
+        Ok(value)
+    }
+}
+
+fn trace_value<'de, T: de::DeserializeSeed<'de>>(
+    seed: T,
+    depth: usize,
+) -> Result<(T::Value, Type), SerdeError> {
+    let mut typ = None;
+    let value = seed.deserialize(TypeTracer::child(&mut typ, depth + 1)?)?;
+    let typ = typ.ok_or(SerdeError::CannotInferSignature(
+        "a value did not describe itself",
+    ))?;
+    Ok((value, typ))
+}
+
+impl<'de> de::Deserializer<'de> for TypeTracer<'_> {
+    type Error = SerdeError;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, SerdeError> {
+        Err(SerdeError::CannotInferSignature(
+            "the type depends on the data it is deserialized from",
+        ))
+    }
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Base(Base::Boolean));
+        visitor.visit_bool(false)
+    }
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Base(Base::Int16));
+        visitor.visit_i8(0)
+    }
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Base(Base::Int16));
+        visitor.visit_i16(0)
+    }
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Base(Base::Int32));
+        visitor.visit_i32(0)
+    }
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Base(Base::Int64));
+        visitor.visit_i64(0)
+    }
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Base(Base::Byte));
+        visitor.visit_u8(0)
+    }
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Base(Base::Uint16));
+        visitor.visit_u16(0)
+    }
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Base(Base::Uint32));
+        visitor.visit_u32(0)
+    }
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Base(Base::Uint64));
+        visitor.visit_u64(0)
+    }
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Base(Base::Double));
+        visitor.visit_f32(0.0)
+    }
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Base(Base::Double));
+        visitor.visit_f64(0.0)
+    }
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(string_type());
+        visitor.visit_char('\0')
+    }
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(string_type());
+        visitor.visit_str("")
+    }
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(string_type());
+        visitor.visit_string(String::new())
+    }
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Container(Container::Array(Box::new(Type::Base(
+            Base::Byte,
+        )))));
+        visitor.visit_bytes(&[])
+    }
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.record(Type::Container(Container::Array(Box::new(Type::Base(
+            Base::Byte,
+        )))));
+        visitor.visit_byte_buf(Vec::new())
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_some(self)
+    }
+    fn deserialize_unit<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, SerdeError> {
+        Err(SerdeError::Unsupported("A unit value"))
+    }
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        Err(SerdeError::Unsupported("A unit struct"))
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let mut elem = None;
+        let value = visitor.visit_seq(TraceElement {
+            elem: &mut elem,
+            depth: self.depth,
+        })?;
+        let elem = elem.ok_or(SerdeError::CannotInferSignature(
+            "the sequence did not ask for an element",
+        ))?;
+        self.record(Type::Container(Container::Array(Box::new(elem))));
+        Ok(value)
+    }
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.trace_fields(len, visitor)
+    }
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.trace_fields(len, visitor)
+    }
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let mut entry = TraceEntry {
+            key: None,
+            value: None,
+            depth: self.depth,
+        };
+        let value = visitor.visit_map(&mut entry)?;
+        match (entry.key, entry.value) {
+            (Some(key), Some(value_sig)) => self.record(dict_sig(key, value_sig)?),
+            _ => {
+                return Err(SerdeError::CannotInferSignature(
+                    "the map did not ask for an entry",
+                ))
+            }
+        }
+        Ok(value)
+    }
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.trace_fields(fields.len(), visitor)
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let depth = self.depth;
+        self.record(Type::Container(Container::Variant));
+        // any variant will do, the content of a variant is not part of the signature
+        let variant = variants
+            .first()
+            .ok_or(SerdeError::CannotInferSignature("the enum has no variants"))?;
+        visitor.visit_enum(TraceEnum { variant, depth })
+    }
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, SerdeError> {
+        Err(SerdeError::CannotInferSignature(
+            "identifiers only occur in self describing formats",
+        ))
+    }
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_any(visitor)
+    }
+}
+
+/// Hands out a single element of a sequence
+struct TraceElement<'a> {
+    elem: &'a mut Option<Type>,
+    depth: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for TraceElement<'_> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        if self.elem.is_some() {
+            return Ok(None);
+        }
+        let (value, typ) = trace_value(seed, self.depth)?;
+        *self.elem = Some(typ);
+        Ok(Some(value))
+    }
+}
+
+/// Hands out the fields of a struct or tuple
+struct TraceFields<'a> {
+    fields: &'a mut Vec<Type>,
+    remaining: usize,
+    depth: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for TraceFields<'_> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let (value, typ) = trace_value(seed, self.depth)?;
+        self.fields.push(typ);
+        Ok(Some(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Hands out a single entry of a map
+struct TraceEntry {
+    key: Option<Type>,
+    value: Option<Type>,
+    depth: usize,
+}
+
+impl<'de> de::MapAccess<'de> for TraceEntry {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        if self.key.is_some() {
+            return Ok(None);
+        }
+        let (key, typ) = trace_value(seed, self.depth)?;
+        self.key = Some(typ);
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, SerdeError> {
+        let (value, typ) = trace_value(seed, self.depth)?;
+        self.value = Some(typ);
+        Ok(value)
+    }
+}
+
+struct TraceEnum {
+    variant: &'static str,
+    depth: usize,
+}
+
+impl<'de> de::EnumAccess<'de> for TraceEnum {
+    type Error = SerdeError;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), SerdeError> {
+        let name: de::value::StrDeserializer<SerdeError> = self.variant.into_deserializer();
+        Ok((seed.deserialize(name)?, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for TraceEnum {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        trace_value(seed, self.depth).map(|(value, _)| value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_seq(TraceFields {
+            fields: &mut Vec::new(),
+            remaining: len,
+            depth: self.depth,
+        })
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.tuple_variant(fields.len(), visitor)
+    }
+}
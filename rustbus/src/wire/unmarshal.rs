@@ -100,6 +100,10 @@ pub fn unmarshal_header(buf: &[u8], offset: usize) -> UnmarshalResult<Header> {
     let (_, body_len) = parse_u32(&header_slice[4..8], byteorder)?;
     let (_, serial) = parse_u32(&header_slice[8..12], byteorder)?;
 
+    if body_len as usize > crate::wire::MAX_MESSAGE_SIZE {
+        return Err(UnmarshalError::MessageTooBig(body_len as usize));
+    }
+
     Ok((
         HEADER_LEN,
         Header {
@@ -124,6 +128,10 @@ pub fn unmarshal_dynamic_header(
         ..Default::default()
     };
     collect_header_fields(&fields, &mut hdr);
+    crate::wire::validate_header(header.typ, header.serial, &hdr)?;
+    // the required fields are present, so this only catches duplicated fields
+    params::validate_header_fields(header.typ, &fields)
+        .map_err(|_| UnmarshalError::InvalidHeaderFields)?;
     Ok((fields_bytes_used, hdr))
 }
 
@@ -233,9 +241,6 @@ fn unmarshal_header_fields(
             Err(e) => return Err(e),
         }
     }
-    params::validate_header_fields(header.typ, &fields)
-        .map_err(|_| UnmarshalError::InvalidHeaderFields)?;
-
     Ok((header_fields_bytes as usize + 4, fields))
 }
 
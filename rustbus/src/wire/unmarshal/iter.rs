@@ -334,7 +334,8 @@ fn make_new_array_iter<'a>(
     el_sig: &'a signature::Type,
 ) -> Result<ArrayIter<'a>, UnmarshalError> {
     // get child array size
-    let (bytes, array_len_bytes) = crate::wire::util::parse_u32(&source[*offset..], byteorder)?;
+    let (bytes, array_len_bytes) =
+        crate::wire::util::parse_array_len(&source[*offset..], byteorder)?;
     debug_assert_eq!(bytes, 4);
 
     // move offset
@@ -350,7 +351,7 @@ fn make_new_array_iter<'a>(
         current_offset: offset,
         element_sig: el_sig,
 
-        consume_max_bytes: array_len_bytes as usize,
+        consume_max_bytes: array_len_bytes,
     })
 }
 fn make_new_variant_iter<'a>(
@@ -386,7 +387,8 @@ fn make_new_dict_iter<'a>(
     val_sig: &'a signature::Type,
 ) -> Result<DictIter<'a>, UnmarshalError> {
     // get child array size
-    let (bytes, array_len_bytes) = crate::wire::util::parse_u32(&source[*offset..], byteorder)?;
+    let (bytes, array_len_bytes) =
+        crate::wire::util::parse_array_len(&source[*offset..], byteorder)?;
     debug_assert_eq!(bytes, 4);
 
     // move offset
@@ -403,7 +405,7 @@ fn make_new_dict_iter<'a>(
         key_sig,
         val_sig,
 
-        consume_max_bytes: array_len_bytes as usize,
+        consume_max_bytes: array_len_bytes,
     })
 }
 
@@ -54,14 +54,14 @@ pub fn unmarshal_container<'a, 'e>(
             let start_offset = ctx.offset;
             ctx.align_to(4)?;
 
-            let (_, bytes_in_array) = parse_u32(&ctx.buf[ctx.offset..], ctx.byteorder)?;
+            let (_, bytes_in_array) = parse_array_len(&ctx.buf[ctx.offset..], ctx.byteorder)?;
             ctx.offset += 4;
 
             ctx.align_to(elem_sig.get_alignment())?;
 
             let mut elements = Vec::new();
             let mut bytes_used_counter = 0;
-            while bytes_used_counter < bytes_in_array as usize {
+            while bytes_used_counter < bytes_in_array {
                 if ctx.offset >= ctx.buf.len() {
                     return Err(UnmarshalError::NotEnoughBytes);
                 }
@@ -84,14 +84,14 @@ pub fn unmarshal_container<'a, 'e>(
             let start_offset = ctx.offset;
 
             ctx.align_to(4)?;
-            let (_, bytes_in_dict) = parse_u32(&ctx.buf[ctx.offset..], ctx.byteorder)?;
+            let (_, bytes_in_dict) = parse_array_len(&ctx.buf[ctx.offset..], ctx.byteorder)?;
             ctx.offset += 4;
 
             ctx.align_to(8)?;
 
             let mut elements = std::collections::HashMap::new();
             let mut bytes_used_counter = 0;
-            while bytes_used_counter < bytes_in_dict as usize {
+            while bytes_used_counter < bytes_in_dict {
                 if ctx.offset >= ctx.buf.len() {
                     return Err(UnmarshalError::NotEnoughBytes);
                 }
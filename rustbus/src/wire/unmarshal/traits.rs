@@ -7,11 +7,10 @@ use crate::wire::unmarshal::UnmarshalContext;
 // these contain the implementations
 mod base;
 mod container;
-pub use base::*;
 pub use container::*;
 
 /// This trait has to be supported to get parameters ergonomically out of a MarshalledMessage.
-/// There are implementations for the base types, Vecs, Hashmaps, and tuples of up to 5 elements
+/// There are implementations for the base types, Vecs, Hashmaps, the other std collections, Box/Rc/Arc and tuples of up to 5 elements
 /// if the contained types are Unmarshal.
 /// If you deal with basic messages, this should cover all your needs and you dont need to implement this type for
 /// your own types.
@@ -134,7 +133,6 @@ pub use container::*;
 ///     }
 /// }
 /// ```
-
 pub trait Unmarshal<'buf, 'fds>: Sized + Signature {
     fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self>;
 }
@@ -170,9 +168,9 @@ mod test {
         // annotate the receiver with a type &str to unmarshal a &str
         "ABCD".marshal(ctx).unwrap();
         let _s: &str = unmarshal(&mut UnmarshalContext {
-            buf: &ctx.buf,
+            buf: ctx.buf,
             byteorder: ctx.byteorder,
-            fds: &ctx.fds,
+            fds: ctx.fds,
             offset: 0,
         })
         .unwrap()
@@ -182,9 +180,9 @@ mod test {
         ctx.buf.clear();
         true.marshal(ctx).unwrap();
         let _b: bool = unmarshal(&mut UnmarshalContext {
-            buf: &ctx.buf,
+            buf: ctx.buf,
             byteorder: ctx.byteorder,
-            fds: &ctx.fds,
+            fds: ctx.fds,
             offset: 0,
         })
         .unwrap()
@@ -194,9 +192,9 @@ mod test {
         ctx.buf.clear();
         0i32.marshal(ctx).unwrap();
         let _i = unmarshal::<i32>(&mut UnmarshalContext {
-            buf: &ctx.buf,
+            buf: ctx.buf,
             byteorder: ctx.byteorder,
-            fds: &ctx.fds,
+            fds: ctx.fds,
             offset: 0,
         })
         .unwrap()
@@ -207,9 +205,9 @@ mod test {
         fn x(_arg: (i32, i32, &str)) {}
         (0, 0, "ABCD").marshal(ctx).unwrap();
         let arg = unmarshal(&mut UnmarshalContext {
-            buf: &ctx.buf,
+            buf: ctx.buf,
             byteorder: ctx.byteorder,
-            fds: &ctx.fds,
+            fds: ctx.fds,
             offset: 0,
         })
         .unwrap()
@@ -275,6 +273,129 @@ mod test {
         assert_eq!(orig, unorig);
     }
 
+    #[test]
+    fn test_unmarshal_fixed_size_arrays() {
+        use crate::wire::errors::UnmarshalError;
+        use std::borrow::Cow;
+
+        let samples: Vec<f64> = (0..100).map(|x| x as f64 / 3.0).collect();
+        let words: Vec<u32> = (0..100).collect();
+
+        let mut fds = Vec::new();
+        let mut buf = Vec::new();
+        let mut ctx = MarshalContext {
+            buf: &mut buf,
+            fds: &mut fds,
+            byteorder: ByteOrder::NATIVE,
+        };
+        let ctx = &mut ctx;
+        (&samples, &words).marshal(ctx).unwrap();
+
+        let (_, (borrowed_samples, borrowed_words)) =
+            <(&[f64], &[u32]) as Unmarshal>::unmarshal(&mut UnmarshalContext {
+                buf: ctx.buf,
+                fds: ctx.fds,
+                byteorder: ctx.byteorder,
+                offset: 0,
+            })
+            .unwrap();
+        assert_eq!(borrowed_samples, samples.as_slice());
+        assert_eq!(borrowed_words, words.as_slice());
+        let (_, (cow_samples, vec_words)) =
+            <(Cow<[f64]>, Vec<u32>) as Unmarshal>::unmarshal(&mut UnmarshalContext {
+                buf: ctx.buf,
+                fds: ctx.fds,
+                byteorder: ctx.byteorder,
+                offset: 0,
+            })
+            .unwrap();
+        assert!(matches!(cow_samples, Cow::Borrowed(_)));
+        assert_eq!(cow_samples.as_ref(), samples.as_slice());
+        assert_eq!(vec_words, words);
+
+        // a VecDeque that wrapped around is copied in two parts
+        let mut deque: std::collections::VecDeque<u32> = words.iter().copied().collect();
+        deque.rotate_left(10);
+        deque.rotate_right(10);
+        let mut deque_buf = Vec::new();
+        deque
+            .marshal(&mut MarshalContext {
+                buf: &mut deque_buf,
+                fds: &mut Vec::new(),
+                byteorder: ByteOrder::NATIVE,
+            })
+            .unwrap();
+        ctx.buf.clear();
+        words.marshal(ctx).unwrap();
+        assert_eq!(&deque_buf, ctx.buf);
+
+        // the array is not aligned in memory if the buffer starts at an odd address
+        let mut unaligned = vec![0u8];
+        ctx.buf.clear();
+        samples.marshal(ctx).unwrap();
+        unaligned.extend_from_slice(ctx.buf);
+        let unaligned_ctx = || UnmarshalContext {
+            buf: &unaligned[1..],
+            fds: &[],
+            byteorder: ByteOrder::NATIVE,
+            offset: 0,
+        };
+        assert_eq!(
+            <&[f64] as Unmarshal>::unmarshal(&mut unaligned_ctx()).unwrap_err(),
+            UnmarshalError::CannotBorrowSlice
+        );
+        let (_, cow_samples) = <Cow<[f64]> as Unmarshal>::unmarshal(&mut unaligned_ctx()).unwrap();
+        assert!(matches!(cow_samples, Cow::Owned(_)));
+        assert_eq!(cow_samples.as_ref(), samples.as_slice());
+        let (_, vec_samples) = <Vec<f64> as Unmarshal>::unmarshal(&mut unaligned_ctx()).unwrap();
+        assert_eq!(vec_samples, samples);
+
+        // foreign byteorder can not be borrowed but is still unmarshalled correctly
+        let foreign = match ByteOrder::NATIVE {
+            ByteOrder::LittleEndian => ByteOrder::BigEndian,
+            ByteOrder::BigEndian => ByteOrder::LittleEndian,
+        };
+        ctx.buf.clear();
+        ctx.byteorder = foreign;
+        words.marshal(ctx).unwrap();
+        let foreign_ctx = || UnmarshalContext {
+            buf: ctx.buf,
+            fds: &[],
+            byteorder: foreign,
+            offset: 0,
+        };
+        assert_eq!(
+            <&[u32] as Unmarshal>::unmarshal(&mut foreign_ctx()).unwrap_err(),
+            UnmarshalError::CannotBorrowSlice
+        );
+        let (_, cow_words) = <Cow<[u32]> as Unmarshal>::unmarshal(&mut foreign_ctx()).unwrap();
+        assert!(matches!(cow_words, Cow::Owned(_)));
+        assert_eq!(cow_words.as_ref(), words.as_slice());
+
+        // a length field pointing past the end of the buffer is rejected
+        let mut truncated = Vec::new();
+        crate::wire::util::write_u32(1024, ByteOrder::NATIVE, &mut truncated);
+        truncated.extend_from_slice(&[0; 12]);
+        let truncated_ctx = || UnmarshalContext {
+            buf: &truncated,
+            fds: &[],
+            byteorder: ByteOrder::NATIVE,
+            offset: 0,
+        };
+        assert_eq!(
+            <&[u32] as Unmarshal>::unmarshal(&mut truncated_ctx()).unwrap_err(),
+            UnmarshalError::NotEnoughBytes
+        );
+        assert_eq!(
+            <Vec<u64> as Unmarshal>::unmarshal(&mut truncated_ctx()).unwrap_err(),
+            UnmarshalError::NotEnoughBytes
+        );
+        assert_eq!(
+            <&[u8] as Unmarshal>::unmarshal(&mut truncated_ctx()).unwrap_err(),
+            UnmarshalError::NotEnoughBytes
+        );
+    }
+
     #[test]
     fn test_unmarshal_traits() {
         use crate::wire::marshal::MarshalContext;
@@ -368,6 +489,69 @@ mod test {
         assert_eq!(s.as_ref(), "ss(aiau)");
     }
 
+    #[test]
+    fn test_std_collections() {
+        use crate::message_builder::MarshalledMessageBody;
+        use std::borrow::Cow;
+        use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+        use std::rc::Rc;
+        use std::sync::Arc;
+
+        let mut map1 = BTreeMap::new();
+        map1.insert("b".to_owned(), 2u32);
+        map1.insert("a".to_owned(), 1u32);
+        let mut map2 = BTreeMap::new();
+        map2.insert("a".to_owned(), 1u32);
+        map2.insert("b".to_owned(), 2u32);
+
+        // BTreeMaps marshal deterministically regardless of insertion order
+        let mut body1 = MarshalledMessageBody::new();
+        body1.push_param(&map1).unwrap();
+        let mut body2 = MarshalledMessageBody::new();
+        body2.push_param(&map2).unwrap();
+        assert_eq!(body1.buf, body2.buf);
+
+        let hash_set: HashSet<u16> = [1, 2, 3].iter().copied().collect();
+        let btree_set: BTreeSet<&str> = ["x", "y"].iter().copied().collect();
+        let deque: VecDeque<(u8, i64)> = vec![(1, -1), (2, -2)].into();
+
+        let mut msg = crate::message_builder::MarshalledMessage::new();
+        let body = &mut msg.body;
+        body.push_param(&map1).unwrap();
+        body.push_param(&hash_set).unwrap();
+        body.push_param(&btree_set).unwrap();
+        body.push_param(&deque).unwrap();
+        body.push_param(Box::new(10u64)).unwrap();
+        body.push_param(Rc::new("rc")).unwrap();
+        body.push_param(Arc::new(vec![true, false])).unwrap();
+        body.push_param(Cow::Borrowed("cow")).unwrap();
+        assert_eq!(msg.get_sig(), "a{su}aqasa(yx)tsabs");
+
+        let mut parser = msg.body.parser();
+        assert_eq!(parser.get::<BTreeMap<String, u32>>().unwrap(), map1);
+        assert_eq!(parser.get::<HashSet<u16>>().unwrap(), hash_set);
+        assert_eq!(parser.get::<BTreeSet<&str>>().unwrap(), btree_set);
+        assert_eq!(parser.get::<VecDeque<(u8, i64)>>().unwrap(), deque);
+        assert_eq!(*parser.get::<Box<u64>>().unwrap(), 10);
+        assert_eq!(*parser.get::<Rc<&str>>().unwrap(), "rc");
+        assert_eq!(*parser.get::<Arc<Vec<bool>>>().unwrap(), vec![true, false]);
+        assert!(matches!(
+            parser.get::<Cow<str>>().unwrap(),
+            Cow::Borrowed("cow")
+        ));
+
+        // sets can be read as plain arrays and vice versa
+        let mut parser = msg.body.parser();
+        parser.get::<BTreeMap<String, u32>>().unwrap();
+        let mut as_vec = parser.get::<Vec<u16>>().unwrap();
+        as_vec.sort_unstable();
+        assert_eq!(as_vec, vec![1, 2, 3]);
+        assert_eq!(
+            parser.get::<HashSet<String>>().unwrap(),
+            ["x".to_owned(), "y".to_owned()].iter().cloned().collect()
+        );
+    }
+
     #[test]
     fn test_variant() {
         use crate::message_builder::MarshalledMessageBody;
@@ -481,10 +665,7 @@ mod test {
             SignatureWrapper::new("sy").unwrap(),
             parser.get::<Variant>().unwrap().get().unwrap()
         );
-        assert_eq!(
-            true,
-            parser.get::<Variant>().unwrap().get::<bool>().unwrap()
-        );
+        assert!(parser.get::<Variant>().unwrap().get::<bool>().unwrap());
 
         // check Array of variants
         let var_vec: Vec<Variant> = parser.get().unwrap();
@@ -500,7 +681,7 @@ mod test {
             SignatureWrapper::new("sy").unwrap(),
             var_vec[8].get().unwrap()
         );
-        assert_eq!(true, var_vec[9].get::<bool>().unwrap());
+        assert!(var_vec[9].get::<bool>().unwrap());
 
         // check Dict of {String, variants}
         let var_map: HashMap<String, Variant> = parser.get().unwrap();
@@ -516,6 +697,6 @@ mod test {
             SignatureWrapper::new("sy").unwrap(),
             var_map["8"].get().unwrap()
         );
-        assert_eq!(true, var_map["9"].get::<bool>().unwrap());
+        assert!(var_map["9"].get::<bool>().unwrap());
     }
 }
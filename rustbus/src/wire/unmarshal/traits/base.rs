@@ -8,6 +8,7 @@ use crate::wire::ObjectPath;
 use crate::wire::SignatureWrapper;
 use crate::Signature;
 use crate::Unmarshal;
+use std::borrow::Cow;
 
 impl<'buf, 'fds> Unmarshal<'buf, 'fds> for u64 {
     fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
@@ -17,6 +18,15 @@ impl<'buf, 'fds> Unmarshal<'buf, 'fds> for u64 {
         Ok((bytes + padding, val))
     }
 }
+impl<'buf, 'fds> Unmarshal<'buf, 'fds> for f64 {
+    fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
+        let padding = ctx.align_to(Self::alignment())?;
+        let (bytes, val) = util::parse_u64(&ctx.buf[ctx.offset..], ctx.byteorder)
+            .map(|(bytes, val)| (bytes, f64::from_bits(val)))?;
+        ctx.offset += bytes;
+        Ok((bytes + padding, val))
+    }
+}
 impl<'buf, 'fds> Unmarshal<'buf, 'fds> for u32 {
     fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
         let padding = ctx.align_to(Self::alignment())?;
@@ -103,6 +113,13 @@ impl<'buf, 'fds> Unmarshal<'buf, 'fds> for String {
     }
 }
 
+/// This will always borrow from the buffer. Use `into_owned()` if you need to keep the string around.
+impl<'buf, 'fds> Unmarshal<'buf, 'fds> for Cow<'buf, str> {
+    fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
+        <&'buf str>::unmarshal(ctx).map(|(bytes, val)| (bytes, Cow::Borrowed(val)))
+    }
+}
+
 impl<'buf, 'fds, S: AsRef<str> + From<&'buf str> + Unmarshal<'buf, 'fds>> Unmarshal<'buf, 'fds>
     for SignatureWrapper<S>
 {
@@ -10,6 +10,9 @@ use crate::ByteOrder;
 use crate::Signature;
 use crate::Unmarshal;
 use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use std::sync::Arc;
 
 impl<'buf, 'fds, E1> Unmarshal<'buf, 'fds> for (E1,)
 where
@@ -126,52 +129,100 @@ impl<E: Signature + Clone> Signature for Cow<'_, [E]> {
         <[E]>::has_sig(sig)
     }
 }
-/// for byte arrays we can give an efficient method of decoding. This will bind the returned slice to the lifetime of the buffer.
-impl<'buf, 'fds> Unmarshal<'buf, 'fds> for &'buf [u8] {
-    fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
-        let padding = ctx.align_to(Self::alignment())?;
-        let (_, bytes_in_array) = u32::unmarshal(ctx)?;
-
-        let elements = &ctx.buf[ctx.offset..ctx.offset + bytes_in_array as usize];
-        ctx.offset += bytes_in_array as usize;
-
-        let total_bytes_used = padding + 4 + bytes_in_array as usize;
-
-        Ok((total_bytes_used, elements))
-    }
-}
-
-unsafe fn unmarshal_slice<'a, 'buf, 'fds, E>(
-    ctx: &'a mut UnmarshalContext<'fds, 'buf>,
-) -> unmarshal::UnmarshalResult<&'a [E]>
-where
-    E: Unmarshal<'buf, 'fds>, //+ 'fds + 'buf
-{
+/// Get the raw bytes of an array of fixed size elements. The returned slice is bound to the lifetime of the buffer.
+fn unmarshal_fixed_size_array<'buf>(
+    ctx: &mut UnmarshalContext<'_, 'buf>,
+    alignment: usize,
+) -> unmarshal::UnmarshalResult<&'buf [u8]> {
     let start_offset = ctx.offset;
     ctx.align_to(4)?;
-    let (_, bytes_in_array) = u32::unmarshal(ctx)?;
-    let bytes_in_array = bytes_in_array as usize;
-    let alignment = E::alignment();
+    let (_, bytes_in_array) = util::parse_array_len(&ctx.buf[ctx.offset..], ctx.byteorder)?;
+    ctx.offset += 4;
     ctx.align_to(alignment)?;
 
-    if bytes_in_array % alignment != 0 {
+    if !bytes_in_array.is_multiple_of(alignment) {
         return Err(UnmarshalError::NotAllBytesUsed);
     }
-    let elem_cnt = bytes_in_array / alignment;
-    let ptr = &ctx.buf[ctx.offset..] as *const [u8] as *const E;
-    let slice = std::slice::from_raw_parts(ptr, elem_cnt);
+    let buf: &'buf [u8] = ctx.buf;
+    if buf.len() - ctx.offset < bytes_in_array {
+        return Err(UnmarshalError::NotEnoughBytes);
+    }
+    let elements = &buf[ctx.offset..ctx.offset + bytes_in_array];
     ctx.offset += bytes_in_array;
-    Ok((ctx.offset - start_offset, slice))
+    Ok((ctx.offset - start_offset, elements))
+}
+
+/// Reinterpret the raw bytes of an array as a slice of `E`.
+/// Returns `None` if the bytes are not properly aligned in memory for `E`.
+///
+/// # Safety
+/// `E::valid_slice()` must have returned true for the byteorder the bytes were marshalled in.
+unsafe fn cast_fixed_size_array<E: Signature>(bytes: &[u8]) -> Option<&[E]> {
+    debug_assert_eq!(E::alignment(), std::mem::size_of::<E>());
+    if !(bytes.as_ptr() as *const E).is_aligned() {
+        return None;
+    }
+    let elem_cnt = bytes.len() / std::mem::size_of::<E>();
+    Some(std::slice::from_raw_parts(
+        bytes.as_ptr() as *const E,
+        elem_cnt,
+    ))
+}
+
+/// Copy the raw bytes of an array into a new `Vec<E>`. This works regardless of the memory alignment of the bytes.
+///
+/// # Safety
+/// `E::valid_slice()` must have returned true for the byteorder the bytes were marshalled in.
+unsafe fn copy_fixed_size_array<E: Signature>(bytes: &[u8]) -> Vec<E> {
+    debug_assert_eq!(E::alignment(), std::mem::size_of::<E>());
+    let elem_cnt = bytes.len() / std::mem::size_of::<E>();
+    let mut ret = Vec::with_capacity(elem_cnt);
+    let dst = ret.as_mut_ptr() as *mut u8;
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), dst, elem_cnt * std::mem::size_of::<E>());
+    ret.set_len(elem_cnt);
+    ret
 }
+
+/// Arrays of fixed size primitives can be borrowed directly from the buffer. This will bind the returned slice to the lifetime of the buffer.
+///
+/// This only works if the message was marshalled in the native byteorder and the array is properly aligned in memory. Otherwise
+/// `UnmarshalError::CannotBorrowSlice` is returned. Use `Cow<[T]>` if you want to borrow if possible and copy otherwise.
+macro_rules! borrowed_slice_impls {
+    ($($typ:ty),*) => {$(
+        impl<'buf, 'fds> Unmarshal<'buf, 'fds> for &'buf [$typ] {
+            fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
+                // SAFETY: valid_slice is checked before the bytes are reinterpreted
+                unsafe {
+                    if !<$typ>::valid_slice(ctx.byteorder) {
+                        return Err(UnmarshalError::CannotBorrowSlice);
+                    }
+                    let start_offset = ctx.offset;
+                    let (used, bytes) = unmarshal_fixed_size_array(ctx, <$typ>::alignment())?;
+                    match cast_fixed_size_array(bytes) {
+                        Some(elements) => Ok((used, elements)),
+                        None => {
+                            ctx.offset = start_offset;
+                            Err(UnmarshalError::CannotBorrowSlice)
+                        }
+                    }
+                }
+            }
+        }
+    )*};
+}
+borrowed_slice_impls!(u8, u16, i16, u32, i32, u64, i64, f64);
+
 impl<'buf, 'fds, E: Unmarshal<'buf, 'fds> + Clone> Unmarshal<'buf, 'fds> for Cow<'buf, [E]> {
     fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
         unsafe {
             if E::valid_slice(ctx.byteorder) {
-                let (used, src): (_, &[E]) = unmarshal_slice(ctx)?;
+                let (used, bytes) = unmarshal_fixed_size_array(ctx, E::alignment())?;
                 // SAFETY: One of requirements is for valid_slice it is only valid for 'buf
-                // Thus this lifetime cast is always valid
-                let l_expand: &'buf [E] = std::mem::transmute(src);
-                return Ok((used, Cow::Borrowed(l_expand)));
+                // Thus borrowing from the buffer is always valid
+                return match cast_fixed_size_array(bytes) {
+                    Some(elements) => Ok((used, Cow::Borrowed(elements))),
+                    None => Ok((used, Cow::Owned(copy_fixed_size_array(bytes)))),
+                };
             }
         }
         Vec::unmarshal(ctx).map(|o| (o.0, Cow::Owned(o.1)))
@@ -181,23 +232,20 @@ impl<'buf, 'fds, E: Unmarshal<'buf, 'fds>> Unmarshal<'buf, 'fds> for Vec<E> {
     fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
         unsafe {
             if E::valid_slice(ctx.byteorder) {
-                let (used, src) = unmarshal_slice::<E>(ctx)?;
-                let mut ret = Vec::with_capacity(src.len());
-                let dst = ret.as_mut_ptr();
-                std::ptr::copy_nonoverlapping(src.as_ptr(), dst, src.len());
-                ret.set_len(src.len());
-                return Ok((used, ret));
+                let (used, bytes) = unmarshal_fixed_size_array(ctx, E::alignment())?;
+                return Ok((used, copy_fixed_size_array(bytes)));
             }
         }
         let start_offset = ctx.offset;
         ctx.align_to(4)?;
-        let (_, bytes_in_array) = u32::unmarshal(ctx)?;
+        let (_, bytes_in_array) = util::parse_array_len(&ctx.buf[ctx.offset..], ctx.byteorder)?;
+        ctx.offset += 4;
 
         ctx.align_to(E::alignment())?;
 
         let mut elements = Vec::new();
         let mut bytes_used_counter = 0;
-        while bytes_used_counter < bytes_in_array as usize {
+        while bytes_used_counter < bytes_in_array {
             if ctx.offset >= ctx.buf.len() {
                 return Err(UnmarshalError::NotEnoughBytes);
             }
@@ -218,47 +266,106 @@ impl<'buf, 'fds, E: Unmarshal<'buf, 'fds>> Unmarshal<'buf, 'fds> for Vec<E> {
     }
 }
 
-impl<'buf, 'fds, K: Unmarshal<'buf, 'fds> + std::hash::Hash + Eq, V: Unmarshal<'buf, 'fds>>
-    Unmarshal<'buf, 'fds> for std::collections::HashMap<K, V>
+/// Unmarshal a dbus dict and pass every entry to `insert`. Returns the total bytes used.
+fn unmarshal_dict_entries<'buf, 'fds, K, V>(
+    ctx: &mut UnmarshalContext<'fds, 'buf>,
+    mut insert: impl FnMut(K, V),
+) -> Result<usize, UnmarshalError>
+where
+    K: Unmarshal<'buf, 'fds>,
+    V: Unmarshal<'buf, 'fds>,
 {
-    fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
-        let start_offset = ctx.offset;
-        ctx.align_to(4)?;
-        let (_, bytes_in_array) = u32::unmarshal(ctx)?;
+    let start_offset = ctx.offset;
+    ctx.align_to(4)?;
+    let (_, bytes_in_array) = util::parse_array_len(&ctx.buf[ctx.offset..], ctx.byteorder)?;
+    ctx.offset += 4;
 
-        // align even if no elements are present
-        ctx.align_to(8)?;
+    // align even if no elements are present
+    ctx.align_to(8)?;
 
-        let mut map = std::collections::HashMap::new();
-        let mut bytes_used_counter = 0;
-        while bytes_used_counter < bytes_in_array as usize {
-            if ctx.offset >= ctx.buf.len() {
-                return Err(UnmarshalError::NotEnoughBytes);
-            }
+    let mut bytes_used_counter = 0;
+    while bytes_used_counter < bytes_in_array {
+        if ctx.offset >= ctx.buf.len() {
+            return Err(UnmarshalError::NotEnoughBytes);
+        }
 
-            let elem_padding = util::align_offset(8, ctx.buf, ctx.offset)?;
-            bytes_used_counter += elem_padding;
-            ctx.offset += elem_padding;
+        let elem_padding = util::align_offset(8, ctx.buf, ctx.offset)?;
+        bytes_used_counter += elem_padding;
+        ctx.offset += elem_padding;
 
-            let (key_bytes_used, key) = K::unmarshal(ctx)?;
-            bytes_used_counter += key_bytes_used;
+        let (key_bytes_used, key) = K::unmarshal(ctx)?;
+        bytes_used_counter += key_bytes_used;
 
-            let val_padding = util::align_offset(V::alignment(), ctx.buf, ctx.offset)?;
-            bytes_used_counter += val_padding;
-            ctx.offset += val_padding;
+        let val_padding = util::align_offset(V::alignment(), ctx.buf, ctx.offset)?;
+        bytes_used_counter += val_padding;
+        ctx.offset += val_padding;
 
-            let (val_bytes_used, val) = V::unmarshal(ctx)?;
-            bytes_used_counter += val_bytes_used;
+        let (val_bytes_used, val) = V::unmarshal(ctx)?;
+        bytes_used_counter += val_bytes_used;
 
-            map.insert(key, val);
-        }
+        insert(key, val);
+    }
 
-        let total_bytes_used = ctx.offset - start_offset;
+    Ok(ctx.offset - start_offset)
+}
 
+impl<'buf, 'fds, K: Unmarshal<'buf, 'fds> + std::hash::Hash + Eq, V: Unmarshal<'buf, 'fds>>
+    Unmarshal<'buf, 'fds> for HashMap<K, V>
+{
+    fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
+        let mut map = HashMap::new();
+        let total_bytes_used = unmarshal_dict_entries(ctx, |k, v| {
+            map.insert(k, v);
+        })?;
+        Ok((total_bytes_used, map))
+    }
+}
+
+impl<'buf, 'fds, K: Unmarshal<'buf, 'fds> + Ord, V: Unmarshal<'buf, 'fds>> Unmarshal<'buf, 'fds>
+    for BTreeMap<K, V>
+{
+    fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
+        let mut map = BTreeMap::new();
+        let total_bytes_used = unmarshal_dict_entries(ctx, |k, v| {
+            map.insert(k, v);
+        })?;
         Ok((total_bytes_used, map))
     }
 }
 
+impl<'buf, 'fds, E: Unmarshal<'buf, 'fds>> Unmarshal<'buf, 'fds> for VecDeque<E> {
+    fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
+        Vec::unmarshal(ctx).map(|(bytes, elements)| (bytes, elements.into()))
+    }
+}
+
+/// Duplicate elements in the array are collapsed into one
+impl<'buf, 'fds, E: Unmarshal<'buf, 'fds> + std::hash::Hash + Eq> Unmarshal<'buf, 'fds>
+    for HashSet<E>
+{
+    fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
+        Vec::unmarshal(ctx).map(|(bytes, elements)| (bytes, elements.into_iter().collect()))
+    }
+}
+
+/// Duplicate elements in the array are collapsed into one
+impl<'buf, 'fds, E: Unmarshal<'buf, 'fds> + Ord> Unmarshal<'buf, 'fds> for BTreeSet<E> {
+    fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
+        Vec::unmarshal(ctx).map(|(bytes, elements)| (bytes, elements.into_iter().collect()))
+    }
+}
+
+macro_rules! smart_pointer_impls {
+    ($($ptr:ident),*) => {$(
+        impl<'buf, 'fds, E: Unmarshal<'buf, 'fds>> Unmarshal<'buf, 'fds> for $ptr<E> {
+            fn unmarshal(ctx: &mut UnmarshalContext<'fds, 'buf>) -> unmarshal::UnmarshalResult<Self> {
+                E::unmarshal(ctx).map(|(bytes, val)| (bytes, $ptr::new(val)))
+            }
+        }
+    )*};
+}
+smart_pointer_impls!(Box, Rc, Arc);
+
 #[derive(Debug)]
 pub struct Variant<'fds, 'buf> {
     pub(crate) sig: signature::Type,
@@ -10,7 +10,7 @@ pub fn pad_to_align(align_to: usize, buf: &mut Vec<u8>) {
     let padding_needed = align_to - (buf.len() % align_to);
     if padding_needed != align_to {
         buf.resize(buf.len() + padding_needed, 0);
-        debug_assert!(buf.len() % align_to == 0);
+        debug_assert!(buf.len().is_multiple_of(align_to));
     }
 }
 
@@ -39,18 +39,26 @@ pub fn marshal_unixfd(
     ctx: &mut crate::wire::marshal::MarshalContext,
 ) -> Result<(), MarshalError> {
     if let Some(fd) = i.get_raw_fd() {
-        let new_fd = nix::unistd::dup(fd).map_err(MarshalError::DupUnixFd)?;
-        ctx.fds.push(crate::wire::UnixFd::new(new_fd));
-
-        let idx = ctx.fds.len() - 1;
-        ctx.align_to(<crate::wire::UnixFd as crate::Signature>::alignment());
-        crate::wire::util::write_u32(idx as u32, ctx.byteorder, ctx.buf);
-        Ok(())
+        marshal_raw_fd(fd, ctx)
     } else {
         Err(MarshalError::EmptyUnixFd)
     }
 }
 
+/// dup() the fd, add the new fd to the message and write the index into the buffer
+pub fn marshal_raw_fd(
+    fd: std::os::unix::io::RawFd,
+    ctx: &mut crate::wire::marshal::MarshalContext,
+) -> Result<(), MarshalError> {
+    let new_fd = nix::unistd::dup(fd).map_err(MarshalError::DupUnixFd)?;
+    ctx.fds.push(crate::wire::UnixFd::new(new_fd));
+
+    let idx = ctx.fds.len() - 1;
+    ctx.align_to(<crate::wire::UnixFd as crate::Signature>::alignment());
+    crate::wire::util::write_u32(idx as u32, ctx.byteorder, ctx.buf);
+    Ok(())
+}
+
 pub fn insert_u16(byteorder: ByteOrder, val: u16, buf: &mut [u8]) {
     match byteorder {
         ByteOrder::LittleEndian => {
@@ -168,6 +176,30 @@ pub fn parse_u32(number: &[u8], byteorder: ByteOrder) -> UnmarshalResult<u32> {
     Ok((4, val))
 }
 
+/// Write the length of an array's content into the placeholder at `buf[pos..pos + 4]`, checking it against `MAX_ARRAY_SIZE`
+pub fn insert_array_len(
+    byteorder: ByteOrder,
+    len: usize,
+    buf: &mut [u8],
+    pos: usize,
+) -> Result<(), MarshalError> {
+    if len > crate::wire::MAX_ARRAY_SIZE {
+        return Err(MarshalError::ArrayTooBig(len));
+    }
+    insert_u32(byteorder, len as u32, &mut buf[pos..pos + 4]);
+    Ok(())
+}
+
+/// Parse the length of an array's content, checking it against `MAX_ARRAY_SIZE`
+pub fn parse_array_len(buf: &[u8], byteorder: ByteOrder) -> UnmarshalResult<usize> {
+    let (bytes, len) = parse_u32(buf, byteorder)?;
+    let len = len as usize;
+    if len > crate::wire::MAX_ARRAY_SIZE {
+        return Err(UnmarshalError::ArrayTooBig(len));
+    }
+    Ok((bytes, len))
+}
+
 pub fn parse_u16(number: &[u8], byteorder: ByteOrder) -> UnmarshalResult<u16> {
     if number.len() < 2 {
         return Err(UnmarshalError::NotEnoughBytes);
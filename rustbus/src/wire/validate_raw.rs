@@ -135,10 +135,10 @@ pub fn validate_marshalled_container(
             let padding = util::align_offset(4, buf, offset).map_err(|err| (offset, err))?;
             let offset = offset + padding;
             let (_, bytes_in_array) =
-                util::parse_u32(&buf[offset..], byteorder).map_err(|err| (offset, err))?;
+                util::parse_array_len(&buf[offset..], byteorder).map_err(|err| (offset, err))?;
             let offset = offset + 4;
 
-            if buf[offset..].len() < bytes_in_array as usize {
+            if buf[offset..].len() < bytes_in_array {
                 return Err((offset, UnmarshalError::NotEnoughBytesForCollection));
             }
 
@@ -146,21 +146,21 @@ pub fn validate_marshalled_container(
                 .map_err(|err| (offset, err))?;
             let offset = offset + first_elem_padding;
 
-            if buf[offset..].len() < bytes_in_array as usize {
+            if buf[offset..].len() < bytes_in_array {
                 return Err((offset, UnmarshalError::NotEnoughBytesForCollection));
             }
 
             if elem_sig.bytes_always_valid() {
                 // bytes_always_valid() only returns true for types whose
                 // length is equal to their alignment
-                if bytes_in_array as usize % elem_sig.get_alignment() != 0 {
+                if !(bytes_in_array).is_multiple_of(elem_sig.get_alignment()) {
                     // there is not a whole number of elements in the array.
                     return Err((offset, UnmarshalError::NotEnoughBytes));
                 }
             } else {
                 let mut bytes_used_counter = 0;
-                let array_end = offset + bytes_in_array as usize;
-                while bytes_used_counter < bytes_in_array as usize {
+                let array_end = offset + bytes_in_array;
+                while bytes_used_counter < bytes_in_array {
                     let bytes_used = validate_marshalled(
                         byteorder,
                         offset + bytes_used_counter,
@@ -170,17 +170,17 @@ pub fn validate_marshalled_container(
                     bytes_used_counter += bytes_used;
                 }
             }
-            let total_bytes_used = padding + 4 + first_elem_padding + bytes_in_array as usize;
+            let total_bytes_used = padding + 4 + first_elem_padding + bytes_in_array;
             Ok(total_bytes_used)
         }
         signature::Container::Dict(key_sig, val_sig) => {
             let padding = util::align_offset(4, buf, offset).map_err(|err| (offset, err))?;
             let offset = offset + padding;
             let (_, bytes_in_dict) =
-                util::parse_u32(&buf[offset..], byteorder).map_err(|err| (offset, err))?;
+                util::parse_array_len(&buf[offset..], byteorder).map_err(|err| (offset, err))?;
             let offset = offset + 4;
 
-            if buf[offset..].len() < bytes_in_dict as usize {
+            if buf[offset..].len() < bytes_in_dict {
                 return Err((offset, UnmarshalError::NotEnoughBytesForCollection));
             }
 
@@ -188,15 +188,15 @@ pub fn validate_marshalled_container(
                 util::align_offset(8, buf, offset).map_err(|err| (offset, err))?;
             let offset = offset + before_elements_padding;
 
-            if buf[offset..].len() < bytes_in_dict as usize {
+            if buf[offset..].len() < bytes_in_dict {
                 return Err((offset, UnmarshalError::NotEnoughBytesForCollection));
             }
 
             // don't let the contents of the dict see anything beyond the dicts claimed end.
-            let buf_for_dict = &buf[..offset + bytes_in_dict as usize];
+            let buf_for_dict = &buf[..offset + bytes_in_dict];
 
             let mut bytes_used_counter = 0;
-            while bytes_used_counter < bytes_in_dict as usize {
+            while bytes_used_counter < bytes_in_dict {
                 let element_padding =
                     util::align_offset(8, buf_for_dict, offset + bytes_used_counter)
                         .map_err(|err| (offset + bytes_used_counter, err))?;
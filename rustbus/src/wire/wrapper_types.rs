@@ -13,6 +13,9 @@ impl<S: AsRef<str>> ObjectPath<S> {
     pub fn to_owned(&self) -> ObjectPath<String> {
         ObjectPath(self.as_ref().to_owned())
     }
+    pub fn into_inner(self) -> S {
+        self.0
+    }
 }
 impl<S: AsRef<str>> AsRef<str> for ObjectPath<S> {
     fn as_ref(&self) -> &str {
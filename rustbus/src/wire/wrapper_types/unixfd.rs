@@ -5,7 +5,8 @@ use crate::wire::marshal::MarshalContext;
 use crate::wire::unmarshal::UnmarshalContext;
 use crate::{Marshal, Signature, Unmarshal};
 
-use std::os::unix::io::RawFd;
+use std::fs::File;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
 use std::sync::atomic::AtomicI32;
 use std::sync::Arc;
 
@@ -47,11 +48,7 @@ impl UnixFdInner {
                 std::sync::atomic::Ordering::SeqCst,
             );
             //  If swapped_fd == fd then we did a sucessful swap and we actually took the value
-            if let Ok(taken_fd) = swapped_fd {
-                Some(taken_fd)
-            } else {
-                None
-            }
+            swapped_fd.ok()
         }
     }
 
@@ -85,12 +82,16 @@ impl UnixFdInner {
 /// "Ownership" as in responsibility of closing the FD works as follows:
 /// 1. You can call take_raw_fd(). At this point UnixFd releases ownership. You are now responsible of closing the FD.
 /// 1. You can call get_raw_fd(). This will not release ownership, UnixFd will still close it if no more references to it exist.
+/// 1. You can call take_owned_fd() or convert it into an `OwnedFd`. This releases ownership like take_raw_fd() but hands it to the `OwnedFd`.
 ///
 /// ## UnixFds and messages
 /// 1. When a UnixFd is **marshalled** rustbus will dup() the FD so that the message and the original UnixFd do not depend on each others lifetime. You are free to use
-/// or close the original one.
+///    or close the original one.
 /// 1. When a UnixFd is **unmarshalled** rustbus will **NOT** dup() the FD. This means if you call take_raw_fd(), it is gone from the message too! If you do not want this,
-/// you have to call dup() and then get_raw_fd() or take_raw_fd()
+///    you have to call dup() and then get_raw_fd() or take_raw_fd()
+///
+/// The std types `OwnedFd`, `BorrowedFd` and `File` can be marshalled directly and are dup()'ed the same way.
+/// Unmarshalling an `OwnedFd` also dup()'s the FD from the message.
 #[derive(Clone, Debug)]
 pub struct UnixFd(Arc<UnixFdInner>);
 impl UnixFd {
@@ -119,6 +120,38 @@ impl UnixFd {
     pub fn dup(&self) -> Result<Self, DupError> {
         self.0.dup().map(|new_inner| Self(Arc::new(new_inner)))
     }
+
+    /// Takes ownership of the FD like `take_raw_fd()` but returns it as an `OwnedFd`,
+    /// which can be turned into a `File` or similar without any unsafe code.
+    pub fn take_owned_fd(self) -> Option<OwnedFd> {
+        // SAFETY: After a successful take nobody else can get the fd from this UnixFd anymore,
+        // so the OwnedFd is the only owner
+        self.take_raw_fd()
+            .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    /// Duplicate the underlying FD into an `OwnedFd`. This UnixFd keeps its FD.
+    pub fn dup_owned_fd(&self) -> Result<OwnedFd, DupError> {
+        self.dup().map(|dup| {
+            dup.take_owned_fd()
+                .expect("A fresh dup can not be taken already")
+        })
+    }
+}
+
+/// The UnixFd takes over the responsibility of closing the FD.
+impl From<OwnedFd> for UnixFd {
+    fn from(fd: OwnedFd) -> Self {
+        UnixFd::new(fd.into_raw_fd())
+    }
+}
+
+/// Same as `UnixFd::take_owned_fd()`. Fails with `DupError::AlreadyTaken` if the FD was already taken.
+impl std::convert::TryFrom<UnixFd> for OwnedFd {
+    type Error = DupError;
+    fn try_from(fd: UnixFd) -> Result<Self, Self::Error> {
+        fd.take_owned_fd().ok_or(DupError::AlreadyTaken)
+    }
 }
 /// Allow for the comparison of `UnixFd` even after the `RawFd`
 /// has been taken, to see if they originally referred to the same thing.
@@ -175,17 +208,38 @@ impl Signature for &dyn std::os::unix::io::AsRawFd {
 }
 impl Marshal for &dyn std::os::unix::io::AsRawFd {
     fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
-        let fd = self.as_raw_fd();
-        let new_fd = nix::unistd::dup(fd).map_err(MarshalError::DupUnixFd)?;
-        ctx.fds.push(UnixFd::new(new_fd));
-
-        let idx = ctx.fds.len() - 1;
-        ctx.align_to(Self::alignment());
-        crate::wire::util::write_u32(idx as u32, ctx.byteorder, ctx.buf);
-        Ok(())
+        crate::wire::util::marshal_raw_fd(self.as_raw_fd(), ctx)
     }
 }
 
+/// Implements Marshal for types that implement `AsFd`. The FD is dup()'ed like it is for `UnixFd`,
+/// so the original stays usable and owned by the caller.
+macro_rules! as_fd_impls {
+    ($($typ:ty),*) => {$(
+        impl Signature for $typ {
+            fn signature() -> crate::signature::Type {
+                UnixFd::signature()
+            }
+            fn alignment() -> usize {
+                UnixFd::alignment()
+            }
+            #[inline]
+            fn sig_str(s_buf: &mut SignatureBuffer) {
+                UnixFd::sig_str(s_buf)
+            }
+            fn has_sig(sig: &str) -> bool {
+                UnixFd::has_sig(sig)
+            }
+        }
+        impl Marshal for $typ {
+            fn marshal(&self, ctx: &mut MarshalContext) -> Result<(), MarshalError> {
+                crate::wire::util::marshal_raw_fd(self.as_fd().as_raw_fd(), ctx)
+            }
+        }
+    )*};
+}
+as_fd_impls!(BorrowedFd<'_>, OwnedFd, File);
+
 impl<'buf, 'fds> Unmarshal<'buf, 'fds> for UnixFd {
     fn unmarshal(
         ctx: &mut UnmarshalContext<'fds, 'buf>,
@@ -201,6 +255,21 @@ impl<'buf, 'fds> Unmarshal<'buf, 'fds> for UnixFd {
     }
 }
 
+/// Unmarshalling into an OwnedFd dup()'s the FD from the message, so the message keeps its own FD.
+/// If you want to move the FD out of the message instead, unmarshal a `UnixFd` and call `take_owned_fd()`.
+impl<'buf, 'fds> Unmarshal<'buf, 'fds> for OwnedFd {
+    fn unmarshal(
+        ctx: &mut UnmarshalContext<'fds, 'buf>,
+    ) -> crate::wire::unmarshal::UnmarshalResult<Self> {
+        let (bytes, fd) = UnixFd::unmarshal(ctx)?;
+        match fd.dup_owned_fd() {
+            Ok(fd) => Ok((bytes, fd)),
+            Err(DupError::AlreadyTaken) => Err(UnmarshalError::EmptyUnixFd),
+            Err(DupError::Nix(e)) => Err(UnmarshalError::DupUnixFd(e)),
+        }
+    }
+}
+
 #[test]
 fn test_fd_send() {
     let x = UnixFd::new(nix::unistd::dup(1).unwrap());
@@ -263,6 +332,60 @@ fn test_races_in_unixfd() {
     }
 }
 
+#[test]
+fn test_std_fd_types() {
+    use std::convert::TryFrom;
+    use std::io::{Read, Write};
+
+    let (read_end, write_end) = nix::unistd::pipe().unwrap();
+    let read_end = unsafe { File::from_raw_fd(read_end) };
+    let write_end = unsafe { OwnedFd::from_raw_fd(write_end) };
+
+    let mut body = crate::message_builder::MarshalledMessageBody::new();
+    body.push_param(&write_end).unwrap();
+    body.push_param(write_end.as_fd()).unwrap();
+    body.push_param(&read_end).unwrap();
+    // all fds got dup()'ed, so the originals can be closed
+    drop(write_end);
+
+    let mut parser = body.parser();
+    let owned: OwnedFd = parser.get().unwrap();
+    let mut file = File::from(owned);
+    file.write_all(b"owned").unwrap();
+    drop(file);
+
+    // the OwnedFd above was a dup, so the message still has its fd and it can be taken explicitly
+    let mut parser = body.parser();
+    let fd: UnixFd = parser.get().unwrap();
+    let mut file = File::from(fd.clone().take_owned_fd().unwrap());
+    assert!(fd.take_owned_fd().is_none());
+    file.write_all(b" taken").unwrap();
+    drop(file);
+
+    let fd: UnixFd = parser.get().unwrap();
+    let mut file = File::from(OwnedFd::try_from(fd).unwrap());
+    file.write_all(b" borrowed").unwrap();
+    drop(file);
+
+    let mut parser = body.parser();
+    let _: UnixFd = parser.get().unwrap();
+    assert_eq!(
+        parser.get::<OwnedFd>().unwrap_err(),
+        UnmarshalError::EmptyUnixFd
+    );
+
+    // drop the read end in the message so reading from the pipe ends
+    let mut read_end = read_end;
+    drop(body);
+    let mut content = String::new();
+    read_end.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "owned taken borrowed");
+
+    let fd = UnixFd::from(OwnedFd::from(read_end));
+    assert!(fd.dup_owned_fd().is_ok());
+    assert!(fd.get_raw_fd().is_some());
+}
+
 #[test]
 fn test_unixfd_dup() {
     let fd = UnixFd::new(nix::unistd::dup(1).unwrap());
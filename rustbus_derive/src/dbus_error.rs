@@ -0,0 +1,73 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Find the value of `#[dbus_error(key = "value")]` in the attributes
+fn attr_value(attrs: &[syn::Attribute], key: &str) -> Option<String> {
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("dbus_error")) {
+        let list = match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => list,
+            _ => panic!("dbus_error attributes need to look like #[dbus_error(name = \"...\")]"),
+        };
+        for nested in list.nested {
+            if let syn::NestedMeta::Meta(syn::Meta::NameValue(pair)) = nested {
+                if pair.path.is_ident(key) {
+                    match pair.lit {
+                        syn::Lit::Str(value) => return Some(value.value()),
+                        _ => panic!("The value of {} in dbus_error needs to be a string", key),
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+pub fn make_dbus_error_impl(ast: &syn::DeriveInput) -> TokenStream {
+    let ident = &ast.ident;
+    let (impl_gen, typ_gen, clause_gen) = ast.generics.split_for_impl();
+
+    let name = match &ast.data {
+        syn::Data::Struct(_) => {
+            let name = attr_value(&ast.attrs, "name").unwrap_or_else(|| {
+                panic!("Structs need a #[dbus_error(name = \"...\")] attribute to derive DBusError")
+            });
+            quote! { #name }
+        }
+        syn::Data::Enum(data) => {
+            let prefix = attr_value(&ast.attrs, "prefix");
+            let arms = data.variants.iter().map(|variant| {
+                let variant_ident = &variant.ident;
+                let name = attr_value(&variant.attrs, "name")
+                    .or_else(|| {
+                        prefix
+                            .as_ref()
+                            .map(|prefix| format!("{}.{}", prefix, variant_ident))
+                    })
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "Variant {} needs a #[dbus_error(name = \"...\")] attribute or the enum a #[dbus_error(prefix = \"...\")] attribute",
+                            variant_ident
+                        )
+                    });
+                quote! { #ident::#variant_ident { .. } => #name, }
+            });
+            quote! {
+                match self {
+                    #( #arms )*
+                }
+            }
+        }
+        _ => unimplemented!("DBusError can only be derived for structs and enums"),
+    };
+
+    quote! {
+        impl #impl_gen ::rustbus::DBusError for #ident #typ_gen #clause_gen {
+            fn error_name(&self) -> &str {
+                #name
+            }
+            fn error_message(&self) -> ::std::option::Option<::std::string::String> {
+                ::std::option::Option::Some(::std::string::ToString::to_string(self))
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+mod dbus_error;
 mod structs;
 mod variants;
 
@@ -43,3 +44,24 @@ pub fn derive_signature(input: proc_macro::TokenStream) -> proc_macro::TokenStre
         _ => unimplemented!("Nothing but structs can be derived on right now"),
     }
 }
+/// Derives `rustbus::DBusError`, so the type can be sent as error reply by the `DispatchConn`.
+///
+/// The error message is the `Display` output of the error, so the type needs to implement `Display`. The error names are
+/// set with attributes: `#[dbus_error(name = "...")]` on structs and enum variants, or `#[dbus_error(prefix = "...")]`
+/// on an enum to name every variant without its own name `<prefix>.<VariantName>`.
+///
+/// ```rust,ignore
+/// #[derive(Debug, DBusError)]
+/// #[dbus_error(prefix = "io.killing.spark.Error")]
+/// enum MyError {
+///     // io.killing.spark.Error.NotFound
+///     NotFound(String),
+///     #[dbus_error(name = "org.freedesktop.DBus.Error.AccessDenied")]
+///     AccessDenied,
+/// }
+/// ```
+#[proc_macro_derive(DBusError, attributes(dbus_error))]
+pub fn derive_dbus_error(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    dbus_error::make_dbus_error_impl(&ast).into()
+}
@@ -102,7 +102,7 @@ pub fn test_enum_derive() {
     }
 
     let v1 = Variant1::A("ABCD".into());
-    let v2 = Variant1::B("ABCD".into(), "EFGH".into());
+    let v2 = Variant1::B("ABCD", "EFGH".into());
     let v3 = Variant1::C {
         c1: "ABCD".into(),
         c2: "EFGH".into(),
@@ -163,3 +163,72 @@ pub fn test_enum_derive() {
         err
     );
 }
+
+#[test]
+fn test_derive_dbus_error() {
+    use rustbus::message_builder::MessageBuilder;
+    use rustbus::DBusError;
+
+    #[derive(Debug, DBusError)]
+    #[dbus_error(prefix = "io.killing.spark.Error")]
+    enum MyError {
+        NotFound(String),
+        #[dbus_error(name = "org.freedesktop.DBus.Error.AccessDenied")]
+        AccessDenied {
+            user: u32,
+        },
+        Busy,
+    }
+
+    impl std::fmt::Display for MyError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                MyError::NotFound(what) => write!(f, "{} was not found", what),
+                MyError::AccessDenied { user } => write!(f, "{} may not do that", user),
+                MyError::Busy => write!(f, "Try again later"),
+            }
+        }
+    }
+
+    #[derive(Debug, DBusError)]
+    #[dbus_error(name = "io.killing.spark.Error.Single")]
+    struct SingleError;
+
+    impl std::fmt::Display for SingleError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "Something went wrong")
+        }
+    }
+
+    let err = MyError::NotFound("The thing".to_owned());
+    assert_eq!(err.error_name(), "io.killing.spark.Error.NotFound");
+    assert_eq!(
+        err.error_message().as_deref(),
+        Some("The thing was not found")
+    );
+    assert_eq!(
+        MyError::AccessDenied { user: 1000 }.error_name(),
+        "org.freedesktop.DBus.Error.AccessDenied"
+    );
+    assert_eq!(MyError::Busy.error_name(), "io.killing.spark.Error.Busy");
+    assert_eq!(SingleError.error_name(), "io.killing.spark.Error.Single");
+
+    let mut call = MessageBuilder::new()
+        .call("Method")
+        .on("/io/killing/spark")
+        .at("io.killing.spark")
+        .build();
+    call.dynheader.serial = Some(42);
+    call.dynheader.sender = Some(":1.1".to_owned());
+    let reply = MyError::Busy.to_error_reply(&call.dynheader);
+    assert_eq!(reply.typ, rustbus::MessageType::Error);
+    assert_eq!(
+        reply.dynheader.error_name.as_deref(),
+        Some("io.killing.spark.Error.Busy")
+    );
+    assert_eq!(reply.dynheader.response_serial, Some(42));
+    assert_eq!(
+        reply.body.parser().get::<&str>().unwrap(),
+        "Try again later"
+    );
+}